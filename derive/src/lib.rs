@@ -0,0 +1,549 @@
+/*!
+derive macros for the `powerplatform-dataverse-service-client` crate
+
+The macros are re-exported by the client crate when its `derive` feature is enabled:
+`DataverseEntity` at the crate root, `ReadEntity` and `WriteEntity` in the `entity`
+module next to the traits of the same name
+
+# Examples
+```rust
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use powerplatform_dataverse_service_client::{
+    entity::{ReadEntity, WriteEntity},
+    reference::{Reference, ReferenceStruct},
+    select::Select,
+    DataverseEntity,
+};
+
+#[derive(Deserialize, Serialize, DataverseEntity, ReadEntity, WriteEntity)]
+#[dataverse(entity_set = "contacts")]
+struct Contact {
+    #[dataverse(key)]
+    contactid: Uuid,
+    #[serde(rename = "firstname")]
+    first_name: String,
+    #[dataverse(lookup = "parentcustomerid")]
+    #[serde(rename = "_parentcustomerid_value", skip_serializing)]
+    parent_customer: Option<Uuid>,
+}
+
+let contact = Contact {
+    contactid: Uuid::from_u128(1),
+    first_name: String::from("Testy"),
+    parent_customer: None,
+};
+
+assert_eq!(Contact::get_columns(), &["contactid", "firstname", "_parentcustomerid_value"]);
+assert_eq!(contact.get_reference(), ReferenceStruct::new("contacts", Uuid::from_u128(1)));
+```
+*/
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    meta::ParseNestedMeta, parenthesized, parse_macro_input, token, Attribute, Data, DeriveInput, Error, Fields,
+    Ident, Lit, LitStr, Result, Token,
+};
+
+/**
+Implements `Select` and `Reference` for a struct with named fields
+
+The struct needs the entity set name of its table and exactly one field holding the
+primary id, which has to be a `Uuid`. Leaving out either one is a compile error:
+
+```compile_fail
+use uuid::Uuid;
+use powerplatform_dataverse_service_client::DataverseEntity;
+
+#[derive(DataverseEntity)]
+#[dataverse(entity_set = "contacts")]
+struct Contact {
+    contactid: Uuid,
+    firstname: String,
+}
+```
+
+The selected columns are the names serde uses for deserializing, so `#[serde(rename = "...")]`,
+`#[serde(rename_all = "...")]` and `#[serde(skip)]` are respected. This keeps the selected
+columns and the deserialized fields from drifting apart. The following attributes are supported:
+
+- `#[dataverse(entity_set = "...")]` on the struct sets the entity set used for the reference
+- `#[dataverse(key)]` marks the field holding the primary id
+- `#[dataverse(column = "...")]` states the column of the field, which has to match its serde name
+- `#[dataverse(lookup = "...")]` states the lookup of the field, whose serde name has to be its
+  value property, e.g. `_parentcustomerid_value`
+- `#[dataverse(skip)]` leaves the field out of the selected columns, e.g. for expanded records
+
+A `column` or `lookup` that differs from the serde name is a compile error, as is a field with
+`#[serde(flatten)]` that is not skipped:
+
+```compile_fail
+use serde::Deserialize;
+use uuid::Uuid;
+use powerplatform_dataverse_service_client::DataverseEntity;
+
+#[derive(Deserialize, DataverseEntity)]
+#[dataverse(entity_set = "contacts")]
+struct Contact {
+    #[dataverse(key)]
+    contactid: Uuid,
+    #[dataverse(lookup = "parentcustomerid")]
+    parent_customer: Option<Uuid>,
+}
+```
+*/
+#[proc_macro_derive(DataverseEntity, attributes(dataverse))]
+pub fn derive_dataverse_entity(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    Entity::parse(&input)
+        .map(|entity| entity.expand(&input))
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Implements `ReadEntity` for a struct that implements `Deserialize` and `Select`
+#[proc_macro_derive(ReadEntity)]
+pub fn derive_read_entity(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    implement_marker(&input, quote!(::powerplatform_dataverse_service_client::entity::ReadEntity)).into()
+}
+
+/// Implements `WriteEntity` for a struct that implements `Serialize` and `Reference` or `Addressable`
+#[proc_macro_derive(WriteEntity)]
+pub fn derive_write_entity(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    implement_marker(&input, quote!(::powerplatform_dataverse_service_client::entity::WriteEntity)).into()
+}
+
+fn implement_marker(input: &DeriveInput, trait_path: TokenStream2) -> TokenStream2 {
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics #trait_path for #name #type_generics #where_clause {}
+    }
+}
+
+/// the table information of a struct deriving `DataverseEntity`
+struct Entity {
+    entity_set: LitStr,
+    key: Ident,
+    columns: Vec<String>,
+}
+
+impl Entity {
+    fn parse(input: &DeriveInput) -> Result<Self> {
+        let fields = match &input.data {
+            Data::Struct(data) => match &data.fields {
+                Fields::Named(fields) => &fields.named,
+                _ => return Err(Error::new_spanned(&input.ident, "DataverseEntity can only be derived for structs with named fields")),
+            },
+            _ => return Err(Error::new_spanned(&input.ident, "DataverseEntity can only be derived for structs")),
+        };
+
+        let mut entity_set = None;
+        for attribute in input.attrs.iter().filter(|attribute| attribute.path().is_ident("dataverse")) {
+            attribute.parse_nested_meta(|meta| {
+                if meta.path.is_ident("entity_set") {
+                    entity_set = Some(meta.value()?.parse::<LitStr>()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("unsupported dataverse attribute on a struct, expected `entity_set = \"...\"`"))
+                }
+            })?;
+        }
+
+        let entity_set = entity_set.ok_or_else(|| {
+            Error::new_spanned(&input.ident, "missing `#[dataverse(entity_set = \"...\")]` on the struct")
+        })?;
+        let rename_rule = read_rename_rule(&input.attrs)?;
+
+        let mut key = None;
+        let mut columns = Vec::new();
+
+        for field in fields {
+            let ident = field.ident.as_ref().expect("named fields have an identifier");
+            let attributes = FieldAttributes::parse(&field.attrs)?;
+
+            if attributes.key {
+                if key.is_some() {
+                    return Err(Error::new_spanned(ident, "only one field can be marked with `#[dataverse(key)]`"));
+                }
+
+                key = Some(ident.clone());
+            }
+
+            if attributes.skip {
+                continue;
+            }
+
+            let serde_name = match attributes.serde_name {
+                Some(name) => name.value(),
+                None => {
+                    let name = ident.to_string();
+                    let name = name.trim_start_matches("r#");
+                    match rename_rule {
+                        Some(rule) => rule.apply(name),
+                        None => String::from(name),
+                    }
+                }
+            };
+
+            if attributes.flatten {
+                return Err(Error::new_spanned(
+                    ident,
+                    "the columns of a flattened field can't be selected, mark it with `#[dataverse(skip)]` if they are not read from dataverse",
+                ));
+            }
+
+            let expected = match (attributes.column, attributes.lookup) {
+                (Some(_), Some(lookup)) => {
+                    return Err(Error::new_spanned(lookup, "a field can't have both a `column` and a `lookup`"))
+                }
+                (Some(column), None) => Some((column.value(), column)),
+                (None, Some(lookup)) => Some((format!("_{}_value", lookup.value()), lookup)),
+                (None, None) => None,
+            };
+
+            if let Some((column, attribute)) = expected {
+                if column != serde_name {
+                    return Err(Error::new_spanned(
+                        attribute,
+                        format!(
+                            "the field is selected as `{}` but serde reads it as `{}`, add `#[serde(rename = \"{}\")]`",
+                            column, serde_name, column
+                        ),
+                    ));
+                }
+            }
+
+            let column = serde_name;
+            columns.push(column);
+        }
+
+        let key = key.ok_or_else(|| {
+            Error::new_spanned(&input.ident, "missing a field marked with `#[dataverse(key)]` that holds the primary id")
+        })?;
+
+        Ok(Self {
+            entity_set,
+            key,
+            columns,
+        })
+    }
+
+    fn expand(&self, input: &DeriveInput) -> TokenStream2 {
+        let name = &input.ident;
+        let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+        let Entity {
+            entity_set,
+            key,
+            columns,
+        } = self;
+
+        quote! {
+            impl #impl_generics ::powerplatform_dataverse_service_client::select::Select for #name #type_generics #where_clause {
+                fn get_columns() -> &'static [&'static str] {
+                    &[#(#columns),*]
+                }
+            }
+
+            impl #impl_generics ::powerplatform_dataverse_service_client::reference::Reference for #name #type_generics #where_clause {
+                fn get_reference(&self) -> ::powerplatform_dataverse_service_client::reference::ReferenceStruct {
+                    ::powerplatform_dataverse_service_client::reference::ReferenceStruct::new(#entity_set, self.#key)
+                }
+            }
+        }
+    }
+}
+
+/// the `dataverse` and `serde` attributes of a single field
+#[derive(Default)]
+struct FieldAttributes {
+    key: bool,
+    skip: bool,
+    flatten: bool,
+    column: Option<LitStr>,
+    lookup: Option<LitStr>,
+    serde_name: Option<LitStr>,
+}
+
+impl FieldAttributes {
+    fn parse(attributes: &[Attribute]) -> Result<Self> {
+        let mut result = Self::default();
+
+        for attribute in attributes {
+            if attribute.path().is_ident("dataverse") {
+                attribute.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("key") {
+                        result.key = true;
+                    } else if meta.path.is_ident("skip") {
+                        result.skip = true;
+                    } else if meta.path.is_ident("column") {
+                        result.column = Some(meta.value()?.parse()?);
+                    } else if meta.path.is_ident("lookup") {
+                        result.lookup = Some(meta.value()?.parse()?);
+                    } else {
+                        return Err(meta.error("unsupported dataverse attribute on a field, expected `key`, `skip`, `column = \"...\"` or `lookup = \"...\"`"));
+                    }
+
+                    Ok(())
+                })?;
+            } else if attribute.path().is_ident("serde") {
+                attribute.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("rename") {
+                        if let Some(name) = read_deserialize_name(&meta)? {
+                            result.serde_name = Some(name);
+                        }
+                    } else if meta.path.is_ident("skip") || meta.path.is_ident("skip_deserializing") {
+                        result.skip = true;
+                    } else if meta.path.is_ident("flatten") {
+                        result.flatten = true;
+                    } else {
+                        ignore_serde_value(&meta)?;
+                    }
+
+                    Ok(())
+                })?;
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// reads the `#[serde(rename_all = "...")]` rule of the struct
+fn read_rename_rule(attributes: &[Attribute]) -> Result<Option<RenameRule>> {
+    let mut rule = None;
+
+    for attribute in attributes.iter().filter(|attribute| attribute.path().is_ident("serde")) {
+        attribute.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename_all") {
+                if let Some(name) = read_deserialize_name(&meta)? {
+                    rule = Some(RenameRule::parse(&name)?);
+                }
+            } else {
+                ignore_serde_value(&meta)?;
+            }
+
+            Ok(())
+        })?;
+    }
+
+    Ok(rule)
+}
+
+/// reads the name of `rename = "..."` or `rename(deserialize = "...")`, as the columns are read from dataverse
+fn read_deserialize_name(meta: &ParseNestedMeta) -> Result<Option<LitStr>> {
+    if meta.input.peek(Token![=]) {
+        return meta.value()?.parse().map(Some);
+    }
+
+    let mut name = None;
+    meta.parse_nested_meta(|nested| {
+        let value = nested.value()?.parse::<LitStr>()?;
+        if nested.path.is_ident("deserialize") {
+            name = Some(value);
+        }
+
+        Ok(())
+    })?;
+
+    Ok(name)
+}
+
+/// consumes the value of a serde option that doesn't affect the column names
+fn ignore_serde_value(meta: &ParseNestedMeta) -> Result<()> {
+    if meta.input.peek(Token![=]) {
+        meta.value()?.parse::<Lit>()?;
+    } else if meta.input.peek(token::Paren) {
+        let content;
+        parenthesized!(content in meta.input);
+        content.parse::<TokenStream2>()?;
+    }
+
+    Ok(())
+}
+
+/// the naming conventions of `#[serde(rename_all = "...")]` applied to snake case field names
+#[derive(Clone, Copy)]
+enum RenameRule {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    fn parse(name: &LitStr) -> Result<Self> {
+        match name.value().as_str() {
+            "lowercase" => Ok(RenameRule::Lower),
+            "UPPERCASE" => Ok(RenameRule::Upper),
+            "PascalCase" => Ok(RenameRule::Pascal),
+            "camelCase" => Ok(RenameRule::Camel),
+            "snake_case" => Ok(RenameRule::Snake),
+            "SCREAMING_SNAKE_CASE" => Ok(RenameRule::ScreamingSnake),
+            "kebab-case" => Ok(RenameRule::Kebab),
+            "SCREAMING-KEBAB-CASE" => Ok(RenameRule::ScreamingKebab),
+            _ => Err(Error::new_spanned(name, "unknown rename rule for `rename_all`")),
+        }
+    }
+
+    fn apply(self, field: &str) -> String {
+        match self {
+            RenameRule::Lower | RenameRule::Snake => String::from(field),
+            RenameRule::Upper | RenameRule::ScreamingSnake => field.to_ascii_uppercase(),
+            RenameRule::Pascal => field
+                .split('_')
+                .map(|word| {
+                    let mut characters = word.chars();
+                    match characters.next() {
+                        Some(first) => first.to_ascii_uppercase().to_string() + characters.as_str(),
+                        None => String::new(),
+                    }
+                })
+                .collect(),
+            RenameRule::Camel => {
+                let pascal = RenameRule::Pascal.apply(field);
+                let mut characters = pascal.chars();
+                match characters.next() {
+                    Some(first) => first.to_ascii_lowercase().to_string() + characters.as_str(),
+                    None => pascal,
+                }
+            }
+            RenameRule::Kebab => field.replace('_', "-"),
+            RenameRule::ScreamingKebab => field.replace('_', "-").to_ascii_uppercase(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use syn::{parse_quote, DeriveInput};
+
+    use crate::Entity;
+
+    #[test]
+    fn columns_follow_serde_and_dataverse_attributes() {
+        let input: DeriveInput = parse_quote! {
+            #[derive(Deserialize)]
+            #[serde(rename_all = "lowercase")]
+            #[dataverse(entity_set = "accounts")]
+            struct Account {
+                #[dataverse(key)]
+                accountid: Uuid,
+                #[serde(rename(serialize = "name", deserialize = "name"), default)]
+                account_name: String,
+                #[dataverse(column = "revenue")]
+                #[serde(rename = "revenue", with = "revenue")]
+                revenue_in_cents: i64,
+                #[dataverse(lookup = "primarycontactid")]
+                #[serde(rename = "_primarycontactid_value")]
+                primary_contact: Option<Uuid>,
+                #[serde(skip)]
+                cache: Vec<u8>,
+                #[dataverse(skip)]
+                contacts: Vec<Contact>,
+                #[dataverse(skip)]
+                #[serde(flatten)]
+                address: Address,
+                r#type: i32,
+            }
+        };
+
+        let entity = Entity::parse(&input).unwrap();
+        assert_eq!(entity.entity_set.value(), "accounts");
+        assert_eq!(entity.key, "accountid");
+        assert_eq!(
+            entity.columns,
+            vec!["accountid", "name", "revenue", "_primarycontactid_value", "type"]
+        );
+    }
+
+    #[test]
+    fn columns_differing_from_serde_names_are_errors() {
+        let with_column: DeriveInput = parse_quote! {
+            #[dataverse(entity_set = "accounts")]
+            struct Account {
+                #[dataverse(key)]
+                accountid: Uuid,
+                #[dataverse(column = "revenue")]
+                revenue_in_cents: i64,
+            }
+        };
+        let with_lookup: DeriveInput = parse_quote! {
+            #[dataverse(entity_set = "accounts")]
+            struct Account {
+                #[dataverse(key)]
+                accountid: Uuid,
+                #[dataverse(lookup = "primarycontactid")]
+                primary_contact: Option<Uuid>,
+            }
+        };
+        let with_flatten: DeriveInput = parse_quote! {
+            #[dataverse(entity_set = "accounts")]
+            struct Account {
+                #[dataverse(key)]
+                accountid: Uuid,
+                #[serde(flatten)]
+                address: Address,
+            }
+        };
+
+        assert!(Entity::parse(&with_column).err().unwrap().to_string().contains("serde reads it as `revenue_in_cents`"));
+        assert!(Entity::parse(&with_lookup).err().unwrap().to_string().contains("#[serde(rename = \"_primarycontactid_value\")]"));
+        assert!(Entity::parse(&with_flatten).err().unwrap().to_string().contains("flattened"));
+    }
+
+    #[test]
+    fn rename_rules_are_applied_to_field_names() {
+        let input: DeriveInput = parse_quote! {
+            #[serde(rename_all(serialize = "snake_case", deserialize = "camelCase"))]
+            #[dataverse(entity_set = "msdyn_projects")]
+            struct Project {
+                #[dataverse(key)]
+                msdyn_projectid: Uuid,
+                msdyn_subject: String,
+            }
+        };
+
+        let entity = Entity::parse(&input).unwrap();
+        assert_eq!(entity.columns, vec!["msdynProjectid", "msdynSubject"]);
+    }
+
+    #[test]
+    fn missing_key_and_entity_set_are_errors() {
+        let without_key: DeriveInput = parse_quote! {
+            #[dataverse(entity_set = "contacts")]
+            struct Contact {
+                contactid: Uuid,
+            }
+        };
+        let without_entity_set: DeriveInput = parse_quote! {
+            struct Contact {
+                #[dataverse(key)]
+                contactid: Uuid,
+            }
+        };
+        let with_two_keys: DeriveInput = parse_quote! {
+            #[dataverse(entity_set = "contacts")]
+            struct Contact {
+                #[dataverse(key)]
+                contactid: Uuid,
+                #[dataverse(key)]
+                parentcontactid: Uuid,
+            }
+        };
+
+        assert!(Entity::parse(&without_key).err().unwrap().to_string().contains("#[dataverse(key)]"));
+        assert!(Entity::parse(&without_entity_set).err().unwrap().to_string().contains("entity_set"));
+        assert!(Entity::parse(&with_two_keys).err().unwrap().to_string().contains("only one field"));
+    }
+}
@@ -1,45 +1,611 @@
-use serde::{Serialize, ser::SerializeMap};
+/*!
+Module for executing Microsoft Dataverse actions and functions
+
+Actions (e.g. `WinOpportunity`, `QualifyLead` or custom APIs) are implemented as
+structs that serialize into the parameters of the action and implement `DataverseAction`.
+Functions (e.g. `WhoAmI` or `RetrieveTotalRecordCount`) implement `DataverseFunction`
+and provide their parameters as values that are rendered into the url.
+
+Both can be unbound or bound to a record or to a table, see `Binding`
+
+# Examples
+```rust
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use powerplatform_dataverse_service_client::{
+    action::{Binding, DataverseAction},
+    client::Client,
+    result::Result,
+};
+
+#[derive(Serialize)]
+struct WinOpportunity {
+    #[serde(rename = "OpportunityClose")]
+    opportunity_close: OpportunityClose,
+    #[serde(rename = "Status")]
+    status: i32,
+}
+
+#[derive(Serialize)]
+struct OpportunityClose {
+    subject: String,
+    #[serde(rename = "opportunityid@odata.bind")]
+    opportunity: String,
+}
+
+impl DataverseAction for WinOpportunity {
+    type Response = ();
+
+    fn name(&self) -> &str {
+        "WinOpportunity"
+    }
+}
+
+async fn test() -> Result<()> {
+    let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+    client.execute_action(&WinOpportunity {
+        opportunity_close: OpportunityClose {
+            subject: String::from("Won the deal"),
+            opportunity: format!("/opportunities({})", Uuid::new_v4()),
+        },
+        status: 3,
+    }).await
+}
+```
+*/
+
+use std::fmt::Display;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize, ser::SerializeMap};
+use uuid::Uuid;
+
+use crate::{
+    query::attribute::Attribute,
+    reference::{encode_path, KeyLiteral, KeyReference},
+};
+
+/**
+Describes what an action or function is bound to
+*/
+#[derive(Clone, Debug, PartialEq)]
+pub enum Binding {
+    /// The operation is not bound and called directly below the Web-API root
+    Unbound,
+
+    /// The operation is bound to the given record
+    Record(KeyReference),
+
+    /// The operation is bound to the given table (entity set)
+    EntitySet(&'static str),
+}
+
+impl Binding {
+    /// renders the url path of the operation with the given name relative to the Web-API root
+    pub(crate) fn path(&self, name: &str) -> String {
+        match self {
+            Binding::Unbound => name.to_string(),
+            Binding::Record(reference) => format!("{}/Microsoft.Dynamics.CRM.{}", reference, name),
+            Binding::EntitySet(entity_set) => format!("{}/Microsoft.Dynamics.CRM.{}", entity_set, name),
+        }
+    }
+}
+
+/**
+trait for requests that execute a Microsoft Dataverse action
+
+The implementing struct is serialized into the json body of the request, so its fields
+are the parameters of the action. The response of the action is deserialized into
+`Response`; use `()` for actions that don't return anything
+
+Use `Client::execute_action(...)` or `Batch::execute_action(...)` to execute it
+*/
+pub trait DataverseAction: Serialize {
+    /// the type the response of the action is deserialized into
+    type Response: DeserializeOwned;
+
+    /// the unique name of the action, e.g. `WinOpportunity` or `new_ApproveInvoice`
+    fn name(&self) -> &str;
+
+    /// what the action is bound to (default: unbound)
+    fn binding(&self) -> Binding {
+        Binding::Unbound
+    }
+}
+
+/**
+A parameter value of a Microsoft Dataverse function
+*/
+#[derive(Clone, Debug, PartialEq)]
+pub enum Parameter {
+    /// A primitive value like a string, number or id
+    Value(Attribute),
+
+    /// A reference to a record, rendered as `{"@odata.id":"accounts(...)"}`
+    Record(KeyReference),
+
+    /// A literal that is rendered as it is, e.g. an enum value like
+    /// `Microsoft.Dynamics.CRM.EntityFilters'Entity'`
+    Literal(String),
+}
+
+impl From<Attribute> for Parameter {
+    fn from(value: Attribute) -> Self {
+        Parameter::Value(value)
+    }
+}
+
+impl From<KeyReference> for Parameter {
+    fn from(reference: KeyReference) -> Self {
+        Parameter::Record(reference)
+    }
+}
+
+impl Display for Parameter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Parameter::Value(value) => f.write_fmt(format_args!("{}", KeyLiteral(value))),
+            Parameter::Record(reference) => f.write_str(&encode_path(
+                &serde_json::json!({ "@odata.id": reference.to_string() }).to_string(),
+            )),
+            Parameter::Literal(literal) => f.write_str(&encode_path(literal)),
+        }
+    }
+}
+
+/**
+trait for requests that call a Microsoft Dataverse function
+
+Functions don't change data and are called with a `GET` request. Their parameters are
+rendered into the url with parameter aliases, e.g.
+`RetrieveTotalRecordCount(EntityNames=@p1)?@p1=["account"]`
+
+Use `Client::call_function(...)` to call it
+
+# Examples
+```rust
+use serde::Deserialize;
 use uuid::Uuid;
+use powerplatform_dataverse_service_client::{
+    action::{DataverseFunction, Parameter},
+    query::attribute::Attribute,
+};
+
+struct GetTimeZoneCodeByLocalizedName {
+    name: String,
+    locale_id: i64,
+}
+
+#[derive(Deserialize)]
+struct TimeZoneCode {
+    #[serde(rename = "TimeZoneCode")]
+    code: i32,
+}
+
+impl DataverseFunction for GetTimeZoneCodeByLocalizedName {
+    type Response = TimeZoneCode;
+
+    fn name(&self) -> &str {
+        "GetTimeZoneCodeByLocalizedName"
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, Parameter)> {
+        vec![
+            ("LocalizedStandardName", Attribute::String(self.name.clone()).into()),
+            ("LocaleId", Attribute::Integer(self.locale_id).into()),
+        ]
+    }
+}
+```
+*/
+pub trait DataverseFunction {
+    /// the type the response of the function is deserialized into
+    type Response: DeserializeOwned;
+
+    /// the unique name of the function, e.g. `WhoAmI` or `new_CalculateDiscount`
+    fn name(&self) -> &str;
+
+    /// what the function is bound to (default: unbound)
+    fn binding(&self) -> Binding {
+        Binding::Unbound
+    }
+
+    /// the names and values of the parameters of the function (default: none)
+    fn parameters(&self) -> Vec<(&'static str, Parameter)> {
+        Vec::new()
+    }
+}
+
+/// renders the url path of the function call relative to the Web-API root
+pub(crate) fn function_path<F: DataverseFunction + ?Sized>(function: &F) -> String {
+    let parameters = function.parameters();
+    let mut path = function.binding().path(function.name());
+    let mut aliases = String::new();
 
-/// Represents a request to execute the Merge action in Dataverse
-#[derive(Debug, Serialize)]
-pub struct MergeRequest<'a> {
+    path.push('(');
+    for (index, (name, value)) in parameters.iter().enumerate() {
+        if index > 0 {
+            path.push(',');
+            aliases.push('&');
+        }
+
+        path.push_str(&format!("{}=@p{}", name, index + 1));
+        aliases.push_str(&format!("@p{}={}", index + 1, value));
+    }
+    path.push(')');
+
+    if !aliases.is_empty() {
+        path.push('?');
+        path.push_str(&aliases);
+    }
+
+    path
+}
+
+/**
+The `WhoAmI` function that returns the ids of the calling user
+
+See `Client::who_am_i()`
+*/
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WhoAmI;
+
+/// The ids of the calling user, its business unit and its organization
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct WhoAmIResponse {
+    #[serde(rename = "UserId")]
+    pub user_id: Uuid,
+    #[serde(rename = "BusinessUnitId")]
+    pub business_unit_id: Uuid,
+    #[serde(rename = "OrganizationId")]
+    pub organization_id: Uuid,
+}
+
+impl DataverseFunction for WhoAmI {
+    type Response = WhoAmIResponse;
+
+    fn name(&self) -> &str {
+        "WhoAmI"
+    }
+}
+
+/**
+The `RetrieveVersion` function that returns the version of the dataverse environment
+
+See `Client::retrieve_version()`
+*/
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RetrieveVersion;
+
+/// The version of the dataverse environment, e.g. `9.2.24044.196`
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct RetrieveVersionResponse {
+    #[serde(rename = "Version")]
+    pub version: String,
+}
+
+impl DataverseFunction for RetrieveVersion {
+    type Response = RetrieveVersionResponse;
+
+    fn name(&self) -> &str {
+        "RetrieveVersion"
+    }
+}
+
+/**
+The `RetrieveTotalRecordCount` function that returns the approximate number of records of whole tables
+
+The counts are taken from a snapshot dataverse refreshes every few hours, so they are
+fast for large tables but don't include recent changes. See `Client::retrieve_total_record_count(...)`
+*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetrieveTotalRecordCount {
+    /// the logical names of the tables, e.g. `account`
+    pub entity_names: Vec<String>,
+}
+
+/// The approximate number of records per table
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct RetrieveTotalRecordCountResponse {
+    #[serde(rename = "EntityRecordCountCollection")]
+    pub entity_record_count_collection: EntityRecordCountCollection,
+}
+
+/// The logical names of the tables and their number of records at the same positions
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct EntityRecordCountCollection {
+    #[serde(rename = "Keys")]
+    pub keys: Vec<String>,
+    #[serde(rename = "Values")]
+    pub values: Vec<i64>,
+}
+
+impl DataverseFunction for RetrieveTotalRecordCount {
+    type Response = RetrieveTotalRecordCountResponse;
+
+    fn name(&self) -> &str {
+        "RetrieveTotalRecordCount"
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, Parameter)> {
+        vec![("EntityNames", Parameter::Literal(serde_json::json!(self.entity_names).to_string()))]
+    }
+}
+
+/**
+The tables that support the Merge action
+*/
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MergeEntity {
+    Account,
+    Contact,
+    Lead,
+    Incident,
+}
+
+impl MergeEntity {
+    /**
+    Finds the table for the given logical name (`account`) or entity set name (`accounts`)
+
+    Returns `None` for tables that don't support the Merge action
+    */
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "account" | "accounts" => Some(MergeEntity::Account),
+            "contact" | "contacts" => Some(MergeEntity::Contact),
+            "lead" | "leads" => Some(MergeEntity::Lead),
+            "incident" | "incidents" => Some(MergeEntity::Incident),
+            _ => None,
+        }
+    }
+
+    /// returns the logical name of the table, e.g. `account`
+    pub fn logical_name(&self) -> &'static str {
+        match self {
+            MergeEntity::Account => "account",
+            MergeEntity::Contact => "contact",
+            MergeEntity::Lead => "lead",
+            MergeEntity::Incident => "incident",
+        }
+    }
+
+    /// returns the name of the primary id column of the table, e.g. `accountid`
+    pub fn primary_id(&self) -> &'static str {
+        match self {
+            MergeEntity::Account => "accountid",
+            MergeEntity::Contact => "contactid",
+            MergeEntity::Lead => "leadid",
+            MergeEntity::Incident => "incidentid",
+        }
+    }
+}
+
+/**
+Represents a request to execute the Merge action in Dataverse
+
+The subordinate record is merged into the target record and deactivated afterwards.
+The column values in `update_content` are written to the target record, which allows
+moving chosen values from the subordinate to the target
+
+# Examples
+```rust
+use serde_json::{json, Map, Value};
+use uuid::Uuid;
+use powerplatform_dataverse_service_client::{
+    action::{MergeEntity, MergeRequest},
+    client::Client,
+    result::Result,
+};
+
+async fn test(target: Uuid, subordinate: Uuid) -> Result<()> {
+    let mut update_content = Map::new();
+    update_content.insert(String::from("telephone1"), json!("+49 123 456789"));
+
+    let merge = MergeRequest::new(MergeEntity::Account, target, subordinate)
+        .perform_parenting_checks(true)
+        .update_content(update_content);
+
+    let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+    client.execute_action(&merge).await
+}
+```
+*/
+#[derive(Clone, Debug, Serialize)]
+pub struct MergeRequest {
     #[serde(rename = "Target")]
-    pub target: EntityReference<'a>,
+    pub target: EntityReference,
     #[serde(rename = "Subordinate")]
-    pub subordinate: EntityReference<'a>,
+    pub subordinate: EntityReference,
+    #[serde(rename = "UpdateContent")]
+    pub update_content: UpdateContent,
     #[serde(rename = "PerformParentingChecks")]
     pub check_parents: bool,
 }
 
-impl<'a> MergeRequest<'a> {
-    pub fn new(entity_name: &'a str, target: Uuid, subordinate: Uuid, check_parents: bool) -> Self {
+impl MergeRequest {
+    /// Creates a request that merges the subordinate into the target without parenting checks
+    pub fn new(entity: MergeEntity, target: Uuid, subordinate: Uuid) -> Self {
         Self {
-            target: EntityReference {
-                entity_name,
-                entity_id: target,
-            },
-            subordinate: EntityReference {
-                entity_name,
-                entity_id: subordinate,
-            },
-            check_parents
+            target: EntityReference { entity, entity_id: target },
+            subordinate: EntityReference { entity, entity_id: subordinate },
+            update_content: UpdateContent { entity, values: serde_json::Map::new() },
+            check_parents: false,
         }
     }
+
+    /// checks whether the parent records of target and subordinate match before merging
+    pub fn perform_parenting_checks(mut self, check_parents: bool) -> Self {
+        self.check_parents = check_parents;
+        self
+    }
+
+    /// writes the given column values to the target record while merging
+    pub fn update_content(mut self, values: serde_json::Map<String, serde_json::Value>) -> Self {
+        self.update_content.values = values;
+        self
+    }
 }
 
-#[derive(Debug)]
-pub struct EntityReference<'a> {
-    pub entity_name: &'a str,
+impl DataverseAction for MergeRequest {
+    type Response = ();
+
+    fn name(&self) -> &str {
+        "Merge"
+    }
+}
+
+/// A reference to a record in the Merge action, serialized with its `@odata.type`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct EntityReference {
+    pub entity: MergeEntity,
     pub entity_id: Uuid,
 }
 
-impl<'a> Serialize for EntityReference<'a> {
+impl Serialize for EntityReference {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where S: serde::Serializer {
         let mut map = serializer.serialize_map(Some(2))?;
-        map.serialize_entry("@odata.type", &format!("Microsoft.Dynamics.CRM.{}", self.entity_name))?;
-        map.serialize_entry(&format!("{}id", self.entity_id), self.entity_id.as_hyphenated())?;
+        map.serialize_entry("@odata.type", &format!("Microsoft.Dynamics.CRM.{}", self.entity.logical_name()))?;
+        map.serialize_entry(self.entity.primary_id(), self.entity_id.as_hyphenated())?;
         map.end()
     }
-}
\ No newline at end of file
+}
+
+/// The column values the Merge action writes to the target record
+#[derive(Clone, Debug, PartialEq)]
+pub struct UpdateContent {
+    pub entity: MergeEntity,
+    pub values: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Serialize for UpdateContent {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where S: serde::Serializer {
+        let mut map = serializer.serialize_map(Some(self.values.len() + 1))?;
+        map.serialize_entry("@odata.type", &format!("Microsoft.Dynamics.CRM.{}", self.entity.logical_name()))?;
+
+        for (column, value) in &self.values {
+            map.serialize_entry(column, value)?;
+        }
+
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::{
+        action::{function_path, Binding, DataverseFunction, MergeEntity, MergeRequest, Parameter, RetrieveTotalRecordCount},
+        query::attribute::Attribute,
+        reference::KeyReference,
+    };
+
+    struct CalculateRollupField {
+        target: KeyReference,
+        field_name: &'static str,
+    }
+
+    impl DataverseFunction for CalculateRollupField {
+        type Response = ();
+
+        fn name(&self) -> &str {
+            "CalculateRollupField"
+        }
+
+        fn parameters(&self) -> Vec<(&'static str, Parameter)> {
+            vec![
+                ("Target", self.target.clone().into()),
+                ("FieldName", Attribute::String(String::from(self.field_name)).into()),
+            ]
+        }
+    }
+
+    struct RetrieveUserQueues;
+
+    impl DataverseFunction for RetrieveUserQueues {
+        type Response = ();
+
+        fn name(&self) -> &str {
+            "RetrieveUserQueues"
+        }
+
+        fn binding(&self) -> Binding {
+            Binding::Record(KeyReference::id("systemusers", Uuid::nil()))
+        }
+    }
+
+    #[test]
+    fn renders_parameter_aliases() {
+        let function = CalculateRollupField {
+            target: KeyReference::id("accounts", Uuid::nil()),
+            field_name: "new_total's",
+        };
+
+        assert_eq!(
+            function_path(&function),
+            "CalculateRollupField(Target=@p1,FieldName=@p2)?@p1=%7B%22%40odata.id%22%3A%22accounts%2800000000-0000-0000-0000-000000000000%29%22%7D&@p2='new_total''s'"
+        );
+    }
+
+    #[test]
+    fn renders_bound_functions() {
+        assert_eq!(
+            function_path(&RetrieveUserQueues),
+            "systemusers(00000000-0000-0000-0000-000000000000)/Microsoft.Dynamics.CRM.RetrieveUserQueues()"
+        );
+    }
+
+    #[test]
+    fn renders_literal_parameters() {
+        let function = RetrieveTotalRecordCount {
+            entity_names: vec![String::from("account"), String::from("contact")],
+        };
+
+        assert_eq!(
+            function_path(&function),
+            "RetrieveTotalRecordCount(EntityNames=@p1)?@p1=%5B%22account%22%2C%22contact%22%5D"
+        );
+    }
+
+    #[test]
+    fn serializes_merge_requests() {
+        let merge = MergeRequest::new(MergeEntity::Contact, Uuid::from_u128(1), Uuid::from_u128(2));
+        assert_eq!(
+            serde_json::to_string(&merge).unwrap(),
+            concat!(
+                r#"{"Target":{"@odata.type":"Microsoft.Dynamics.CRM.contact","contactid":"00000000-0000-0000-0000-000000000001"},"#,
+                r#""Subordinate":{"@odata.type":"Microsoft.Dynamics.CRM.contact","contactid":"00000000-0000-0000-0000-000000000002"},"#,
+                r#""UpdateContent":{"@odata.type":"Microsoft.Dynamics.CRM.contact"},"#,
+                r#""PerformParentingChecks":false}"#
+            )
+        );
+    }
+
+    #[test]
+    fn serializes_merge_requests_with_update_content() {
+        let mut values = serde_json::Map::new();
+        values.insert(String::from("description"), serde_json::json!("merged"));
+
+        let merge = MergeRequest::new(MergeEntity::Incident, Uuid::from_u128(1), Uuid::from_u128(2))
+            .perform_parenting_checks(true)
+            .update_content(values);
+
+        assert_eq!(
+            serde_json::to_string(&merge).unwrap(),
+            concat!(
+                r#"{"Target":{"@odata.type":"Microsoft.Dynamics.CRM.incident","incidentid":"00000000-0000-0000-0000-000000000001"},"#,
+                r#""Subordinate":{"@odata.type":"Microsoft.Dynamics.CRM.incident","incidentid":"00000000-0000-0000-0000-000000000002"},"#,
+                r#""UpdateContent":{"@odata.type":"Microsoft.Dynamics.CRM.incident","description":"merged"},"#,
+                r#""PerformParentingChecks":true}"#
+            )
+        );
+    }
+
+    #[test]
+    fn finds_merge_entities() {
+        assert_eq!(MergeEntity::from_name("accounts"), Some(MergeEntity::Account));
+        assert_eq!(MergeEntity::from_name("Lead"), Some(MergeEntity::Lead));
+        assert_eq!(MergeEntity::from_name("opportunity"), None);
+    }
+}
@@ -10,8 +10,9 @@ use tokio::sync::Mutex;
 
 use super::Authenticate;
 use crate::{
-    error::DataverseError,
+    error::{DataverseError, ErrorKind},
     result::{IntoDataverseResult, Result},
+    retry::RetryPolicy,
 };
 
 /**
@@ -25,6 +26,7 @@ pub struct ClientSecretAuth {
     login_url: String,
     login_data: HashMap<&'static str, String>,
     token_info: Mutex<Option<TokenInfo>>,
+    retry_policy: RetryPolicy,
 }
 
 impl ClientSecretAuth {
@@ -46,8 +48,20 @@ impl ClientSecretAuth {
             login_url,
             login_data: build_login_data(client_id, client_secret, scope),
             token_info: Mutex::new(None),
+            retry_policy: RetryPolicy::default(),
         }
     }
+
+    /**
+    Replaces the retry policy used when a token is requested from the login endpoint
+
+    Token requests are always treated as idempotent, so transient failures are retried
+    regardless of `RetryPolicy::retry_non_idempotent`
+    */
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
 }
 
 #[async_trait]
@@ -62,25 +76,27 @@ impl Authenticate for ClientSecretAuth {
         }
 
         let response = self
-            .http_client
-            .post(&self.login_url)
-            .form(&self.login_data)
-            .send()
-            .await
-            .into_dataverse_result()?;
+            .retry_policy
+            .send(true, || async {
+                Ok(self.http_client.post(&self.login_url).form(&self.login_data))
+            })
+            .await?;
 
         if response.status().is_client_error() || response.status().is_server_error() {
-            let error_message = response
-                .text()
+            return Err(DataverseError::from_response(response)
                 .await
-                .unwrap_or_else(|_| String::from("no error details provided from server"));
-            return Err(DataverseError::new(error_message));
+                .into_kind(ErrorKind::Authentication));
         }
 
         let content = response.bytes().await.into_dataverse_result()?;
         let mut result: TokenResult =
             serde_json::from_slice(content.as_ref()).into_dataverse_result()?;
-        let key = Arc::from(result.access_token.take().unwrap());
+        let key = Arc::from(result.access_token.take().ok_or_else(|| {
+            DataverseError::with_kind(
+                ErrorKind::Authentication,
+                String::from("the login endpoint provided no access token"),
+            )
+        })?);
 
         *token_info = Some(TokenInfo {
             key: Arc::clone(&key),
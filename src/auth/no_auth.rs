@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use async_trait::async_trait;
 
-use crate::{result::Result, error::DataverseError};
+use crate::{result::Result, error::{DataverseError, ErrorKind}};
 use super::Authenticate;
 
 /**
@@ -16,6 +16,6 @@ pub struct NoAuth {}
 #[async_trait]
 impl Authenticate for NoAuth {
     async fn get_valid_token(&self) -> Result<Arc<String>> {
-        Err(DataverseError::new(String::from("No authentication method selected. This is here for testing purposes. please select another auth method")))
+        Err(DataverseError::with_kind(ErrorKind::Authentication, String::from("No authentication method selected. This is here for testing purposes. please select another auth method")))
     }
 }
\ No newline at end of file
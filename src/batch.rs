@@ -1,11 +1,14 @@
-use std::fmt::{Display, Write};
+use std::fmt::Display;
 
+use serde::Serialize;
 use uuid::Uuid;
 
 use crate::{
-    client::VERSION,
-    entity::WriteEntity,
-    reference::Reference,
+    action::DataverseAction,
+    client::{Column, ColumnValue, VERSION},
+    entity::{UpsertMode, WriteEntity},
+    options::RequestOptions,
+    reference::{encode_path, Addressable, KeyReference},
     result::{IntoDataverseResult, Result},
 };
 
@@ -81,8 +84,8 @@ pub struct Batch {
     url: &'static str,
     batch_id: Uuid,
     dataset_id: Uuid,
-    payload: String,
-    next_content_id: u16,
+    parts: Vec<Part>,
+    options: RequestOptions,
 }
 
 impl Batch {
@@ -92,23 +95,59 @@ impl Batch {
             url,
             batch_id: Uuid::new_v4(),
             dataset_id: Uuid::new_v4(),
-            payload: String::new(),
-            next_content_id: 1,
+            parts: Vec::new(),
+            options: RequestOptions::default(),
         }
     }
 
     /**
-    Clears the batch of its contents and generates a new batch id and
-    a new dataset id
+    Clears the batch of its contents and its request options and generates
+    a new batch id and a new dataset id
 
     Note that this can be used to prevent frequent allocations by reusing
-    the `Batch` instance and its buffer
+    the `Batch` instance and its list of requests
     */
     pub fn reset(&mut self) {
         self.batch_id = Uuid::new_v4();
         self.dataset_id = Uuid::new_v4();
-        self.payload.clear();
-        self.next_content_id = 1;
+        self.parts.clear();
+        self.options = RequestOptions::default();
+    }
+
+    /**
+    Sets the request options for all operations that are added to this batch afterwards
+
+    Operations that were added before keep their options, so different operations of the
+    same batch can use different options
+
+    # Examples
+    ```rust
+    use uuid::Uuid;
+    use powerplatform_dataverse_service_client::{
+        batch::Batch,
+        options::RequestOptions,
+        reference::ReferenceStruct,
+        result::Result,
+    };
+
+    fn test() -> Result<()> {
+        let mut batch = Batch::new("https://instance.crm.dynamics.com/");
+        batch.set_options(RequestOptions::new().bypass_custom_plugin_execution(true));
+        batch.delete(&ReferenceStruct::new("contacts", Uuid::new_v4()))?;
+
+        batch.set_options(RequestOptions::new());
+        batch.delete(&ReferenceStruct::new("contacts", Uuid::new_v4()))?;
+        Ok(())
+    }
+    ```
+    */
+    pub fn set_options(&mut self, options: RequestOptions) {
+        self.options = options;
+    }
+
+    /// returns the request options for operations that are added to this batch
+    pub fn get_options(&self) -> &RequestOptions {
+        &self.options
     }
 
     /// returns the current batch id (This will change after a call to `reset()`)
@@ -123,7 +162,7 @@ impl Batch {
 
     /// returns the current count of requests in this batch
     pub fn get_count(&self) -> u16 {
-        self.next_content_id - 1
+        self.parts.len() as u16
     }
 
     /**
@@ -186,21 +225,10 @@ impl Batch {
     ```
     */
     pub fn create(&mut self, entity: &impl WriteEntity) -> Result<()> {
-        let reference = entity.get_reference();
+        let reference = entity.get_key_reference();
         let entity = serde_json::to_string(entity).into_dataverse_result()?;
 
-        write!(
-            self.payload,
-            "--changeset_{}\nContent-Type: application/http\nContent-Transfer-Encoding:binary\nContent-Id: {}\n\nPOST {}api/data/v{}/{} HTTP/1.1\nContent-Type: application/json;type=entry\n\n{}\n", 
-            self.dataset_id.as_simple(),
-            self.next_content_id,
-            self.url,
-            VERSION,
-            reference.entity_name,
-            entity
-        ).into_dataverse_result()?;
-
-        self.next_content_id += 1;
+        self.write_part(Part::new("POST", reference.entity_name).json(entity));
         Ok(())
     }
 
@@ -264,22 +292,63 @@ impl Batch {
     ```
     */
     pub fn update(&mut self, entity: &impl WriteEntity) -> Result<()> {
-        let reference = entity.get_reference();
+        self.update_if_match(entity, "*")
+    }
+
+    /**
+    Adds an Update Request for the given entity to this batch that only succeeds
+    if the record still has the given ETag
+
+    If the record was changed in the meantime the whole changeset fails with
+    `412 Precondition Failed`
+
+    Please note that this function can fail if a serde serialization error occurs
+
+    # Examples
+    ```rust
+    use uuid::Uuid;
+    use serde::Serialize;
+    use powerplatform_dataverse_service_client::{
+        batch::Batch,
+        entity::WriteEntity,
+        reference::{Reference, ReferenceStruct},
+        result::{Result, IntoDataverseResult}
+    };
+
+    fn test() -> Result<()> {
+        let testy_contact = Contact {
+            contactid: Uuid::parse_str("12345678-1234-1234-1234-123456789012").into_dataverse_result()?,
+            lastname: String::from("McTestface"),
+        };
+
+        let mut batch = Batch::new("https://instance.crm.dynamics.com/");
+        batch.update_if_match(&testy_contact, "W/\"1234567\"")?;
+        Ok(())
+    }
+
+    #[derive(Serialize)]
+    struct Contact {
+        contactid: Uuid,
+        lastname: String,
+    }
+
+    impl WriteEntity for Contact {}
+
+    impl Reference for Contact {
+        fn get_reference(&self) -> ReferenceStruct {
+            ReferenceStruct::new(
+                "contacts",
+                self.contactid,
+            )
+        }
+    }
+    ```
+    */
+    pub fn update_if_match(&mut self, entity: &impl WriteEntity, etag: &str) -> Result<()> {
+        let reference = entity.get_key_reference();
         let entity = serde_json::to_string(entity).into_dataverse_result()?;
 
-        write!(
-            self.payload,
-            "--changeset_{}\nContent-Type: application/http\nContent-Transfer-Encoding:binary\nContent-Id: {}\n\nPATCH {}api/data/v{}/{}({}) HTTP/1.1\nContent-Type: application/json;type=entry\nIf-Match: *\n\n{}\n", 
-            self.dataset_id.as_simple(),
-            self.next_content_id,
-            self.url,
-            VERSION,
-            reference.entity_name,
-            reference.entity_id,
-            entity
-        ).into_dataverse_result()?;
-
-        self.next_content_id += 1;
+        self.write_part(Part::new("PATCH", reference).header("If-Match", etag).json(entity));
         Ok(())
     }
 
@@ -343,22 +412,66 @@ impl Batch {
     ```
     */
     pub fn upsert(&mut self, entity: &impl WriteEntity) -> Result<()> {
-        let reference = entity.get_reference();
+        self.upsert_with_mode(entity, UpsertMode::CreateOrUpdate)
+    }
+
+    /**
+    Adds an Upsert Request with the given mode for the given entity to this batch
+
+    `UpsertMode::CreateOnly` fails the changeset if the record already exists and
+    `UpsertMode::UpdateOnly` fails it if the record doesn't exist
+
+    Please note that this function can fail if a serde serialization error occurs
+
+    # Examples
+    ```rust
+    use uuid::Uuid;
+    use serde::Serialize;
+    use powerplatform_dataverse_service_client::{
+        batch::Batch,
+        entity::{UpsertMode, WriteEntity},
+        reference::{Reference, ReferenceStruct},
+        result::{Result, IntoDataverseResult}
+    };
+
+    fn test() -> Result<()> {
+        let testy_contact = Contact {
+            contactid: Uuid::parse_str("12345678-1234-1234-1234-123456789012").into_dataverse_result()?,
+            lastname: String::from("McTestface"),
+        };
+
+        let mut batch = Batch::new("https://instance.crm.dynamics.com/");
+        batch.upsert_with_mode(&testy_contact, UpsertMode::CreateOnly)?;
+        Ok(())
+    }
+
+    #[derive(Serialize)]
+    struct Contact {
+        contactid: Uuid,
+        lastname: String,
+    }
+
+    impl WriteEntity for Contact {}
+
+    impl Reference for Contact {
+        fn get_reference(&self) -> ReferenceStruct {
+            ReferenceStruct::new(
+                "contacts",
+                self.contactid,
+            )
+        }
+    }
+    ```
+    */
+    pub fn upsert_with_mode(&mut self, entity: &impl WriteEntity, mode: UpsertMode) -> Result<()> {
+        let reference = entity.get_key_reference();
         let entity = serde_json::to_string(entity).into_dataverse_result()?;
+        let mut part = Part::new("PATCH", reference);
+        if let Some((name, value)) = mode.precondition() {
+            part = part.header(name, value);
+        }
 
-        write!(
-            self.payload,
-            "--changeset_{}\nContent-Type: application/http\nContent-Transfer-Encoding:binary\nContent-Id: {}\n\nPATCH {}api/data/v{}/{}({}) HTTP/1.1\nContent-Type: application/json;type=entry\n\n{}\n", 
-            self.dataset_id.as_simple(),
-            self.next_content_id,
-            self.url,
-            VERSION,
-            reference.entity_name,
-            reference.entity_id,
-            entity
-        ).into_dataverse_result()?;
-
-        self.next_content_id += 1;
+        self.write_part(part.json(entity));
         Ok(())
     }
 
@@ -401,39 +514,424 @@ impl Batch {
     }
     ```
     */
-    pub fn delete(&mut self, entity: &impl Reference) -> Result<()> {
-        let reference = entity.get_reference();
-
-        write!(
-            self.payload,
-            "--changeset_{}\nContent-Type: application/http\nContent-Transfer-Encoding:binary\nContent-Id: {}\n\nDELETE {}api/data/v{}/{}({}) HTTP/1.1\n\n", 
-            self.dataset_id.as_simple(),
-            self.next_content_id,
-            self.url,
-            VERSION,
-            reference.entity_name,
-            reference.entity_id
-        ).into_dataverse_result()?;
-
-        self.next_content_id += 1;
+    pub fn delete(&mut self, entity: &impl Addressable) -> Result<()> {
+        self.write_part(Part::new("DELETE", entity.get_key_reference()));
+        Ok(())
+    }
+
+    /**
+    Adds a request to this batch that associates two records through the given
+    collection-valued navigation property (see `Client::associate(...)`)
+
+    # Examples
+    ```rust
+    use uuid::Uuid;
+    use powerplatform_dataverse_service_client::{
+        batch::Batch,
+        reference::ReferenceStruct,
+        result::Result,
+    };
+
+    fn test() -> Result<()> {
+        let account = ReferenceStruct::new("accounts", Uuid::new_v4());
+        let opportunity = ReferenceStruct::new("opportunities", Uuid::new_v4());
+
+        let mut batch = Batch::new("https://instance.crm.dynamics.com/");
+        batch.associate(&account, "opportunity_customer_accounts", &opportunity)?;
+        Ok(())
+    }
+    ```
+    */
+    pub fn associate(&mut self, from: &impl Addressable, relationship: &str, to: &impl Addressable) -> Result<()> {
+        let path = format!("{}/{}/$ref", from.get_key_reference(), relationship);
+        self.write_part(Part::new("POST", path).odata_id(to.get_key_reference()));
+        Ok(())
+    }
+
+    /**
+    Adds a request to this batch that removes the association of two records through
+    the given collection-valued navigation property (see `Client::disassociate(...)`)
+    */
+    pub fn disassociate(&mut self, from: &impl Addressable, relationship: &str, to: &impl Addressable) -> Result<()> {
+        let path = format!("{}/{}/$ref", from.get_key_reference(), relationship);
+        self.write_part(Part::new("DELETE", path).id_parameter(to.get_key_reference()));
+        Ok(())
+    }
+
+    /**
+    Adds a request to this batch that sets the single-valued navigation property (lookup)
+    of the record `from` to the record `to` (see `Client::set_reference(...)`)
+    */
+    pub fn set_reference(&mut self, from: &impl Addressable, navigation_property: &str, to: &impl Addressable) -> Result<()> {
+        let path = format!("{}/{}/$ref", from.get_key_reference(), navigation_property);
+        self.write_part(Part::new("PUT", path).odata_id(to.get_key_reference()));
+        Ok(())
+    }
+
+    /**
+    Adds a request to this batch that clears the single-valued navigation property (lookup)
+    of the given record (see `Client::clear_reference(...)`)
+    */
+    pub fn clear_reference(&mut self, from: &impl Addressable, navigation_property: &str) -> Result<()> {
+        let path = format!("{}/{}/$ref", from.get_key_reference(), navigation_property);
+        self.write_part(Part::new("DELETE", path));
+        Ok(())
+    }
+
+    /**
+    Adds a request to this batch that writes the value of a single column of the given record
+    (see `Client::set_column(...)`)
+
+    Please note that this function can fail if a serde serialization error occurs. Reading
+    single columns is not possible in a batch because changesets only contain writes
+    */
+    pub fn set_column<T: Serialize>(&mut self, reference: &impl Addressable, column: &str, value: &T) -> Result<()> {
+        let path = format!("{}/{}", reference.get_key_reference(), column);
+        let body = serde_json::to_string(&ColumnValue { value }).into_dataverse_result()?;
+        self.write_part(Part::new("PUT", path).json(body));
+        Ok(())
+    }
+
+    /**
+    Adds a request to this batch that clears the value of a single column of the given record
+    (see `Client::clear_column(...)`), lookups are passed as `Column::Lookup`
+
+    # Examples
+    ```rust
+    use uuid::Uuid;
+    use powerplatform_dataverse_service_client::{
+        batch::Batch,
+        client::Column,
+        reference::ReferenceStruct,
+        result::Result,
+    };
+
+    fn test() -> Result<()> {
+        let contact = ReferenceStruct::new("contacts", Uuid::new_v4());
+
+        let mut batch = Batch::new("https://instance.crm.dynamics.com/");
+        batch.clear_column(&contact, "telephone1")?;
+        batch.clear_column(&contact, Column::Lookup("parentcustomerid_account"))?;
+        Ok(())
+    }
+    ```
+    */
+    pub fn clear_column<'c>(&mut self, reference: &impl Addressable, column: impl Into<Column<'c>>) -> Result<()> {
+        let path = format!("{}/{}", reference.get_key_reference(), column.into().path());
+        self.write_part(Part::new("DELETE", path));
         Ok(())
     }
+
+    /**
+    Adds a request to this batch that executes the given action (see `Client::execute_action(...)`)
+
+    The responses of the actions in a batch are not read. Functions can't be added to a
+    batch because changesets only contain writes
+
+    Please note that this function can fail if a serde serialization error occurs
+    */
+    pub fn execute_action<T: DataverseAction>(&mut self, action: &T) -> Result<()> {
+        let path = action.binding().path(action.name());
+        let body = serde_json::to_string(action).into_dataverse_result()?;
+        self.write_part(Part::new("POST", path).json(body));
+        Ok(())
+    }
+
+    /**
+    Renders this batch for the given Web-API version and adds the given headers to every request inside of it
+
+    A header is left out of a request if the request options of the operation already set it
+
+    Used by clients that send additional headers with each of their requests (e.g. impersonation)
+    */
+    pub(crate) fn render_for_client(&self, web_api_version: &str, headers: &[(&str, String)]) -> String {
+        let base_url = format!("{}api/data/v{}/", self.url, web_api_version);
+        let dataset_id = self.dataset_id.as_simple();
+        let mut payload = String::new();
+
+        for (index, part) in self.parts.iter().enumerate() {
+            payload.push_str(&format!(
+                "--changeset_{}\nContent-Type: application/http\nContent-Transfer-Encoding:binary\nContent-Id: {}\n\n{} {} HTTP/1.1\n",
+                dataset_id,
+                index + 1,
+                part.method,
+                part.url(&base_url),
+            ));
+
+            // the options of the operation take precedence over the same options of the client
+            let options = part.options.headers();
+            for (name, value) in headers {
+                if !options.iter().any(|(option, _)| option.eq_ignore_ascii_case(name)) {
+                    payload.push_str(&format!("{}: {}\n", name, value));
+                }
+            }
+
+            if part.body.is_some() {
+                payload.push_str("Content-Type: application/json;type=entry\n");
+            }
+
+            for (name, value) in &part.headers {
+                payload.push_str(&format!("{}: {}\n", name, value));
+            }
+
+            for (name, value) in options {
+                payload.push_str(&format!("{}: {}\n", name, value));
+            }
+
+            payload.push('\n');
+            if let Some(body) = &part.body {
+                payload.push_str(&body.render(&base_url));
+                payload.push('\n');
+            }
+        }
+
+        format!(
+            "--batch_{}\nContent-Type: multipart/mixed; boundary=changeset_{}\n\n{}--changeset_{}--\n--batch_{}--",
+            self.batch_id.as_simple(),
+            dataset_id,
+            payload,
+            dataset_id,
+            self.batch_id.as_simple(),
+        )
+    }
+
+    /// adds a request with the current request options as the next part of the changeset
+    fn write_part(&mut self, mut part: Part) {
+        part.options = self.options.clone();
+        self.parts.push(part);
+    }
 }
 
 impl Display for Batch {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let batch_id = self.batch_id.as_simple();
-        let dataset_id = self.dataset_id.as_simple();
+        f.write_str(&self.render_for_client(VERSION, &[]))
+    }
+}
 
-        f.write_fmt(
-            format_args!(
-                "--batch_{}\nContent-Type: multipart/mixed; boundary=changeset_{}\n\n{}--changeset_{}--\n--batch_{}--",
-                batch_id,
-                dataset_id,
-                self.payload,
-                dataset_id,
-                batch_id,
+/**
+A request of the changeset
+
+Its path and the records it references are relative to `api/data/v{version}/` until the
+batch is rendered for the Web-API version of the executing client
+*/
+struct Part {
+    method: &'static str,
+    path: String,
+    id_parameter: Option<String>,
+    headers: Vec<(&'static str, String)>,
+    body: Option<PartBody>,
+    options: RequestOptions,
+}
+
+enum PartBody {
+    Json(String),
+
+    /// the `{"@odata.id": "..."}` body that references the record with the given path
+    ODataId(String),
+}
+
+impl Part {
+    fn new(method: &'static str, path: impl Display) -> Self {
+        Self {
+            method,
+            path: path.to_string(),
+            id_parameter: None,
+            headers: Vec::new(),
+            body: None,
+            options: RequestOptions::default(),
+        }
+    }
+
+    fn header(mut self, name: &'static str, value: &str) -> Self {
+        self.headers.push((name, value.to_string()));
+        self
+    }
+
+    fn json(mut self, body: String) -> Self {
+        self.body = Some(PartBody::Json(body));
+        self
+    }
+
+    fn odata_id(mut self, target: KeyReference) -> Self {
+        self.body = Some(PartBody::ODataId(target.to_string()));
+        self
+    }
+
+    /// references the record with the given path in the `$id` query parameter
+    fn id_parameter(mut self, target: KeyReference) -> Self {
+        self.id_parameter = Some(target.to_string());
+        self
+    }
+
+    fn url(&self, base_url: &str) -> String {
+        let mut url = format!("{}{}", base_url, self.path);
+        if let Some(target) = &self.id_parameter {
+            url.push_str("?$id=");
+            url.push_str(&encode_path(&format!("{}{}", base_url, target)));
+        }
+
+        self.options.apply_to_url(&mut url);
+        url
+    }
+}
+
+impl PartBody {
+    fn render(&self, base_url: &str) -> String {
+        match self {
+            PartBody::Json(body) => body.clone(),
+            PartBody::ODataId(target) => {
+                serde_json::json!({ "@odata.id": format!("{}{}", base_url, target) }).to_string()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+    use uuid::Uuid;
+
+    use crate::{
+        action::{MergeEntity, MergeRequest},
+        batch::Batch,
+        client::Column,
+        entity::{UpsertMode, WriteEntity},
+        options::RequestOptions,
+        query::attribute::Attribute,
+        reference::{KeyReference, Reference, ReferenceStruct},
+    };
+
+    #[derive(Serialize)]
+    struct Contact {
+        contactid: Uuid,
+        lastname: String,
+    }
+
+    impl WriteEntity for Contact {}
+
+    impl Reference for Contact {
+        fn get_reference(&self) -> ReferenceStruct {
+            ReferenceStruct::new("contacts", self.contactid)
+        }
+    }
+
+    fn contact() -> Contact {
+        Contact {
+            contactid: Uuid::nil(),
+            lastname: String::from("McTestface"),
+        }
+    }
+
+    #[test]
+    fn update_with_etag() {
+        let mut batch = Batch::new("https://instance.crm.dynamics.com/");
+        batch.update(&contact()).unwrap();
+        batch.update_if_match(&contact(), "W/\"42\"").unwrap();
+
+        let payload = batch.to_string();
+        assert_eq!(batch.get_count(), 2);
+        assert!(payload.contains("PATCH https://instance.crm.dynamics.com/api/data/v9.2/contacts(00000000-0000-0000-0000-000000000000) HTTP/1.1\nContent-Type: application/json;type=entry\nIf-Match: *\n"));
+        assert!(payload.contains("If-Match: W/\"42\"\n\n{\"contactid\":\"00000000-0000-0000-0000-000000000000\",\"lastname\":\"McTestface\"}\n"));
+    }
+
+    #[test]
+    fn delete_by_alternate_key() {
+        let mut batch = Batch::new("https://instance.crm.dynamics.com/");
+        batch
+            .delete(&KeyReference::alternate(
+                "accounts",
+                vec![("accountnumber", Attribute::String(String::from("A-100")))],
+            ))
+            .unwrap();
+
+        assert!(batch
+            .to_string()
+            .contains("DELETE https://instance.crm.dynamics.com/api/data/v9.2/accounts(accountnumber='A-100') HTTP/1.1\n"));
+    }
+
+    #[test]
+    fn upsert_modes() {
+        let mut batch = Batch::new("https://instance.crm.dynamics.com/");
+        batch.upsert(&contact()).unwrap();
+        batch.upsert_with_mode(&contact(), UpsertMode::CreateOnly).unwrap();
+        batch.upsert_with_mode(&contact(), UpsertMode::UpdateOnly).unwrap();
+
+        let payload = batch.to_string();
+        assert!(payload.contains("Content-Id: 1\n\nPATCH https://instance.crm.dynamics.com/api/data/v9.2/contacts(00000000-0000-0000-0000-000000000000) HTTP/1.1\nContent-Type: application/json;type=entry\n\n{"));
+        assert!(payload.contains("Content-Type: application/json;type=entry\nIf-None-Match: *\n\n{"));
+        assert!(payload.contains("Content-Type: application/json;type=entry\nIf-Match: *\n\n{"));
+    }
+
+    #[test]
+    fn renders_options_per_operation() {
+        let mut batch = Batch::new("https://instance.crm.dynamics.com/");
+        batch.set_options(RequestOptions::new().suppress_duplicate_detection(true).tag("import"));
+        batch.create(&contact()).unwrap();
+        batch.set_options(RequestOptions::new());
+        batch.delete(&contact()).unwrap();
+
+        let payload = batch.to_string();
+        assert!(payload.contains("POST https://instance.crm.dynamics.com/api/data/v9.2/contacts?tag=import HTTP/1.1\nContent-Type: application/json;type=entry\nMSCRM.SuppressDuplicateDetection: true\n\n{"));
+        assert!(payload.contains("DELETE https://instance.crm.dynamics.com/api/data/v9.2/contacts(00000000-0000-0000-0000-000000000000) HTTP/1.1\n\n"));
+        assert_eq!(payload.matches("MSCRM.SuppressDuplicateDetection").count(), 1);
+    }
+
+    #[test]
+    fn operation_options_override_client_options() {
+        let mut batch = Batch::new("https://instance.crm.dynamics.com/");
+        batch.set_options(RequestOptions::new().bypass_custom_plugin_execution(false));
+        batch.delete(&contact()).unwrap();
+        batch.set_options(RequestOptions::new());
+        batch.delete(&contact()).unwrap();
+
+        let client_headers = RequestOptions::new().bypass_custom_plugin_execution(true).solution_unique_name("Core").headers();
+        let payload = batch.render_for_client("9.2", &client_headers);
+        assert!(payload.contains("Content-Id: 1\n\nDELETE https://instance.crm.dynamics.com/api/data/v9.2/contacts(00000000-0000-0000-0000-000000000000) HTTP/1.1\nMSCRM.SolutionUniqueName: Core\nMSCRM.BypassCustomPluginExecution: false\n\n"));
+        assert!(payload.contains("Content-Id: 2\n\nDELETE https://instance.crm.dynamics.com/api/data/v9.2/contacts(00000000-0000-0000-0000-000000000000) HTTP/1.1\nMSCRM.BypassCustomPluginExecution: true\nMSCRM.SolutionUniqueName: Core\n\n"));
+        assert_eq!(payload.matches("MSCRM.BypassCustomPluginExecution").count(), 2);
+    }
+
+    #[test]
+    fn associates_through_references() {
+        let account = ReferenceStruct::new("accounts", Uuid::nil());
+        let mut batch = Batch::new("https://instance.crm.dynamics.com/");
+        batch.associate(&account, "contact_customer_accounts", &contact()).unwrap();
+        batch.disassociate(&account, "contact_customer_accounts", &contact()).unwrap();
+        batch.clear_reference(&contact(), "parentcustomerid_account").unwrap();
+        batch
+            .disassociate(
+                &account,
+                "contact_customer_accounts",
+                &KeyReference::alternate("contacts", vec![("employeeid", Attribute::String(String::from("A&B")))]),
             )
-        )
+            .unwrap();
+
+        let payload = batch.to_string();
+        assert!(payload.contains("POST https://instance.crm.dynamics.com/api/data/v9.2/accounts(00000000-0000-0000-0000-000000000000)/contact_customer_accounts/$ref HTTP/1.1\nContent-Type: application/json;type=entry\n\n{\"@odata.id\":\"https://instance.crm.dynamics.com/api/data/v9.2/contacts(00000000-0000-0000-0000-000000000000)\"}\n"));
+        assert!(payload.contains("DELETE https://instance.crm.dynamics.com/api/data/v9.2/accounts(00000000-0000-0000-0000-000000000000)/contact_customer_accounts/$ref?$id=https%3A%2F%2Finstance.crm.dynamics.com%2Fapi%2Fdata%2Fv9.2%2Fcontacts%2800000000-0000-0000-0000-000000000000%29 HTTP/1.1\n\n"));
+        assert!(payload.contains("DELETE https://instance.crm.dynamics.com/api/data/v9.2/contacts(00000000-0000-0000-0000-000000000000)/parentcustomerid_account/$ref HTTP/1.1\n\n"));
+        assert!(payload.contains("$ref?$id=https%3A%2F%2Finstance.crm.dynamics.com%2Fapi%2Fdata%2Fv9.2%2Fcontacts%28employeeid%3D'A%2526B'%29 HTTP/1.1\n"));
+    }
+
+    #[test]
+    fn single_columns() {
+        let mut batch = Batch::new("https://instance.crm.dynamics.com/");
+        batch.set_column(&contact(), "donotemail", &true).unwrap();
+        batch.clear_column(&contact(), "telephone1").unwrap();
+        batch.clear_column(&contact(), Column::Lookup("parentcustomerid_account")).unwrap();
+
+        let payload = batch.to_string();
+        assert!(payload.contains("PUT https://instance.crm.dynamics.com/api/data/v9.2/contacts(00000000-0000-0000-0000-000000000000)/donotemail HTTP/1.1\nContent-Type: application/json;type=entry\n\n{\"value\":true}\n"));
+        assert!(payload.contains("DELETE https://instance.crm.dynamics.com/api/data/v9.2/contacts(00000000-0000-0000-0000-000000000000)/telephone1 HTTP/1.1\n\n"));
+        assert!(payload.contains("DELETE https://instance.crm.dynamics.com/api/data/v9.2/contacts(00000000-0000-0000-0000-000000000000)/parentcustomerid_account/$ref HTTP/1.1\n\n"));
+    }
+
+    #[test]
+    fn executes_actions() {
+        let mut batch = Batch::new("https://instance.crm.dynamics.com/");
+        batch.execute_action(&MergeRequest::new(MergeEntity::Account, Uuid::nil(), Uuid::nil())).unwrap();
+
+        let payload = batch.to_string();
+        assert!(payload.contains("POST https://instance.crm.dynamics.com/api/data/v9.2/Merge HTTP/1.1\nContent-Type: application/json;type=entry\n\n{\"Target\":"));
     }
 }
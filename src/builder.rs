@@ -0,0 +1,392 @@
+/*!
+Module for configuring clients before they connect to a dataverse environment
+
+A `ClientBuilder` collects the settings of the underlying http client (timeouts,
+proxy, certificates, user agent, default headers and connection pooling), the Web-API
+version, the retry policy and the authentication method. `build()` validates the
+configuration and returns an error instead of panicking
+
+# Examples
+```rust
+use std::time::Duration;
+use powerplatform_dataverse_service_client::{client::Client, result::Result};
+
+# fn main() -> Result<()> {
+let client = Client::builder("https://instance.crm.dynamics.com")
+    .connect_timeout(Duration::from_secs(10))
+    .timeout(Duration::from_secs(60))
+    .user_agent("nightly-export/1.0")
+    .web_api_version("9.1")
+    .client_secret_auth("12345678-1234-1234-1234-123456789012", "<clientid>", "<clientsecret>")
+    .build()?;
+
+assert_eq!(client.url, "https://instance.crm.dynamics.com/");
+# Ok(())
+# }
+```
+*/
+
+use std::borrow::Cow;
+use std::time::Duration;
+
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Certificate, Proxy, Url,
+};
+
+use crate::{
+    auth::{client_secret::ClientSecretAuth, no_auth::NoAuth, Authenticate},
+    client::{Client, VERSION},
+    error::DataverseError,
+    result::{IntoDataverseResult, Result},
+    retry::RetryPolicy,
+};
+
+type AuthFactory<'url, A> = Box<dyn FnOnce(&reqwest::Client, &str, &RetryPolicy) -> A + 'url>;
+
+/**
+Builder for a `Client` with custom connection settings
+
+Use `Client::builder(...)` to create one. Unless configured otherwise the built client
+- only connects via https
+- uses a connect timeout and a request timeout of 120 seconds each
+- uses the Web-API version `VERSION`
+- uses the default `RetryPolicy`
+- fails every call because no authentication method is selected
+*/
+pub struct ClientBuilder<'url, A: Authenticate> {
+    url: Cow<'url, str>,
+    web_api_version: String,
+    https_only: bool,
+    connect_timeout: Duration,
+    timeout: Duration,
+    user_agent: String,
+    proxies: Vec<Proxy>,
+    root_certificates: Vec<Certificate>,
+    default_headers: Vec<(String, String)>,
+    pool_max_idle_per_host: Option<usize>,
+    pool_idle_timeout: Option<Duration>,
+    retry_policy: RetryPolicy,
+    auth: AuthFactory<'url, A>,
+}
+
+impl<'url> ClientBuilder<'url, NoAuth> {
+    /// Creates a builder for a client connecting to the given organization url
+    pub fn new(url: impl Into<Cow<'url, str>>) -> Self {
+        Self {
+            url: url.into(),
+            web_api_version: VERSION.to_string(),
+            https_only: true,
+            connect_timeout: Duration::from_secs(120),
+            timeout: Duration::from_secs(120),
+            user_agent: format!("{}/{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            proxies: Vec::new(),
+            root_certificates: Vec::new(),
+            default_headers: Vec::new(),
+            pool_max_idle_per_host: None,
+            pool_idle_timeout: None,
+            retry_policy: RetryPolicy::default(),
+            auth: Box::new(|_, _, _| NoAuth {}),
+        }
+    }
+}
+
+impl<'url, A: Authenticate> ClientBuilder<'url, A> {
+    /// sets the Web-API version used in every request url (default: `VERSION`)
+    pub fn web_api_version(mut self, version: impl Into<String>) -> Self {
+        self.web_api_version = version.into();
+        self
+    }
+
+    /**
+    allows or forbids plain http connections (default: https only)
+
+    Plain http is only useful for local stand-ins of dataverse in tests
+    */
+    pub fn https_only(mut self, https_only: bool) -> Self {
+        self.https_only = https_only;
+        self
+    }
+
+    /// sets the timeout for establishing a connection
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// sets the timeout for a whole request including reading the response
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// sets the `User-Agent` header sent with every request
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// routes the requests through the given proxy
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxies.push(proxy);
+        self
+    }
+
+    /// trusts the given root certificate in addition to the system certificates
+    pub fn add_root_certificate(mut self, certificate: Certificate) -> Self {
+        self.root_certificates.push(certificate);
+        self
+    }
+
+    /**
+    adds a header that is sent with every request
+
+    Invalid header names or values are reported by `build()`
+    */
+    pub fn default_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.default_headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// sets the maximum number of idle connections kept per host
+    pub fn pool_max_idle_per_host(mut self, max_idle: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max_idle);
+        self
+    }
+
+    /// sets how long idle connections are kept in the pool
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// sets the retry policy for the requests to dataverse and for the token acquisition
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// authenticates the client with the given custom authentication handler
+    pub fn auth<B: Authenticate + 'url>(self, auth: B) -> ClientBuilder<'url, B> {
+        self.with_auth_factory(Box::new(move |_, _, _| auth))
+    }
+
+    /**
+    authenticates the client with the client/secret method against the given tenant
+
+    The token acquisition shares the connection settings and the retry policy of the client
+    */
+    pub fn client_secret_auth(
+        self,
+        tenant_id: &str,
+        client_id: impl Into<String>,
+        client_secret: impl Into<String>,
+    ) -> ClientBuilder<'url, ClientSecretAuth> {
+        let login_url = format!(
+            "https://login.microsoftonline.com/{}/oauth2/v2.0/token",
+            tenant_id
+        );
+        let client_id = client_id.into();
+        let client_secret = client_secret.into();
+
+        self.with_auth_factory(Box::new(move |backend, url, retry_policy| {
+            ClientSecretAuth::new(
+                backend.clone(),
+                login_url,
+                format!("{}.default", url),
+                client_id,
+                client_secret,
+            )
+            .with_retry_policy(retry_policy.clone())
+        }))
+    }
+
+    /**
+    Creates the configured client
+
+    This may fail for any of these reasons
+    - the organization url is not a valid absolute url
+    - the organization url uses plain http while `https_only` is set
+    - a default header has an invalid name or value
+    - the http client could not be initialized (e.g. the TLS backend is unavailable)
+    */
+    pub fn build(mut self) -> Result<Client<'url, A>> {
+        let url = normalize_url(std::mem::take(&mut self.url), self.https_only)?;
+        let backend = self.build_backend()?;
+        Ok(self.finish(url, backend))
+    }
+
+    /**
+    Creates the configured client without validating the organization url
+
+    The url is still trimmed and completed with a trailing slash. An invalid url surfaces as an error of the first request instead. Used by the
+    constructors of `Client` that never failed, where the http client falls back
+    to the reqwest defaults if it can't be initialized
+    */
+    pub(crate) fn build_unvalidated(mut self) -> Client<'url, A> {
+        let url = with_trailing_slash(std::mem::take(&mut self.url));
+        let backend = self.build_backend().unwrap_or_default();
+        self.finish(url, backend)
+    }
+
+    fn build_backend(&mut self) -> Result<reqwest::Client> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.default_headers {
+            headers.insert(
+                HeaderName::from_bytes(name.as_bytes()).into_dataverse_result()?,
+                HeaderValue::from_str(value).into_dataverse_result()?,
+            );
+        }
+
+        let mut backend = reqwest::Client::builder()
+            .https_only(self.https_only)
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.timeout)
+            .user_agent(self.user_agent.as_str())
+            .default_headers(headers);
+
+        for proxy in std::mem::take(&mut self.proxies) {
+            backend = backend.proxy(proxy);
+        }
+
+        for certificate in std::mem::take(&mut self.root_certificates) {
+            backend = backend.add_root_certificate(certificate);
+        }
+
+        if let Some(max_idle) = self.pool_max_idle_per_host {
+            backend = backend.pool_max_idle_per_host(max_idle);
+        }
+
+        if let Some(timeout) = self.pool_idle_timeout {
+            backend = backend.pool_idle_timeout(timeout);
+        }
+
+        backend.build().into_dataverse_result()
+    }
+
+    fn finish(self, url: Cow<'url, str>, backend: reqwest::Client) -> Client<'url, A> {
+        let auth = (self.auth)(&backend, &url, &self.retry_policy);
+
+        let mut client = Client::new(url, backend, auth).with_retry_policy(self.retry_policy);
+        client.web_api_version = self.web_api_version;
+        client
+    }
+
+    fn with_auth_factory<B: Authenticate>(self, auth: AuthFactory<'url, B>) -> ClientBuilder<'url, B> {
+        ClientBuilder {
+            url: self.url,
+            web_api_version: self.web_api_version,
+            https_only: self.https_only,
+            connect_timeout: self.connect_timeout,
+            timeout: self.timeout,
+            user_agent: self.user_agent,
+            proxies: self.proxies,
+            root_certificates: self.root_certificates,
+            default_headers: self.default_headers,
+            pool_max_idle_per_host: self.pool_max_idle_per_host,
+            pool_idle_timeout: self.pool_idle_timeout,
+            retry_policy: self.retry_policy,
+            auth,
+        }
+    }
+}
+
+/// validates the organization url and makes sure it ends with a slash
+fn normalize_url(url: Cow<'_, str>, https_only: bool) -> Result<Cow<'_, str>> {
+    let parsed = Url::parse(url.trim()).into_dataverse_result()?;
+
+    if parsed.cannot_be_a_base() || parsed.host_str().is_none() {
+        return Err(DataverseError::new(format!("'{}' is not a valid organization url", url)));
+    }
+
+    if https_only && parsed.scheme() != "https" {
+        return Err(DataverseError::new(format!(
+            "'{}' does not use https. Use `https_only(false)` to allow plain http connections",
+            url
+        )));
+    }
+
+    Ok(with_trailing_slash(url))
+}
+
+/// trims the organization url and makes sure it ends with a slash, an empty url is kept as is
+pub(crate) fn with_trailing_slash(url: Cow<'_, str>) -> Cow<'_, str> {
+    if url.is_empty() || (url.ends_with('/') && url.trim() == url) {
+        return url;
+    }
+
+    let mut normalized = url.trim().to_string();
+    if !normalized.ends_with('/') {
+        normalized.push('/');
+    }
+
+    Cow::Owned(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::{
+        client::Client,
+        reference::ReferenceStruct,
+        testing::{CannedResponse, StaticAuth, TestServer},
+    };
+
+    #[test]
+    fn normalizes_the_organization_url() {
+        let client = Client::builder(" https://instance.crm.dynamics.com")
+            .build()
+            .unwrap();
+
+        assert_eq!(client.url, "https://instance.crm.dynamics.com/");
+    }
+
+    #[test]
+    fn rejects_invalid_urls() {
+        assert!(Client::builder("instance.crm.dynamics.com").build().is_err());
+        assert!(Client::builder("http://instance.crm.dynamics.com/").build().is_err());
+        assert!(Client::builder("https://instance.crm.dynamics.com/")
+            .default_header("invalid header", "value")
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn client_secret_constructor_does_not_validate_the_url() {
+        let client = Client::with_client_secret_auth("instance.crm.dynamics.com", "tenant", "client", "secret");
+        assert_eq!(client.url, "instance.crm.dynamics.com/");
+
+        let client = Client::with_client_secret_auth(" https://instance.crm.dynamics.com", "tenant", "client", "secret");
+        assert_eq!(client.url, "https://instance.crm.dynamics.com/");
+    }
+
+    #[test]
+    fn new_completes_the_organization_url() {
+        let client = Client::new("https://instance.crm.dynamics.com", reqwest::Client::new(), StaticAuth);
+        assert_eq!(client.url, "https://instance.crm.dynamics.com/");
+    }
+
+    #[tokio::test]
+    async fn applies_connection_settings() {
+        let server = TestServer::start(vec![CannedResponse::new(204)]).await;
+        let client = Client::builder(server.url.trim_end_matches('/'))
+            .https_only(false)
+            .user_agent("tests/1.0")
+            .default_header("x-custom", "custom value")
+            .web_api_version("9.1")
+            .auth(StaticAuth)
+            .build()
+            .unwrap();
+
+        client
+            .delete(&ReferenceStruct::new("contacts", Uuid::nil()))
+            .await
+            .unwrap();
+
+        let request = &server.requests()[0];
+        assert_eq!(request.path, "/api/data/v9.1/contacts(00000000-0000-0000-0000-000000000000)");
+        assert_eq!(request.header("User-Agent"), Some("tests/1.0"));
+        assert_eq!(request.header("x-custom"), Some("custom value"));
+    }
+}
@@ -0,0 +1,236 @@
+/*!
+Module for the incremental synchronization of tables with change tracking
+
+A query is started with `Client::track_changes(...)`, which returns the first page of all
+matching records. Each page either has a `ChangeCursor` to the next page, which is passed
+to `Client::resume_changes(...)`, or is the last page and carries the `ChangeToken`.
+Passing the token to `Client::retrieve_changes(...)` later returns only the records that
+were created, updated or deleted since the token was issued, again page by page
+
+Only one page is held in memory at a time, so even the initial synchronization of a
+large table does not buffer the whole table. Both the cursor and the token can be
+stored to continue after a restart
+
+Change tracking has to be enabled for the table in dataverse
+
+# Examples
+```rust
+use serde::Deserialize;
+use uuid::Uuid;
+use powerplatform_dataverse_service_client::{
+    changes::{Change, ChangeToken},
+    client::Client,
+    entity::ReadEntity,
+    query::Query,
+    result::{IntoDataverseResult, Result},
+    select::Select,
+};
+
+async fn synchronize(stored_token: Option<String>) -> Result<String> {
+    let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+    let query = Query::new("accounts").max_page_size(1000);
+
+    let mut changes = match stored_token {
+        Some(token) => {
+            let token: ChangeToken = serde_json::from_str(&token).into_dataverse_result()?;
+            client.retrieve_changes::<Account>(&query, &token).await?
+        }
+        None => client.track_changes::<Account>(&query).await?,
+    };
+
+    loop {
+        for change in changes.changes.drain(..) {
+            match change {
+                Change::Upserted(account) => println!("write {} into the warehouse", account.accountid),
+                Change::Deleted(reference) => println!("delete {} from the warehouse", reference.entity_id),
+            }
+        }
+
+        match changes.cursor() {
+            Some(cursor) => changes = client.resume_changes(&query, &cursor).await?,
+            None => break,
+        }
+    }
+
+    serde_json::to_string(&changes.token()).into_dataverse_result()
+}
+
+#[derive(Deserialize)]
+struct Account {
+    accountid: Uuid,
+    name: String,
+}
+
+impl ReadEntity for Account {}
+
+impl Select for Account {
+    fn get_columns() -> &'static [&'static str] {
+        &["accountid", "name"]
+    }
+}
+```
+*/
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    error::{DataverseError, ErrorKind},
+    reference::ReferenceStruct,
+    result::{IntoDataverseResult, Result},
+};
+
+/**
+A serializable position in the change history of a table
+
+Created from the `@odata.deltaLink` of the last page of a tracked query
+*/
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeToken {
+    /// the url that returns the changes after this position as provided by `@odata.deltaLink`
+    pub delta_link: String,
+    /// identifies the query and selected columns the token was created for
+    pub query_fingerprint: String,
+}
+
+/**
+A record that changed since the last synchronization
+*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change<E> {
+    /// The record was created or updated, the selected columns contain its current values
+    Upserted(E),
+
+    /// The record was deleted, only its reference is known
+    Deleted(ReferenceStruct),
+}
+
+impl<E: DeserializeOwned> Change<E> {
+    /// reads a record of a tracked query, where deleted records are marked as `$deletedEntity`
+    pub(crate) fn read(value: Value, entity_name: &'static str) -> Result<Self> {
+        let context = value.get("@odata.context").and_then(Value::as_str);
+        let deleted = matches!(context, Some(context) if context.ends_with("$deletedEntity"))
+            || value.get("reason").and_then(Value::as_str) == Some("deleted");
+
+        if !deleted {
+            return serde_json::from_value(value).into_dataverse_result().map(Change::Upserted);
+        }
+
+        let entity_id = value
+            .get("id")
+            .and_then(Value::as_str)
+            .ok_or_else(|| DataverseError::with_kind(
+                ErrorKind::Serialization,
+                String::from("Dataverse provided no id for a deleted record"),
+            ))?;
+
+        Ok(Change::Deleted(ReferenceStruct::new(
+            entity_name,
+            Uuid::parse_str(entity_id).into_dataverse_result()?,
+        )))
+    }
+}
+
+/**
+A serializable position between two pages of changes
+
+Created by `Changes::cursor()` and consumed by `Client::resume_changes(...)`
+*/
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeCursor {
+    /// the url of the next page as provided by `@odata.nextLink`
+    pub next_link: String,
+    /// identifies the query and selected columns the cursor was created for
+    pub query_fingerprint: String,
+}
+
+/**
+A page of changes of a table
+
+Returned by `Client::track_changes(...)`, `Client::retrieve_changes(...)` and
+`Client::resume_changes(...)`. Every page but the last one has a cursor to the next
+page, only the last page has the token to continue after all of them
+*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Changes<E> {
+    pub changes: Vec<Change<E>>,
+    next_link: Option<String>,
+    token: Option<ChangeToken>,
+    query_fingerprint: String,
+}
+
+impl<E> Changes<E> {
+    pub(crate) fn new(changes: Vec<Change<E>>, next_link: Option<String>, delta_link: Option<String>, query_fingerprint: String) -> Self {
+        Self {
+            changes,
+            token: delta_link.map(|delta_link| ChangeToken {
+                delta_link,
+                query_fingerprint: query_fingerprint.clone(),
+            }),
+            next_link,
+            query_fingerprint,
+        }
+    }
+
+    /// Indicates if more pages of changes follow this one
+    pub fn is_incomplete(&self) -> bool {
+        self.next_link.is_some()
+    }
+
+    /**
+    returns a serializable cursor to the page after this one or `None` if this is the last page
+
+    The cursor can be stored and later passed to `Client::resume_changes(...)`
+    */
+    pub fn cursor(&self) -> Option<ChangeCursor> {
+        let next_link = self.next_link.as_ref()?;
+
+        Some(ChangeCursor {
+            next_link: next_link.clone(),
+            query_fingerprint: self.query_fingerprint.clone(),
+        })
+    }
+
+    /**
+    returns the token to continue after the changes or `None` if more pages follow
+
+    Store the token only after every page was processed, otherwise the changes
+    of the remaining pages are lost
+    */
+    pub fn token(&self) -> Option<&ChangeToken> {
+        self.token.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use uuid::Uuid;
+
+    use crate::{changes::Change, reference::ReferenceStruct};
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Account {
+        name: String,
+    }
+
+    #[test]
+    fn reads_upserted_and_deleted_records() {
+        let upserted = serde_json::json!({ "@odata.etag": "W/\"1\"", "name": "Contoso" });
+        let deleted = serde_json::json!({
+            "@odata.context": "https://org.crm.dynamics.com/api/data/v9.2/$metadata#accounts/$deletedEntity",
+            "id": "00000000-0000-0000-0000-000000000001",
+            "reason": "deleted"
+        });
+
+        assert_eq!(
+            Change::<Account>::read(upserted, "accounts").unwrap(),
+            Change::Upserted(Account { name: String::from("Contoso") })
+        );
+        assert_eq!(
+            Change::<Account>::read(deleted, "accounts").unwrap(),
+            Change::Deleted(ReferenceStruct::new("accounts", Uuid::from_u128(1)))
+        );
+    }
+}
@@ -21,24 +21,37 @@ let client = Client::with_client_secret_auth(
 */
 
 use std::future::Future;
+use std::sync::Arc;
+use std::time::Instant;
 use std::{borrow::Cow, fmt::Display};
-use std::time::Duration;
 
+use futures::StreamExt;
+use tokio::io::{AsyncRead, AsyncReadExt};
 use lazy_static::lazy_static;
 use regex::Regex;
-use reqwest::{RequestBuilder, Response, Method};
-use serde::Deserialize;
+use reqwest::{RequestBuilder, Response, Method, StatusCode, Url};
+use serde::{de::{DeserializeOwned, IgnoredAny}, Deserialize, Serialize};
+use serde_json::Value;
 use uuid::Uuid;
 
-use crate::action::MergeRequest;
+use crate::action::{function_path, DataverseAction, DataverseFunction, MergeEntity, MergeRequest, RetrieveTotalRecordCount, RetrieveVersion, WhoAmI, WhoAmIResponse};
+use crate::builder::{with_trailing_slash, ClientBuilder};
+use crate::changes::{Change, ChangeCursor, ChangeToken, Changes};
+use crate::health::{Diagnosis, HealthReport};
+use crate::metadata::{entity_path, option_set_path, AttributeMetadata, EntityMetadata, MetadataCache, MetadataQuery, OptionSetMetadata};
+use crate::file::{FileBlock, FileDownload, FileUpload, ImageSize, DEFAULT_BLOCK_SIZE};
+use crate::stream::EntityStream;
 use crate::{
     auth::{client_secret::ClientSecretAuth, Authenticate, no_auth::NoAuth},
     batch::Batch,
-    entity::{ReadEntity, WriteEntity},
-    error::DataverseError,
-    query::Query,
-    reference::Reference,
+    entity::{Conditional, CreatedRecord, DeepInsert, ReadEntity, UpsertMode, UpsertOutcome, WriteEntity},
+    error::{DataverseError, ErrorKind},
+    options::RequestOptions,
+    query::{expand::{Expand, ExpandList}, fetch::{self, FetchXml}, Query},
+    reference::{encode_path, Addressable, KeyReference, RecordKey},
     result::{IntoDataverseResult, Result},
+    retry::RetryPolicy,
+    select::Select,
 };
 
 lazy_static! {
@@ -47,8 +60,12 @@ lazy_static! {
             .unwrap();
 }
 
-/// Microsoft Dataverse Web-API Version this client uses
+/// Microsoft Dataverse Web-API Version clients use unless configured otherwise
 pub static VERSION: &str = "9.2";
+
+/// the longest url dataverse accepts, longer GET requests are sent within a `$batch` request
+const MAX_URL_LENGTH: usize = 32_768;
+
 /**
 A client capable of connecting to a dataverse environment
 
@@ -73,7 +90,92 @@ let client = Client::with_client_secret_auth(
 pub struct Client<'url, A: Authenticate> {
     pub url: Cow<'url, str>,
     backend: reqwest::Client,
-    auth: A,
+    auth: Arc<A>,
+    retry_policy: RetryPolicy,
+    pub(crate) web_api_version: String,
+    caller: Option<CallerId>,
+    options: RequestOptions,
+    metadata_cache: Arc<MetadataCache>,
+}
+
+/**
+Identifies the user a client acts on behalf of when impersonating
+
+The application user of the client needs the privilege `prvActOnBehalfOfAnotherUser`
+to impersonate other users. See `Client::impersonate(...)`
+*/
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CallerId {
+    /// The Azure Active Directory object id of the user (sent as `CallerObjectId`)
+    AadObjectId(Uuid),
+
+    /// The id of the `systemuser` record in dataverse (sent as `MSCRMCallerID`)
+    SystemUserId(Uuid),
+}
+
+impl CallerId {
+    /// returns the header dataverse expects for this kind of caller id
+    pub fn header(&self) -> (&'static str, String) {
+        match self {
+            CallerId::AadObjectId(id) => ("CallerObjectId", id.to_string()),
+            CallerId::SystemUserId(id) => ("MSCRMCallerID", id.to_string()),
+        }
+    }
+}
+
+/**
+A column of a record that is cleared with `Client::clear_column(...)` or `Batch::clear_column(...)`
+
+Plain column names convert into `Column::Value`, so `client.clear_column(&contact, "telephone1")`
+clears a value column
+
+# Examples
+```rust
+use powerplatform_dataverse_service_client::client::Column;
+
+assert_eq!(Column::from("telephone1").path(), "telephone1");
+assert_eq!(Column::Lookup("parentcustomerid_account").path(), "parentcustomerid_account/$ref");
+```
+*/
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Column<'a> {
+    /// A column holding a value, cleared with `DELETE <record>/<column>`
+    Value(&'a str),
+
+    /// A lookup, cleared through its single-valued navigation property with
+    /// `DELETE <record>/<navigation property>/$ref`
+    Lookup(&'a str),
+}
+
+impl<'a> Column<'a> {
+    /// returns the path of the column relative to its record
+    pub fn path(&self) -> String {
+        match self {
+            Column::Value(column) => String::from(*column),
+            Column::Lookup(navigation_property) => format!("{}/$ref", navigation_property),
+        }
+    }
+}
+
+impl<'a> From<&'a str> for Column<'a> {
+    fn from(column: &'a str) -> Self {
+        Column::Value(column)
+    }
+}
+
+impl<'url, A: Authenticate> Clone for Client<'url, A> {
+    fn clone(&self) -> Self {
+        Self {
+            url: self.url.clone(),
+            backend: self.backend.clone(),
+            auth: Arc::clone(&self.auth),
+            retry_policy: self.retry_policy.clone(),
+            web_api_version: self.web_api_version.clone(),
+            caller: self.caller,
+            options: self.options.clone(),
+            metadata_cache: Arc::clone(&self.metadata_cache),
+        }
+    }
 }
 
 impl<'url> Client<'url, ClientSecretAuth> {
@@ -85,6 +187,10 @@ impl<'url> Client<'url, ClientSecretAuth> {
     is handled lazily and a token is only acquired on the first call or
     when an acquired token is no longer valid and needs to be refreshed
 
+    The organization url is not validated either, an invalid url surfaces as an error
+    of the first request. Use `Client::builder(...)` to reject an invalid configuration
+    right away
+
     # Examples
     ```rust
     use powerplatform_dataverse_service_client::client::Client;
@@ -106,28 +212,9 @@ impl<'url> Client<'url, ClientSecretAuth> {
         client_id: impl Into<String>,
         client_secret: impl Into<String>,
     ) -> Self {
-        let url = url.into();
-        let client_id = client_id.into();
-        let client_secret = client_secret.into();
-        let client = reqwest::Client::builder()
-            .https_only(true)
-            .connect_timeout(Duration::from_secs(120))
-            .timeout(Duration::from_secs(120))
-            .build()
-            .unwrap();
-
-        let auth = ClientSecretAuth::new(
-            client.clone(),
-            format!(
-                "https://login.microsoftonline.com/{}/oauth2/v2.0/token",
-                tenant_id
-            ),
-            format!("{}.default", url),
-            client_id,
-            client_secret,
-        );
-
-        Client::new(url, client, auth)
+        ClientBuilder::new(url)
+            .client_secret_auth(tenant_id, client_id, client_secret)
+            .build_unvalidated()
     }
 }
 
@@ -139,15 +226,31 @@ impl<'url> Client<'url, NoAuth> {
     want to prevent a bunch of erronous auth-calls each time a test is run
     */
     pub fn new_dummy() -> Self {
-        let client = reqwest::Client::builder()
-            .https_only(true)
-            .connect_timeout(Duration::from_secs(120))
-            .timeout(Duration::from_secs(120))
-            .build()
-            .unwrap();
-
         let auth = NoAuth {};
-        Client::new("", client, auth)
+        Client::new("", reqwest::Client::new(), auth)
+    }
+
+    /**
+    Creates a builder to configure the connection settings of a client
+
+    See `ClientBuilder` for the available settings and their defaults
+
+    # Examples
+    ```rust
+    use std::time::Duration;
+    use powerplatform_dataverse_service_client::{client::Client, result::Result};
+
+    # fn main() -> Result<()> {
+    let client = Client::builder("https://instance.crm.dynamics.com/")
+        .timeout(Duration::from_secs(30))
+        .client_secret_auth("12345678-1234-1234-1234-123456789012", "<clientid>", "<clientsecret>")
+        .build()?;
+    # Ok(())
+    # }
+    ```
+    */
+    pub fn builder(url: impl Into<Cow<'url, str>>) -> ClientBuilder<'url, NoAuth> {
+        ClientBuilder::new(url)
     }
 }
 
@@ -155,12 +258,15 @@ impl<'url, A: Authenticate> Client<'url, A> {
     /**
     Creates a dataverse client with a custom authentication handler and backend
 
+    The organization url is trimmed and completed with a trailing slash but not validated,
+    use `Client::builder(...)` to reject an invalid url right away
+
     This function may not panic so the custom authentication should follow these
     rules:
     - tokens should be acquired lazily
     - tokens should be cached and reused where possible
     - each call to the `get_valid_token()` function should give a token that is valid
-    for at least the next 2 minutes
+      for at least the next 2 minutes
 
     # Examples
     ```rust
@@ -198,8 +304,138 @@ impl<'url, A: Authenticate> Client<'url, A> {
     ```
     */
     pub fn new(url: impl Into<Cow<'url, str>>, backend: reqwest::Client, auth: A) -> Self {
-        let url = url.into();
-        Self { url, backend, auth }
+        let url = with_trailing_slash(url.into());
+        Self {
+            url,
+            backend,
+            auth: Arc::new(auth),
+            retry_policy: RetryPolicy::default(),
+            web_api_version: VERSION.to_string(),
+            caller: None,
+            options: RequestOptions::default(),
+            metadata_cache: Arc::new(MetadataCache::default()),
+        }
+    }
+
+    /**
+    Creates a handle of this client that acts on behalf of the given user
+
+    The handle shares the connection pool and the authentication of this client,
+    so creating one per end user is cheap. Every request of the handle, including
+    the requests inside of an executed `Batch`, carries the caller id header. Records
+    created or updated via the handle are audited and owned as if the user wrote them
+
+    # Examples
+    ```rust
+    use uuid::Uuid;
+    use powerplatform_dataverse_service_client::{
+        client::{CallerId, Client},
+        reference::ReferenceStruct,
+        result::{IntoDataverseResult, Result},
+    };
+
+    async fn test() -> Result<()> {
+        let user_id = Uuid::parse_str("12345678-1234-1234-1234-123456789012").into_dataverse_result()?;
+        let contact_id = Uuid::parse_str("12345678-1234-1234-1234-123456789abc").into_dataverse_result()?;
+
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+        let on_behalf = client.impersonate(CallerId::AadObjectId(user_id));
+        on_behalf.delete(&ReferenceStruct::new("contacts", contact_id)).await
+    }
+    ```
+    */
+    pub fn impersonate(&self, caller: CallerId) -> Self {
+        Self {
+            caller: Some(caller),
+            ..self.clone()
+        }
+    }
+
+    /// returns the user this client acts on behalf of, if it impersonates one
+    pub fn get_caller(&self) -> Option<CallerId> {
+        self.caller
+    }
+
+    /**
+    Creates a handle of this client that sends the given options with every request
+
+    Like `impersonate(...)` the handle shares the connection pool and the authentication
+    of this client. When a `Batch` is executed with the handle, the options apply to the
+    `$batch` request and to every request inside of it
+
+    # Examples
+    ```rust
+    use serde::Serialize;
+    use uuid::Uuid;
+    use powerplatform_dataverse_service_client::{
+        client::Client,
+        entity::WriteEntity,
+        options::RequestOptions,
+        reference::{Reference, ReferenceStruct},
+        result::Result,
+    };
+
+    async fn test(contact: &Contact) -> Result<Uuid> {
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+        client
+            .using_options(RequestOptions::new().bypass_custom_plugin_execution(true))
+            .create(contact)
+            .await
+    }
+
+    #[derive(Serialize)]
+    struct Contact {
+        contactid: Uuid,
+        lastname: String,
+    }
+
+    impl WriteEntity for Contact {}
+
+    impl Reference for Contact {
+        fn get_reference(&self) -> ReferenceStruct {
+            ReferenceStruct::new("contacts", self.contactid)
+        }
+    }
+    ```
+    */
+    pub fn using_options(&self, options: RequestOptions) -> Self {
+        Self {
+            options,
+            ..self.clone()
+        }
+    }
+
+    /// returns the options this client sends with every request
+    pub fn get_options(&self) -> &RequestOptions {
+        &self.options
+    }
+
+    /**
+    Replaces the retry policy this client uses for its requests to dataverse
+
+    Please note that the token acquisition of the authentication handler has its own
+    retry policy, see `ClientSecretAuth::with_retry_policy(...)`
+
+    # Examples
+    ```rust
+    use powerplatform_dataverse_service_client::{client::Client, retry::RetryPolicy};
+
+    let client = Client::new_dummy().with_retry_policy(RetryPolicy::new().max_attempts(3));
+    ```
+    */
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// returns the retry policy this client uses for its requests to dataverse
+    pub fn get_retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    /// returns the Web-API version this client uses in its request urls
+    pub fn get_web_api_version(&self) -> &str {
+        &self.web_api_version
     }
 
     /**
@@ -251,16 +487,12 @@ impl<'url, A: Authenticate> Client<'url, A> {
     ```
     */
     pub async fn create(&self, entity: &impl WriteEntity) -> Result<Uuid> {
-        let reference = entity.get_reference();
+        let reference = entity.get_key_reference();
         let url_path = self.build_simple_url(reference.entity_name);
 
         async fn handle_response(response: Response) -> Result<Uuid> {
             if response.status().is_client_error() || response.status().is_server_error() {
-                let error_message = response
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| String::from("no error details provided from server"));
-                return Err(DataverseError::new(error_message));
+                return Err(DataverseError::from_response(response).await);
             }
     
             let header_value = response
@@ -289,25 +521,28 @@ impl<'url, A: Authenticate> Client<'url, A> {
     }
 
     /**
-    Updates the attributes of the gven entity in the current dataverse instance
+    Writes the given entity into the current dataverse instance and returns the written record
 
-    Please note that only those attributes are updated that are present in the
-    serialization payload. Other attributes are untouched
+    The record is returned by dataverse in the same response (`Prefer: return=representation`),
+    so server calculated columns like autonumbers, `createdon` or defaulted values are available
+    without an additional `retrieve(...)` call. The returned columns are selected by the
+    implementation of the `Select` trait of `R`
 
     This may fail for any of these reasons
     - An authentication failure
-    - A serde serialization error
+    - A serde serialization or deserialization error
     - Any http client or server error
-    - there is no record with this Uuid in the table
+    - there is already a record with this Uuid in the table
 
     # Examples
     ```rust
     use uuid::Uuid;
-    use serde::Serialize;
+    use serde::{Deserialize, Serialize};
     use powerplatform_dataverse_service_client::client::Client;
-    use powerplatform_dataverse_service_client::entity::WriteEntity;
+    use powerplatform_dataverse_service_client::entity::{ReadEntity, WriteEntity};
     use powerplatform_dataverse_service_client::reference::{Reference, ReferenceStruct};
     use powerplatform_dataverse_service_client::result::{IntoDataverseResult, Result};
+    use powerplatform_dataverse_service_client::select::Select;
 
     async fn test() -> Result<()> {
         let contact = Contact {
@@ -317,7 +552,8 @@ impl<'url, A: Authenticate> Client<'url, A> {
         };
 
         let client = Client::new_dummy(); // Please replace this with your preferred authentication method
-        client.update(&contact).await
+        let created: CreatedContact = client.create_and_retrieve(&contact).await?;
+        Ok(())
     }
 
     #[derive(Serialize)]
@@ -337,28 +573,142 @@ impl<'url, A: Authenticate> Client<'url, A> {
             )
         }
     }
+
+    #[derive(Deserialize)]
+    struct CreatedContact {
+        contactid: Uuid,
+        fullname: String,
+        createdon: String,
+    }
+
+    impl ReadEntity for CreatedContact {}
+
+    impl Select for CreatedContact {
+        fn get_columns() -> &'static [&'static str] {
+            &["contactid", "fullname", "createdon"]
+        }
+    }
     ```
     */
-    pub async fn update(&self, entity: &impl WriteEntity) -> Result<()> {
-        let reference = entity.get_reference();
-        let url_path = self.build_targeted_url(reference.entity_name, reference.entity_id);
+    pub async fn create_and_retrieve<E: WriteEntity, R: ReadEntity>(&self, entity: &E) -> Result<R> {
+        let reference = entity.get_key_reference();
+        let url_path = format!(
+            "{}?{}",
+            self.build_simple_url(reference.entity_name),
+            build_select::<R>(&[])
+        );
 
         self.request(
-            Method::PATCH,
-            &url_path, 
+            Method::POST,
+            &url_path,
             move |request| {
                 Ok(request
                     .header("Content-Type", "application/json")
-                    .header("If-Match", "*")
+                    .header("Prefer", "return=representation")
                     .body(serde_json::to_vec(entity).into_dataverse_result()?)
                 )
-            }, 
-            handle_empty_response
+            },
+            handle_entity_response
         ).await
     }
 
     /**
-    Updates or creates the given entity in the current dataverse instance
+    Creates the given entity together with the related records embedded into it and
+    returns the ids of all created records
+
+    Related records are embedded by serializing them under the name of the navigation
+    property, as an object for single-valued and as an array for collection-valued
+    navigation properties. Dataverse creates all of them in one transaction. The layout
+    describes the embedded records so their ids can be read from the returned representation
+
+    This may fail for any of these reasons
+    - An authentication failure
+    - A serde serialization error
+    - Any http client or server error
+    - The response does not contain the ids described by the layout
+
+    # Examples
+    ```rust
+    use serde::Serialize;
+    use uuid::Uuid;
+    use powerplatform_dataverse_service_client::{
+        client::Client,
+        entity::{DeepInsert, WriteEntity},
+        reference::{Reference, ReferenceStruct},
+        result::Result,
+    };
+
+    async fn test() -> Result<()> {
+        let account = Account {
+            accountid: Uuid::new_v4(),
+            name: String::from("Testy Inc."),
+            contact_customer_accounts: vec![Contact { lastname: String::from("McTestface") }],
+        };
+        let layout = DeepInsert::new("accountid")
+            .related("contact_customer_accounts", DeepInsert::new("contactid"));
+
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+        let created = client.deep_insert(&account, &layout).await?;
+        let contact_ids: Vec<Uuid> = created
+            .related("contact_customer_accounts")
+            .iter()
+            .map(|contact| contact.entity_id)
+            .collect();
+
+        Ok(())
+    }
+
+    #[derive(Serialize)]
+    struct Account {
+        accountid: Uuid,
+        name: String,
+        contact_customer_accounts: Vec<Contact>,
+    }
+
+    #[derive(Serialize)]
+    struct Contact {
+        lastname: String,
+    }
+
+    impl WriteEntity for Account {}
+
+    impl Reference for Account {
+        fn get_reference(&self) -> ReferenceStruct {
+            ReferenceStruct::new("accounts", self.accountid)
+        }
+    }
+    ```
+    */
+    pub async fn deep_insert(&self, entity: &impl WriteEntity, layout: &DeepInsert) -> Result<CreatedRecord> {
+        let reference = entity.get_key_reference();
+        let url_path = format!("{}?{}", self.build_simple_url(reference.entity_name), layout.to_select());
+
+        let handle_response = move |response: Response| async move {
+            if response.status().is_client_error() || response.status().is_server_error() {
+                return Err(DataverseError::from_response(response).await);
+            }
+
+            let content = response.bytes().await.into_dataverse_result()?;
+            let value: serde_json::Value = serde_json::from_slice(content.as_ref()).into_dataverse_result()?;
+            layout.read_ids(&value)
+        };
+
+        self.request(
+            Method::POST,
+            &url_path,
+            move |request| {
+                Ok(request
+                    .header("Content-Type", "application/json")
+                    .header("Prefer", "return=representation")
+                    .body(serde_json::to_vec(entity).into_dataverse_result()?)
+                )
+            },
+            handle_response
+        ).await
+    }
+
+    /**
+    Updates the attributes of the gven entity in the current dataverse instance
 
     Please note that only those attributes are updated that are present in the
     serialization payload. Other attributes are untouched
@@ -367,6 +717,7 @@ impl<'url, A: Authenticate> Client<'url, A> {
     - An authentication failure
     - A serde serialization error
     - Any http client or server error
+    - there is no record with this Uuid in the table
 
     # Examples
     ```rust
@@ -385,7 +736,7 @@ impl<'url, A: Authenticate> Client<'url, A> {
         };
 
         let client = Client::new_dummy(); // Please replace this with your preferred authentication method
-        client.upsert(&contact).await
+        client.update(&contact).await
     }
 
     #[derive(Serialize)]
@@ -407,16 +758,16 @@ impl<'url, A: Authenticate> Client<'url, A> {
     }
     ```
     */
-    pub async fn upsert(&self, entity: &impl WriteEntity) -> Result<()> {
-        let reference = entity.get_reference();
-        let url_path = self.build_targeted_url(reference.entity_name, reference.entity_id);
+    pub async fn update(&self, entity: &impl WriteEntity) -> Result<()> {
+        let url_path = self.build_targeted_url(&entity.get_key_reference());
 
         self.request(
-            Method::PATCH, 
+            Method::PATCH,
             &url_path, 
             move |request| {
                 Ok(request
                     .header("Content-Type", "application/json")
+                    .header("If-Match", "*")
                     .body(serde_json::to_vec(entity).into_dataverse_result()?)
                 )
             }, 
@@ -425,345 +776,196 @@ impl<'url, A: Authenticate> Client<'url, A> {
     }
 
     /**
-    Deletes the entity record this reference points to
+    Updates the attributes of the given entity and returns the updated record
 
-    Please note that each structs that implements `WriteEntity` also implements
-    `Reference` so you can use it as input here, but there is a sensible default implementation
-    called `ReferenceStruct` for those cases where you only have access to the raw
-    reference data
+    The record is returned by dataverse in the same response (`Prefer: return=representation`),
+    so server calculated columns are available without an additional `retrieve(...)` call.
+    The returned columns are selected by the implementation of the `Select` trait of `R`
 
     This may fail for any of these reasons
     - An authentication failure
+    - A serde serialization or deserialization error
     - Any http client or server error
-    - The referenced entity record doesn't exist
+    - there is no record with this Uuid in the table
 
     # Examples
     ```rust
     use uuid::Uuid;
+    use serde::{Deserialize, Serialize};
     use powerplatform_dataverse_service_client::client::Client;
-    use powerplatform_dataverse_service_client::reference::ReferenceStruct;
+    use powerplatform_dataverse_service_client::entity::{ReadEntity, WriteEntity};
+    use powerplatform_dataverse_service_client::reference::{Reference, ReferenceStruct};
     use powerplatform_dataverse_service_client::result::{IntoDataverseResult, Result};
+    use powerplatform_dataverse_service_client::select::Select;
 
-    # async fn test() -> Result<()> {
-    let reference = ReferenceStruct::new(
-        "contacts",
-        Uuid::parse_str("12345678-1234-1234-1234-123456789012").into_dataverse_result()?
-    );
+    async fn test() -> Result<()> {
+        let contact = Contact {
+            contactid: Uuid::parse_str("12345678-1234-1234-1234-123456789012").into_dataverse_result()?,
+            firstname: String::from("Testy"),
+            lastname: String::from("McTestface"),
+        };
 
-    let client = Client::new_dummy(); // Please replace this with your preferred authentication method
-    client.delete(&reference).await?;
-    # Ok(())
-    # }
-    ```
-    */
-    pub async fn delete(&self, reference: &impl Reference) -> Result<()> {
-        let reference = reference.get_reference();
-        let url_path = self.build_targeted_url(reference.entity_name, reference.entity_id);
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+        let updated: CreatedContact = client.update_and_retrieve(&contact).await?;
+        Ok(())
+    }
 
-        self.request(
-            Method::DELETE, 
-            &url_path, 
-            move |request| Ok(request), 
-            handle_empty_response
-        ).await
-    }
-
-    /**
-    retrieves the entity record that the reference points to from dataverse
-
-    This function uses the implementation of the `Select` trait to only retrieve
-    those attributes relevant to the struct defined. It is an Anti-Pattern to
-    retrieve all attributes when they are not needed, so this library does not
-    give the option to do that
-
-    This may fail for any of these reasons
-    - An authentication failure
-    - A serde deserialization error
-    - Any http client or server error
-    - The entity record referenced doesn't exist
-
-    # Examples
-    ```rust
-    use serde::Deserialize;
-    use uuid::Uuid;
-    use powerplatform_dataverse_service_client::{
-        client::Client,
-        entity::ReadEntity,
-        reference::ReferenceStruct,
-        result::{IntoDataverseResult, Result},
-        select::Select
-    };
-    
-    async fn test() -> Result<()> {
-        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
-        let contact: Contact = client
-            .retrieve(
-                &ReferenceStruct::new(
-                    "contacts",
-                    Uuid::parse_str("12345678-1234-1234-1234-123456789012").into_dataverse_result()?
-                )
-            )
-            .await?;
-        Ok(())
-    }
-
-    #[derive(Deserialize)]
+    #[derive(Serialize)]
     struct Contact {
         contactid: Uuid,
         firstname: String,
         lastname: String,
     }
 
-    impl ReadEntity for Contact {}
-
-    impl Select for Contact {
-        fn get_columns() -> &'static [&'static str] {
-            &["contactid", "firstname", "lastname"]
-        }
-    }
-    ```
-    */
-    pub async fn retrieve<E: ReadEntity>(&self, reference: &impl Reference) -> Result<E> {
-        let reference = reference.get_reference();
-        let columns = E::get_columns();
-        let url_path = self.build_retrieve_url(reference.entity_name, reference.entity_id, columns);
+    impl WriteEntity for Contact {}
 
-        async fn handle_response<E: ReadEntity>(response: Response) -> Result<E> {
-            if response.status().is_client_error() || response.status().is_server_error() {
-                let error_message = response
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| String::from("no error details provided from server"));
-                return Err(DataverseError::new(error_message));
-            }
-    
-            let content = response.bytes().await.into_dataverse_result()?;
-            serde_json::from_slice(content.as_ref()).into_dataverse_result()
+    impl Reference for Contact {
+        fn get_reference(&self) -> ReferenceStruct {
+            ReferenceStruct::new(
+                "contacts",
+                self.contactid,
+            )
         }
-
-        self.request(
-            Method::GET, 
-            &url_path, 
-            move |request| Ok(request), 
-            handle_response
-        ).await
-    }
-
-    /**
-    Executes the query and retrieves the entities from dataverse
-
-    This function uses the implementation of the `Select` trait to only retrieve
-    those attributes relevant to the struct defined. It is an Anti-Pattern to
-    retrieve all attributes when they are not needed, so this library does not
-    give the option to do that
-
-    Please note that if you don't specify a limit then the client will try to retrieve
-    up to 5000 records. Further records can then be retrieved with the `retrieve_next_page()`
-    function
-
-    This may fail for any of these reasons
-    - An authentication failure
-    - A serde deserialization error
-    - Any http client or server error
-
-    # Examples
-    ```rust
-    use uuid::Uuid;
-    use serde::Deserialize;
-    use powerplatform_dataverse_service_client::{
-        client::{Client, Page},
-        entity::ReadEntity,
-        reference::ReferenceStruct,
-        result::{IntoDataverseResult, Result},
-        select::Select,
-        query::Query
-    };
-
-    async fn test() -> Result<()> {
-        // this query retrieves the first 3 contacts
-        let query = Query::new("contacts").limit(3);
-        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
-        let contacts: Page<Contact> = client.retrieve_multiple(&query).await?;
-        Ok(())
     }
 
     #[derive(Deserialize)]
-    struct Contact {
+    struct CreatedContact {
         contactid: Uuid,
-        firstname: String,
-        lastname: String,
+        fullname: String,
+        createdon: String,
     }
 
-    impl ReadEntity for Contact {}
+    impl ReadEntity for CreatedContact {}
 
-    impl Select for Contact {
+    impl Select for CreatedContact {
         fn get_columns() -> &'static [&'static str] {
-            &["contactid", "firstname", "lastname"]
+            &["contactid", "fullname", "createdon"]
         }
     }
     ```
     */
-    pub async fn retrieve_multiple<E: ReadEntity>(&self, query: &Query) -> Result<Page<E>> {
-        let columns = E::get_columns();
-        let url_path = self.build_query_url(columns, query);
-
-        async fn handle_response<E: ReadEntity>(response: Response) -> Result<Page<E>> {
-            if response.status().is_client_error() || response.status().is_server_error() {
-                let error_message = response
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| String::from("no error details provided from server"));
-                return Err(DataverseError::new(error_message));
-            }
-    
-            let content = response.bytes().await.into_dataverse_result()?;
-            let result = serde_json::from_slice(content.as_ref()).into_dataverse_result()?;
-    
-            match result {
-                RetrieveMultipleResult {entities, next_link} => {
-                    Ok(Page::new(entities, next_link))
-                }
-            }
-        }
+    pub async fn update_and_retrieve<E: WriteEntity, R: ReadEntity>(&self, entity: &E) -> Result<R> {
+        let url_path = format!(
+            "{}?{}",
+            self.build_targeted_url(&entity.get_key_reference()),
+            build_select::<R>(&[])
+        );
 
         self.request(
-            Method::GET, 
-            &url_path, 
-            move |request| Ok(request),
-            handle_response
+            Method::PATCH,
+            &url_path,
+            move |request| {
+                Ok(request
+                    .header("Content-Type", "application/json")
+                    .header("If-Match", "*")
+                    .header("Prefer", "return=representation")
+                    .body(serde_json::to_vec(entity).into_dataverse_result()?)
+                )
+            },
+            handle_entity_response
         ).await
     }
 
     /**
-    Continues a previous query by fetching the next records after a `Page`
+    Updates the attributes of the given entity only if the record still has the given ETag
 
-    You can check with `is_incomplete()` if there are further records available to a query 
+    Use `Versioned<E>` with `retrieve(...)` to get the ETag of a record. If someone else
+    changed the record in the meantime dataverse answers with `412 Precondition Failed`,
+    which can be detected with `DataverseError::is_concurrency_conflict()`
 
     This may fail for any of these reasons
     - An authentication failure
-    - A serde deserialization error
+    - A serde serialization error
     - Any http client or server error
-    - The query already finished with the last page
+    - there is no record with this Uuid in the table
+    - the record was changed since the ETag was retrieved
 
     # Examples
     ```rust
     use uuid::Uuid;
-    use serde::Deserialize;
-    use powerplatform_dataverse_service_client::{
-        client::{Client, Page},
-        entity::ReadEntity,
-        reference::ReferenceStruct,
-        result::{IntoDataverseResult, Result},
-        select::Select,
-        query::Query
-    };
+    use serde::Serialize;
+    use powerplatform_dataverse_service_client::client::Client;
+    use powerplatform_dataverse_service_client::entity::WriteEntity;
+    use powerplatform_dataverse_service_client::reference::{Reference, ReferenceStruct};
+    use powerplatform_dataverse_service_client::result::{IntoDataverseResult, Result};
 
     async fn test() -> Result<()> {
-        let query = Query::new("contacts");
-        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
-        let contact_page1: Page<Contact> = client.retrieve_multiple(&query).await?;
+        let contact = Contact {
+            contactid: Uuid::parse_str("12345678-1234-1234-1234-123456789012").into_dataverse_result()?,
+            lastname: String::from("McTestface"),
+        };
 
-        if contact_page1.is_incomplete() {
-            let contact_page2 = client.retrieve_next_page(&contact_page1).await?;
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+        match client.update_if_match(&contact, "W/\"1234567\"").await {
+            Err(error) if error.is_concurrency_conflict() => Ok(()), // someone else was faster
+            result => result,
         }
-
-        Ok(())
     }
 
-    #[derive(Deserialize)]
+    #[derive(Serialize)]
     struct Contact {
         contactid: Uuid,
-        firstname: String,
         lastname: String,
     }
 
-    impl ReadEntity for Contact {}
+    impl WriteEntity for Contact {}
 
-    impl Select for Contact {
-        fn get_columns() -> &'static [&'static str] {
-            &["contactid", "firstname", "lastname"]
+    impl Reference for Contact {
+        fn get_reference(&self) -> ReferenceStruct {
+            ReferenceStruct::new(
+                "contacts",
+                self.contactid,
+            )
         }
     }
     ```
     */
-    pub async fn retrieve_next_page<E: ReadEntity>(&self, previous_page: &Page<E>) -> Result<Page<E>> {
-        if previous_page.next_link.is_none() {
-            return Err(DataverseError::new(String::from("There is no next page to retrieve")))
-        }
-        
-        async fn handle_response<E: ReadEntity>(response: Response) -> Result<Page<E>> {
-            if response.status().is_client_error() || response.status().is_server_error() {
-                let error_message = response
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| String::from("no error details provided from server"));
-                return Err(DataverseError::new(error_message));
-            }
-    
-            let content = response.bytes().await.into_dataverse_result()?;
-            let result = serde_json::from_slice(content.as_ref()).into_dataverse_result()?;
-    
-            match result {
-                RetrieveMultipleResult {entities, next_link} => {
-                    Ok(Page::new( entities, next_link))
-                }
-            }
-        }
+    pub async fn update_if_match(&self, entity: &impl WriteEntity, etag: &str) -> Result<()> {
+        let url_path = self.build_targeted_url(&entity.get_key_reference());
 
         self.request(
-            Method::GET, 
-            previous_page.next_link.as_ref().unwrap(), 
-            move |request| Ok(request),
-            handle_response
+            Method::PATCH,
+            &url_path,
+            move |request| {
+                Ok(request
+                    .header("Content-Type", "application/json")
+                    .header("If-Match", etag)
+                    .body(serde_json::to_vec(entity).into_dataverse_result()?)
+                )
+            },
+            handle_empty_response
         ).await
     }
 
     /**
-    executes the batch against the dataverse environment
-
-    This function will fail if:
-    - the batch size exceeds 1000 calls
-    - the batch execution time exceeds 2 minutes
+    Updates or creates the given entity in the current dataverse instance
 
-    the second restriction is especially tricky to handle because the execution time
-    depends on the complexity of the entity in dataverse.
-    So it is possible to create 300 records of an entity with low complexity
-    but only 50 records of an entity with high complexity in that timeframe.
+    Please note that only those attributes are updated that are present in the
+    serialization payload. Other attributes are untouched
 
-    Based on experience a batch size of 50 should be safe for all entities though
+    This may fail for any of these reasons
+    - An authentication failure
+    - A serde serialization error
+    - Any http client or server error
 
     # Examples
     ```rust
     use uuid::Uuid;
     use serde::Serialize;
-    use powerplatform_dataverse_service_client::{
-        batch::Batch,
-        client::Client,
-        entity::WriteEntity,
-        reference::{Reference, ReferenceStruct},
-        result::{IntoDataverseResult, Result}
-    };
+    use powerplatform_dataverse_service_client::client::Client;
+    use powerplatform_dataverse_service_client::entity::WriteEntity;
+    use powerplatform_dataverse_service_client::reference::{Reference, ReferenceStruct};
+    use powerplatform_dataverse_service_client::result::{IntoDataverseResult, Result};
 
     async fn test() -> Result<()> {
-        let testy_contact = Contact {
+        let contact = Contact {
             contactid: Uuid::parse_str("12345678-1234-1234-1234-123456789012").into_dataverse_result()?,
             firstname: String::from("Testy"),
             lastname: String::from("McTestface"),
         };
 
-        let marianne_contact = Contact {
-            contactid: Uuid::parse_str("12345678-1234-1234-1234-123456789abc").into_dataverse_result()?,
-            firstname: String::from("Marianne"),
-            lastname: String::from("McTestface"),
-        };
-
-        // this batch creates both contacts in one call
-        let mut batch = Batch::new("https://instance.crm.dynamics.com/");
-        batch.create(&testy_contact)?;
-        batch.create(&marianne_contact)?;
         let client = Client::new_dummy(); // Please replace this with your preferred authentication method
-        client.execute(&batch).await?;
-        Ok(())
+        client.upsert(&contact).await
     }
 
     #[derive(Serialize)]
@@ -785,16 +987,16 @@ impl<'url, A: Authenticate> Client<'url, A> {
     }
     ```
     */
-    pub async fn execute(&self, batch: &Batch) -> Result<()> {
-        let url_path = self.build_simple_url("$batch");
+    pub async fn upsert(&self, entity: &impl WriteEntity) -> Result<()> {
+        let url_path = self.build_targeted_url(&entity.get_key_reference());
 
         self.request(
-            Method::POST, 
+            Method::PATCH, 
             &url_path, 
             move |request| {
                 Ok(request
-                    .header("Content-Type", format!("multipart/mixed; boundary=batch_{}", batch.get_batch_id()))
-                    .body(batch.to_string())
+                    .header("Content-Type", "application/json")
+                    .body(serde_json::to_vec(entity).into_dataverse_result()?)
                 )
             }, 
             handle_empty_response
@@ -802,17 +1004,1582 @@ impl<'url, A: Authenticate> Client<'url, A> {
     }
 
     /**
-    Tries to merge two entities with and deactivates the subordinate after the process
+    Upserts the given entity with the given mode and reports whether the record was created or updated
 
-    This method is only supported for the following entities:
-    - account
-    - contact
-    - lead
-    - incident
+    - `UpsertMode::CreateOrUpdate` asks dataverse to return the written record
+      (`Prefer: return=representation`) because only then dataverse distinguishes between
+      `201 Created` and `200 OK`. Only the alternate key columns, or `versionnumber` for
+      records addressed by their primary id, are selected and the returned body is discarded
+    - `UpsertMode::CreateOnly` sends `If-None-Match: *` and fails with a duplicate key error
+      (see `DataverseError::is_duplicate_key()`) if the record already exists
+    - `UpsertMode::UpdateOnly` sends `If-Match: *` and fails with a not found error
+      (see `DataverseError::is_not_found()`) if the record doesn't exist
 
-    # Examples
-    ```rust
-    use uuid::Uuid;
+    This may fail for any of these reasons
+    - An authentication failure
+    - A serde serialization error
+    - Any http client or server error
+    - The precondition of the mode is not met
+
+    # Examples
+    ```rust
+    use serde::Serialize;
+    use powerplatform_dataverse_service_client::client::Client;
+    use powerplatform_dataverse_service_client::entity::{UpsertMode, UpsertOutcome, WriteEntity};
+    use powerplatform_dataverse_service_client::query::attribute::Attribute;
+    use powerplatform_dataverse_service_client::reference::{Addressable, KeyReference};
+    use powerplatform_dataverse_service_client::result::Result;
+
+    async fn test() -> Result<()> {
+        let account = Account {
+            accountnumber: String::from("A-100"),
+            name: String::from("Testy Inc."),
+        };
+
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+        match client.upsert_with_mode(&account, UpsertMode::CreateOrUpdate).await? {
+            UpsertOutcome::Created => println!("created A-100"),
+            UpsertOutcome::Updated => println!("updated A-100"),
+        }
+
+        Ok(())
+    }
+
+    #[derive(Serialize)]
+    struct Account {
+        accountnumber: String,
+        name: String,
+    }
+
+    impl WriteEntity for Account {}
+
+    impl Addressable for Account {
+        fn get_key_reference(&self) -> KeyReference {
+            KeyReference::alternate(
+                "accounts",
+                vec![("accountnumber", Attribute::String(self.accountnumber.clone()))],
+            )
+        }
+    }
+    ```
+    */
+    pub async fn upsert_with_mode(&self, entity: &impl WriteEntity, mode: UpsertMode) -> Result<UpsertOutcome> {
+        let reference = entity.get_key_reference();
+        let mut url_path = self.build_targeted_url(&reference);
+
+        // the representation is only requested for its status, so it should be as small as possible
+        if mode.precondition().is_none() {
+            let columns = match &reference.key {
+                RecordKey::Id(_) => String::from("versionnumber"),
+                RecordKey::Alternate(values) => values.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(","),
+            };
+
+            url_path.push_str("?$select=");
+            url_path.push_str(&columns);
+        }
+
+        let handle_response = move |response: Response| async move {
+            if response.status().is_client_error() || response.status().is_server_error() {
+                return Err(DataverseError::from_response(response).await);
+            }
+
+            match (mode, response.status()) {
+                (UpsertMode::CreateOnly, _) | (_, StatusCode::CREATED) => Ok(UpsertOutcome::Created),
+                _ => Ok(UpsertOutcome::Updated),
+            }
+        };
+
+        self.request(
+            Method::PATCH,
+            &url_path,
+            move |request| {
+                let request = match mode.precondition() {
+                    Some((header, value)) => request.header(header, value),
+                    None => request.header("Prefer", "return=representation"),
+                };
+
+                Ok(request
+                    .header("Content-Type", "application/json")
+                    .body(serde_json::to_vec(entity).into_dataverse_result()?)
+                )
+            },
+            handle_response
+        ).await
+    }
+
+    /**
+    Deletes the entity record this reference points to
+
+    Please note that each structs that implements `WriteEntity` also implements
+    `Addressable` so you can use it as input here, but there are sensible default implementations
+    called `ReferenceStruct` and `KeyReference` for those cases where you only have access to the raw
+    reference data or want to address the record by an alternate key
+
+    This may fail for any of these reasons
+    - An authentication failure
+    - Any http client or server error
+    - The referenced entity record doesn't exist
+
+    # Examples
+    ```rust
+    use uuid::Uuid;
+    use powerplatform_dataverse_service_client::client::Client;
+    use powerplatform_dataverse_service_client::reference::ReferenceStruct;
+    use powerplatform_dataverse_service_client::result::{IntoDataverseResult, Result};
+
+    # async fn test() -> Result<()> {
+    let reference = ReferenceStruct::new(
+        "contacts",
+        Uuid::parse_str("12345678-1234-1234-1234-123456789012").into_dataverse_result()?
+    );
+
+    let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+    client.delete(&reference).await?;
+    # Ok(())
+    # }
+    ```
+    */
+    pub async fn delete(&self, reference: &impl Addressable) -> Result<()> {
+        let url_path = self.build_targeted_url(&reference.get_key_reference());
+
+        self.request(
+            Method::DELETE, 
+            &url_path, 
+            Ok, 
+            handle_empty_response
+        ).await
+    }
+
+    /**
+    Deletes the entity record this reference points to only if it still has the given ETag
+
+    If someone else changed the record in the meantime dataverse answers with
+    `412 Precondition Failed`, which can be detected with `DataverseError::is_concurrency_conflict()`
+
+    # Examples
+    ```rust
+    use uuid::Uuid;
+    use powerplatform_dataverse_service_client::client::Client;
+    use powerplatform_dataverse_service_client::reference::ReferenceStruct;
+    use powerplatform_dataverse_service_client::result::{IntoDataverseResult, Result};
+
+    # async fn test() -> Result<()> {
+    let reference = ReferenceStruct::new(
+        "contacts",
+        Uuid::parse_str("12345678-1234-1234-1234-123456789012").into_dataverse_result()?
+    );
+
+    let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+    client.delete_if_match(&reference, "W/\"1234567\"").await?;
+    # Ok(())
+    # }
+    ```
+    */
+    pub async fn delete_if_match(&self, reference: &impl Addressable, etag: &str) -> Result<()> {
+        let url_path = self.build_targeted_url(&reference.get_key_reference());
+
+        self.request(
+            Method::DELETE,
+            &url_path,
+            move |request| Ok(request.header("If-Match", etag)),
+            handle_empty_response
+        ).await
+    }
+
+    /**
+    Associates two records through the given collection-valued navigation property
+
+    This links the records of a N:N relationship or adds the record `to` to the
+    1:N relationship of the record `from`. Use `set_reference(...)` for single-valued
+    navigation properties (lookups)
+
+    If the navigation property doesn't exist dataverse answers with an error that can be
+    detected with `DataverseError::is_missing_relationship()`
+
+    # Examples
+    ```rust
+    use uuid::Uuid;
+    use powerplatform_dataverse_service_client::client::Client;
+    use powerplatform_dataverse_service_client::reference::ReferenceStruct;
+    use powerplatform_dataverse_service_client::result::{IntoDataverseResult, Result};
+
+    # async fn test() -> Result<()> {
+    let account = ReferenceStruct::new(
+        "accounts",
+        Uuid::parse_str("12345678-1234-1234-1234-123456789012").into_dataverse_result()?
+    );
+    let opportunity = ReferenceStruct::new(
+        "opportunities",
+        Uuid::parse_str("12345678-1234-1234-1234-123456789abc").into_dataverse_result()?
+    );
+
+    let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+    client.associate(&account, "opportunity_customer_accounts", &opportunity).await?;
+    # Ok(())
+    # }
+    ```
+    */
+    pub async fn associate(&self, from: &impl Addressable, relationship: &str, to: &impl Addressable) -> Result<()> {
+        let url_path = format!("{}/{}/$ref", self.build_targeted_url(&from.get_key_reference()), relationship);
+        let body = self.build_odata_id(&to.get_key_reference());
+
+        self.request(
+            Method::POST,
+            &url_path,
+            move |request| Ok(request.header("Content-Type", "application/json").body(body)),
+            handle_empty_response
+        ).await
+    }
+
+    /**
+    Removes the association of two records through the given collection-valued navigation property
+
+    This is the counterpart to `associate(...)`. Use `clear_reference(...)` for
+    single-valued navigation properties (lookups)
+
+    # Examples
+    ```rust
+    use uuid::Uuid;
+    use powerplatform_dataverse_service_client::client::Client;
+    use powerplatform_dataverse_service_client::reference::ReferenceStruct;
+    use powerplatform_dataverse_service_client::result::{IntoDataverseResult, Result};
+
+    # async fn test() -> Result<()> {
+    let account = ReferenceStruct::new(
+        "accounts",
+        Uuid::parse_str("12345678-1234-1234-1234-123456789012").into_dataverse_result()?
+    );
+    let opportunity = ReferenceStruct::new(
+        "opportunities",
+        Uuid::parse_str("12345678-1234-1234-1234-123456789abc").into_dataverse_result()?
+    );
+
+    let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+    client.disassociate(&account, "opportunity_customer_accounts", &opportunity).await?;
+    # Ok(())
+    # }
+    ```
+    */
+    pub async fn disassociate(&self, from: &impl Addressable, relationship: &str, to: &impl Addressable) -> Result<()> {
+        let url_path = format!(
+            "{}/{}/$ref?$id={}",
+            self.build_targeted_url(&from.get_key_reference()),
+            relationship,
+            encode_path(&self.build_targeted_url(&to.get_key_reference()))
+        );
+
+        self.request(
+            Method::DELETE,
+            &url_path,
+            Ok,
+            handle_empty_response
+        ).await
+    }
+
+    /**
+    Sets the single-valued navigation property (lookup) of the record `from` to the record `to`
+
+    # Examples
+    ```rust
+    use uuid::Uuid;
+    use powerplatform_dataverse_service_client::client::Client;
+    use powerplatform_dataverse_service_client::reference::ReferenceStruct;
+    use powerplatform_dataverse_service_client::result::{IntoDataverseResult, Result};
+
+    # async fn test() -> Result<()> {
+    let contact = ReferenceStruct::new(
+        "contacts",
+        Uuid::parse_str("12345678-1234-1234-1234-123456789012").into_dataverse_result()?
+    );
+    let account = ReferenceStruct::new(
+        "accounts",
+        Uuid::parse_str("12345678-1234-1234-1234-123456789abc").into_dataverse_result()?
+    );
+
+    let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+    client.set_reference(&contact, "parentcustomerid_account", &account).await?;
+    # Ok(())
+    # }
+    ```
+    */
+    pub async fn set_reference(&self, from: &impl Addressable, navigation_property: &str, to: &impl Addressable) -> Result<()> {
+        let url_path = format!("{}/{}/$ref", self.build_targeted_url(&from.get_key_reference()), navigation_property);
+        let body = self.build_odata_id(&to.get_key_reference());
+
+        self.request(
+            Method::PUT,
+            &url_path,
+            move |request| Ok(request.header("Content-Type", "application/json").body(body)),
+            handle_empty_response
+        ).await
+    }
+
+    /**
+    Clears the single-valued navigation property (lookup) of the given record
+
+    # Examples
+    ```rust
+    use uuid::Uuid;
+    use powerplatform_dataverse_service_client::client::Client;
+    use powerplatform_dataverse_service_client::reference::ReferenceStruct;
+    use powerplatform_dataverse_service_client::result::{IntoDataverseResult, Result};
+
+    # async fn test() -> Result<()> {
+    let contact = ReferenceStruct::new(
+        "contacts",
+        Uuid::parse_str("12345678-1234-1234-1234-123456789012").into_dataverse_result()?
+    );
+
+    let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+    client.clear_reference(&contact, "parentcustomerid_account").await?;
+    # Ok(())
+    # }
+    ```
+    */
+    pub async fn clear_reference(&self, from: &impl Addressable, navigation_property: &str) -> Result<()> {
+        let url_path = format!("{}/{}/$ref", self.build_targeted_url(&from.get_key_reference()), navigation_property);
+
+        self.request(
+            Method::DELETE,
+            &url_path,
+            Ok,
+            handle_empty_response
+        ).await
+    }
+
+    /**
+    Reads the value of a single column of the given record
+
+    Returns `None` if the column is empty
+
+    # Examples
+    ```rust
+    use uuid::Uuid;
+    use powerplatform_dataverse_service_client::client::Client;
+    use powerplatform_dataverse_service_client::reference::ReferenceStruct;
+    use powerplatform_dataverse_service_client::result::{IntoDataverseResult, Result};
+
+    # async fn test() -> Result<()> {
+    let contact = ReferenceStruct::new(
+        "contacts",
+        Uuid::parse_str("12345678-1234-1234-1234-123456789012").into_dataverse_result()?
+    );
+
+    let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+    let email: Option<String> = client.get_column(&contact, "emailaddress1").await?;
+    # Ok(())
+    # }
+    ```
+    */
+    pub async fn get_column<T: DeserializeOwned>(&self, reference: &impl Addressable, column: &str) -> Result<Option<T>> {
+        let url_path = format!("{}/{}", self.build_targeted_url(&reference.get_key_reference()), column);
+
+        async fn handle_response<T: DeserializeOwned>(response: Response) -> Result<Option<T>> {
+            if response.status().is_client_error() || response.status().is_server_error() {
+                return Err(DataverseError::from_response(response).await);
+            }
+
+            if response.status() == StatusCode::NO_CONTENT {
+                return Ok(None);
+            }
+
+            let content = response.bytes().await.into_dataverse_result()?;
+            let column: ColumnValue<Option<T>> = serde_json::from_slice(content.as_ref()).into_dataverse_result()?;
+            Ok(column.value)
+        }
+
+        self.request(
+            Method::GET,
+            &url_path,
+            Ok,
+            handle_response
+        ).await
+    }
+
+    /**
+    Writes the value of a single column of the given record
+
+    Unlike an update with a `WriteEntity` this only touches the given column
+
+    # Examples
+    ```rust
+    use uuid::Uuid;
+    use powerplatform_dataverse_service_client::client::Client;
+    use powerplatform_dataverse_service_client::reference::ReferenceStruct;
+    use powerplatform_dataverse_service_client::result::{IntoDataverseResult, Result};
+
+    # async fn test() -> Result<()> {
+    let contact = ReferenceStruct::new(
+        "contacts",
+        Uuid::parse_str("12345678-1234-1234-1234-123456789012").into_dataverse_result()?
+    );
+
+    let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+    client.set_column(&contact, "donotemail", &true).await?;
+    # Ok(())
+    # }
+    ```
+    */
+    pub async fn set_column<T: Serialize>(&self, reference: &impl Addressable, column: &str, value: &T) -> Result<()> {
+        let url_path = format!("{}/{}", self.build_targeted_url(&reference.get_key_reference()), column);
+
+        self.request(
+            Method::PUT,
+            &url_path,
+            move |request| {
+                Ok(request
+                    .header("Content-Type", "application/json")
+                    .body(serde_json::to_vec(&ColumnValue { value }).into_dataverse_result()?)
+                )
+            },
+            handle_empty_response
+        ).await
+    }
+
+    /**
+    Clears the value of a single column of the given record
+
+    Lookup columns can't be cleared by their name, pass their single-valued navigation
+    property as `Column::Lookup` instead, which sends `DELETE <record>/<navigation property>/$ref`
+    like `clear_reference(...)`
+
+    # Examples
+    ```rust
+    use uuid::Uuid;
+    use powerplatform_dataverse_service_client::client::{Client, Column};
+    use powerplatform_dataverse_service_client::reference::ReferenceStruct;
+    use powerplatform_dataverse_service_client::result::{IntoDataverseResult, Result};
+
+    # async fn test() -> Result<()> {
+    let contact = ReferenceStruct::new(
+        "contacts",
+        Uuid::parse_str("12345678-1234-1234-1234-123456789012").into_dataverse_result()?
+    );
+
+    let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+    client.clear_column(&contact, "telephone1").await?;
+    client.clear_column(&contact, Column::Lookup("parentcustomerid_account")).await?;
+    # Ok(())
+    # }
+    ```
+    */
+    pub async fn clear_column<'c>(&self, reference: &impl Addressable, column: impl Into<Column<'c>>) -> Result<()> {
+        let url_path = format!("{}/{}", self.build_targeted_url(&reference.get_key_reference()), column.into().path());
+
+        self.request(
+            Method::DELETE,
+            &url_path,
+            Ok,
+            handle_empty_response
+        ).await
+    }
+
+    /**
+    Uploads the content of the reader into the given file or image column
+
+    The file is sent in blocks with the chunked transfer protocol of dataverse, so only
+    one block is held in memory at a time. The reader has to provide exactly the number
+    of bytes given in the `FileUpload`. A size mismatch is detected before the last block
+    is sent, so dataverse never commits the file and the column keeps its previous content.
+    The blocks sent before are discarded by dataverse once the upload session expires
+
+    This may fail for any of these reasons
+    - An authentication failure
+    - Any http client or server error
+    - The announced size is 0, use `clear_column(...)` to remove a file instead
+    - The reader fails or provides more or less bytes than announced
+    - The file is larger than the maximum size configured for the column
+
+    # Examples
+    ```rust
+    use uuid::Uuid;
+    use powerplatform_dataverse_service_client::{
+        client::Client,
+        file::FileUpload,
+        reference::ReferenceStruct,
+        result::{IntoDataverseResult, Result},
+    };
+
+    async fn test() -> Result<()> {
+        let file = tokio::fs::File::open("report.pdf").await.into_dataverse_result()?;
+        let size = file.metadata().await.into_dataverse_result()?.len();
+        let account = ReferenceStruct::new("accounts", Uuid::new_v4());
+
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+        let upload = FileUpload::new("report.pdf", size).block_size(1024 * 1024);
+        client.upload_file(&account, "cr_document", &upload, file).await
+    }
+    ```
+    */
+    pub async fn upload_file<R: AsyncRead + Unpin>(&self, reference: &impl Addressable, column: &str, upload: &FileUpload, mut reader: R) -> Result<()> {
+        // the chunked transfer protocol has no block to commit an empty file with
+        if upload.size == 0 {
+            return Err(DataverseError::new(String::from(
+                "empty files can't be uploaded, use `clear_column(...)` to remove a file instead"
+            )));
+        }
+
+        let url_path = format!(
+            "{}/{}?x-ms-file-name={}",
+            self.build_targeted_url(&reference.get_key_reference()),
+            column,
+            encode_path(&upload.file_name)
+        );
+
+        let base_url = Url::parse(&url_path).into_dataverse_result()?;
+        let handle_response = move |response: Response| async move {
+            if response.status().is_client_error() || response.status().is_server_error() {
+                return Err(DataverseError::from_response(response).await);
+            }
+
+            let header = |name: &str| response.headers().get(name).and_then(|value| value.to_str().ok());
+            let location = header("Location")
+                .ok_or_else(|| DataverseError::new(String::from("Dataverse provided no upload location")))?;
+            let location = base_url.join(location).into_dataverse_result()?;
+            let block_size = header("x-ms-chunk-size").and_then(|value| value.parse::<u32>().ok());
+
+            Ok((location, block_size))
+        };
+
+        let (location, proposed_block_size) = self.request(
+            Method::PATCH,
+            &url_path,
+            |request| Ok(request.header("x-ms-transfer-mode", "chunked")),
+            handle_response
+        ).await?;
+
+        let block_size = upload.block_size.or(proposed_block_size).unwrap_or(DEFAULT_BLOCK_SIZE).max(1) as u64;
+        let mut offset = 0u64;
+
+        while offset < upload.size {
+            let mut block = vec![0u8; block_size.min(upload.size - offset) as usize];
+            let mut filled = 0;
+            while filled < block.len() {
+                let read = reader.read(&mut block[filled..]).await.into_dataverse_result()?;
+                if read == 0 {
+                    return Err(DataverseError::new(format!(
+                        "the file has {} bytes instead of the announced size of {} bytes",
+                        offset + filled as u64,
+                        upload.size
+                    )));
+                }
+
+                filled += read;
+            }
+
+            let end = offset + filled as u64;
+            if end == upload.size && reader.read(&mut [0u8; 1]).await.into_dataverse_result()? > 0 {
+                return Err(DataverseError::new(format!("the file is larger than the announced size of {} bytes", upload.size)));
+            }
+
+            self.request(
+                Method::PATCH,
+                location.as_str(),
+                move |request| {
+                    Ok(request
+                        .header("Content-Type", "application/octet-stream")
+                        .header("Content-Range", format!("bytes {}-{}/{}", offset, end - 1, upload.size))
+                        .header("x-ms-file-name", upload.file_name.as_str())
+                        .body(block)
+                    )
+                },
+                handle_empty_response
+            ).await?;
+
+            offset = end;
+        }
+
+        Ok(())
+    }
+
+    /**
+    Downloads the content of the given file column in blocks of at most `block_size` bytes
+
+    The first block is requested right away to learn the name and the size of the file.
+    The remaining blocks are requested while the returned `FileDownload` is consumed,
+    either as `Stream` of blocks or as `AsyncRead`
+
+    # Examples
+    ```rust
+    use uuid::Uuid;
+    use powerplatform_dataverse_service_client::{
+        client::Client,
+        file::DEFAULT_BLOCK_SIZE,
+        reference::ReferenceStruct,
+        result::{IntoDataverseResult, Result},
+    };
+
+    async fn test() -> Result<()> {
+        let account = ReferenceStruct::new("accounts", Uuid::new_v4());
+
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+        let mut download = client.download_file(&account, "cr_document", DEFAULT_BLOCK_SIZE).await?;
+        let mut file = tokio::fs::File::create(download.file_name().unwrap_or("document")).await.into_dataverse_result()?;
+        tokio::io::copy(&mut download, &mut file).await.into_dataverse_result()?;
+        Ok(())
+    }
+    ```
+    */
+    pub async fn download_file(&self, reference: &impl Addressable, column: &str, block_size: u32) -> Result<FileDownload<'_>>
+    where
+        A: Send + Sync,
+    {
+        let url_path = format!("{}/{}/$value", self.build_targeted_url(&reference.get_key_reference()), column);
+        FileDownload::start(self, url_path, block_size).await
+    }
+
+    /**
+    Downloads the full-size image or the thumbnail of the given image column in blocks
+    of at most `block_size` bytes
+
+    See `download_file(...)` for how the blocks are requested
+    */
+    pub async fn download_image(&self, reference: &impl Addressable, column: &str, size: ImageSize, block_size: u32) -> Result<FileDownload<'_>>
+    where
+        A: Send + Sync,
+    {
+        let mut url_path = format!("{}/{}/$value", self.build_targeted_url(&reference.get_key_reference()), column);
+        if size == ImageSize::Full {
+            url_path.push_str("?size=full");
+        }
+
+        FileDownload::start(self, url_path, block_size).await
+    }
+
+    /**
+    retrieves the entity record that the reference points to from dataverse
+
+    This function uses the implementation of the `Select` trait to only retrieve
+    those attributes relevant to the struct defined. It is an Anti-Pattern to
+    retrieve all attributes when they are not needed, so this library does not
+    give the option to do that
+
+    This may fail for any of these reasons
+    - An authentication failure
+    - A serde deserialization error
+    - Any http client or server error
+    - The entity record referenced doesn't exist
+
+    # Examples
+    ```rust
+    use serde::Deserialize;
+    use uuid::Uuid;
+    use powerplatform_dataverse_service_client::{
+        client::Client,
+        entity::ReadEntity,
+        reference::ReferenceStruct,
+        result::{IntoDataverseResult, Result},
+        select::Select
+    };
+    
+    async fn test() -> Result<()> {
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+        let contact: Contact = client
+            .retrieve(
+                &ReferenceStruct::new(
+                    "contacts",
+                    Uuid::parse_str("12345678-1234-1234-1234-123456789012").into_dataverse_result()?
+                )
+            )
+            .await?;
+        Ok(())
+    }
+
+    #[derive(Deserialize)]
+    struct Contact {
+        contactid: Uuid,
+        firstname: String,
+        lastname: String,
+    }
+
+    impl ReadEntity for Contact {}
+
+    impl Select for Contact {
+        fn get_columns() -> &'static [&'static str] {
+            &["contactid", "firstname", "lastname"]
+        }
+    }
+    ```
+    */
+    pub async fn retrieve<E: ReadEntity>(&self, reference: &impl Addressable) -> Result<E> {
+        let url_path = self.build_retrieve_url(&reference.get_key_reference(), &build_select::<E>(&[]));
+
+        self.request(
+            Method::GET, 
+            &url_path, 
+            Ok, 
+            handle_entity_response
+        ).await
+    }
+
+    /**
+    retrieves the entity record that the reference points to only if it changed since the given ETag
+
+    If the record still has the given ETag dataverse answers with `304 Not Modified`
+    and `Conditional::NotModified` is returned without transferring the record again.
+    Use `Versioned<E>` as entity type to get the new ETag of a modified record
+
+    This may fail for any of these reasons
+    - An authentication failure
+    - A serde deserialization error
+    - Any http client or server error
+    - The entity record referenced doesn't exist
+
+    # Examples
+    ```rust
+    use serde::Deserialize;
+    use uuid::Uuid;
+    use powerplatform_dataverse_service_client::{
+        client::Client,
+        entity::{Conditional, ReadEntity, Versioned},
+        reference::ReferenceStruct,
+        result::{IntoDataverseResult, Result},
+        select::Select
+    };
+
+    async fn test(cached: Versioned<Contact>) -> Result<Versioned<Contact>> {
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+        let reference = ReferenceStruct::new("contacts", cached.entity.contactid);
+
+        match client.retrieve_if_none_match(&reference, cached.etag.as_deref().unwrap_or("")).await? {
+            Conditional::Modified(contact) => Ok(contact),
+            Conditional::NotModified => Ok(cached),
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct Contact {
+        contactid: Uuid,
+        firstname: String,
+        lastname: String,
+    }
+
+    impl ReadEntity for Contact {}
+
+    impl Select for Contact {
+        fn get_columns() -> &'static [&'static str] {
+            &["contactid", "firstname", "lastname"]
+        }
+    }
+    ```
+    */
+    pub async fn retrieve_if_none_match<E: ReadEntity>(&self, reference: &impl Addressable, etag: &str) -> Result<Conditional<E>> {
+        let url_path = self.build_retrieve_url(&reference.get_key_reference(), &build_select::<E>(&[]));
+
+        async fn handle_response<E: ReadEntity>(response: Response) -> Result<Conditional<E>> {
+            if response.status() == StatusCode::NOT_MODIFIED {
+                return Ok(Conditional::NotModified);
+            }
+
+            handle_entity_response(response).await.map(Conditional::Modified)
+        }
+
+        self.request(
+            Method::GET,
+            &url_path,
+            move |request| Ok(request.header("If-None-Match", etag)),
+            handle_response
+        ).await
+    }
+
+    /**
+    Executes the query and retrieves the entities from dataverse
+
+    This function uses the implementation of the `Select` trait to only retrieve
+    those attributes relevant to the struct defined. It is an Anti-Pattern to
+    retrieve all attributes when they are not needed, so this library does not
+    give the option to do that
+
+    Please note that if you don't specify a limit then the client will try to retrieve
+    up to 5000 records. Further records can then be retrieved with the `retrieve_next_page()`
+    function
+
+    This may fail for any of these reasons
+    - An authentication failure
+    - A serde deserialization error
+    - Any http client or server error
+
+    # Examples
+    ```rust
+    use uuid::Uuid;
+    use serde::Deserialize;
+    use powerplatform_dataverse_service_client::{
+        client::{Client, Page},
+        entity::ReadEntity,
+        reference::ReferenceStruct,
+        result::{IntoDataverseResult, Result},
+        select::Select,
+        query::Query
+    };
+
+    async fn test() -> Result<()> {
+        // this query retrieves the first 3 contacts
+        let query = Query::new("contacts").limit(3);
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+        let contacts: Page<Contact> = client.retrieve_multiple(&query).await?;
+        Ok(())
+    }
+
+    #[derive(Deserialize)]
+    struct Contact {
+        contactid: Uuid,
+        firstname: String,
+        lastname: String,
+    }
+
+    impl ReadEntity for Contact {}
+
+    impl Select for Contact {
+        fn get_columns() -> &'static [&'static str] {
+            &["contactid", "firstname", "lastname"]
+        }
+    }
+    ```
+    */
+    pub async fn retrieve_multiple<E: ReadEntity>(&self, query: &Query) -> Result<Page<E>> {
+        self.retrieve_page(self.build_query_cursor(&build_select::<E>(&query.expand), query)).await
+    }
+
+    /**
+    Continues a previous query by fetching the next records after a `Page`
+
+    You can check with `is_incomplete()` if there are further records available to a query.
+    If the query already finished with the given page `None` is returned
+
+    This may fail for any of these reasons
+    - An authentication failure
+    - A serde deserialization error
+    - Any http client or server error
+
+    # Examples
+    ```rust
+    use uuid::Uuid;
+    use serde::Deserialize;
+    use powerplatform_dataverse_service_client::{
+        client::{Client, Page},
+        entity::ReadEntity,
+        reference::ReferenceStruct,
+        result::{IntoDataverseResult, Result},
+        select::Select,
+        query::Query
+    };
+
+    async fn test() -> Result<()> {
+        let query = Query::new("contacts");
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+        let contact_page1: Page<Contact> = client.retrieve_multiple(&query).await?;
+
+        if let Some(contact_page2) = client.retrieve_next_page(&contact_page1).await? {
+            println!("{} more contacts", contact_page2.entities.len());
+        }
+
+        Ok(())
+    }
+
+    #[derive(Deserialize)]
+    struct Contact {
+        contactid: Uuid,
+        firstname: String,
+        lastname: String,
+    }
+
+    impl ReadEntity for Contact {}
+
+    impl Select for Contact {
+        fn get_columns() -> &'static [&'static str] {
+            &["contactid", "firstname", "lastname"]
+        }
+    }
+    ```
+    */
+    pub async fn retrieve_next_page<E: ReadEntity>(&self, previous_page: &Page<E>) -> Result<Option<Page<E>>> {
+        match previous_page.cursor() {
+            Some(cursor) => Ok(Some(self.retrieve_page(cursor).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /**
+    Continues a query from a cursor that was saved from a previous `Page`
+
+    This allows long running exports to be resumed after a restart. Use
+    `PageCursor::is_for(...)` to make sure the cursor belongs to the expected query
+
+    This may fail for any of these reasons
+    - An authentication failure
+    - A serde deserialization error
+    - Any http client or server error
+    - The cursor points to another dataverse environment than this client
+
+    # Examples
+    ```rust
+    use uuid::Uuid;
+    use serde::Deserialize;
+    use powerplatform_dataverse_service_client::{
+        client::{Client, Page, PageCursor},
+        entity::ReadEntity,
+        result::{IntoDataverseResult, Result},
+        select::Select,
+        query::Query
+    };
+
+    async fn test() -> Result<()> {
+        let query = Query::new("contacts").max_page_size(500);
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+        let page: Page<Contact> = client.retrieve_multiple(&query).await?;
+
+        if let Some(cursor) = page.cursor() {
+            let saved = serde_json::to_string(&cursor).into_dataverse_result()?;
+
+            // ... after a restart
+            let cursor: PageCursor = serde_json::from_str(&saved).into_dataverse_result()?;
+            if cursor.is_for::<Contact>(&query) {
+                let next_page: Page<Contact> = client.resume_from(&cursor).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[derive(Deserialize)]
+    struct Contact {
+        contactid: Uuid,
+        firstname: String,
+        lastname: String,
+    }
+
+    impl ReadEntity for Contact {}
+
+    impl Select for Contact {
+        fn get_columns() -> &'static [&'static str] {
+            &["contactid", "firstname", "lastname"]
+        }
+    }
+    ```
+    */
+    pub async fn resume_from<E: ReadEntity>(&self, cursor: &PageCursor) -> Result<Page<E>> {
+        if !cursor.next_link.starts_with(self.url.as_ref()) {
+            return Err(DataverseError::new(format!(
+                "the cursor points to '{}' which does not belong to '{}'",
+                cursor.next_link, self.url
+            )));
+        }
+
+        self.retrieve_page(cursor.clone()).await
+    }
+
+    /**
+    Executes the query and streams the entities of all its pages
+
+    The `@odata.nextLink` of each page is followed automatically. While the entities
+    of a page are consumed the next page is already requested, so slow consumers don't
+    wait for the network after each page
+
+    The stream ends after the first error
+
+    # Examples
+    ```rust
+    use futures::StreamExt;
+    use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
+    use powerplatform_dataverse_service_client::{
+        client::Client,
+        entity::ReadEntity,
+        result::Result,
+        select::Select,
+        query::Query
+    };
+
+    async fn test() -> Result<()> {
+        let query = Query::new("contacts");
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+        let mut contacts = client.retrieve_stream::<Contact>(&query);
+
+        while let Some(contact) = contacts.next().await {
+            println!("{}", contact?.lastname);
+        }
+
+        Ok(())
+    }
+
+    #[derive(Deserialize)]
+    struct Contact {
+        contactid: Uuid,
+        firstname: String,
+        lastname: String,
+    }
+
+    impl ReadEntity for Contact {}
+
+    impl Select for Contact {
+        fn get_columns() -> &'static [&'static str] {
+            &["contactid", "firstname", "lastname"]
+        }
+    }
+    ```
+    */
+    pub fn retrieve_stream<'a, E>(&'a self, query: &Query) -> EntityStream<'a, E>
+    where
+        E: ReadEntity + Send + 'a,
+        A: Send + Sync,
+    {
+        EntityStream::new(self, self.build_query_cursor(&build_select::<E>(&query.expand), query))
+    }
+
+    /**
+    Executes the query and collects the entities of all its pages into a `Vec`
+
+    If `max_entities` is given the retrieval stops as soon as that many entities
+    have been collected and the remaining pages are not read
+
+    This may fail for any of these reasons
+    - An authentication failure
+    - A serde deserialization error
+    - Any http client or server error
+
+    # Examples
+    ```rust
+    use serde::Deserialize;
+    use uuid::Uuid;
+    use powerplatform_dataverse_service_client::{
+        client::Client,
+        entity::ReadEntity,
+        result::Result,
+        select::Select,
+        query::Query
+    };
+
+    async fn test() -> Result<()> {
+        let query = Query::new("contacts");
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+        let contacts: Vec<Contact> = client.retrieve_all(&query, Some(20_000)).await?;
+        Ok(())
+    }
+
+    #[derive(Deserialize)]
+    struct Contact {
+        contactid: Uuid,
+        firstname: String,
+        lastname: String,
+    }
+
+    impl ReadEntity for Contact {}
+
+    impl Select for Contact {
+        fn get_columns() -> &'static [&'static str] {
+            &["contactid", "firstname", "lastname"]
+        }
+    }
+    ```
+    */
+    pub async fn retrieve_all<E>(&self, query: &Query, max_entities: Option<usize>) -> Result<Vec<E>>
+    where
+        E: ReadEntity + Send,
+        A: Send + Sync,
+    {
+        let mut stream = self.retrieve_stream::<E>(query);
+        let mut entities = Vec::new();
+
+        loop {
+            if matches!(max_entities, Some(max) if entities.len() >= max) {
+                break;
+            }
+
+            match stream.next().await {
+                Some(entity) => entities.push(entity?),
+                None => break,
+            }
+        }
+
+        Ok(entities)
+    }
+
+    /**
+    Counts the records that match the given query
+
+    Only the filter of the query is relevant, the count is requested with `$count=true`
+    together with a single record of which only the `versionnumber` column is selected. Dataverse counts at most 5000 records, so check
+    `limit_exceeded` of the result or use `retrieve_total_record_count(...)` for large tables
+
+    This may fail for any of these reasons
+    - An authentication failure
+    - A serde deserialization error
+    - Any http client or server error
+
+    # Examples
+    ```rust
+    use powerplatform_dataverse_service_client::{
+        client::Client,
+        query::{attribute::Attribute, filter::Filter, Query},
+        result::Result,
+    };
+
+    async fn test() -> Result<()> {
+        let query = Query::new("contacts").filter(Filter::Equal("statecode", Attribute::Integer(0)));
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+        let active = client.count(&query).await?;
+
+        if active.limit_exceeded {
+            println!("more than {} active contacts", active.count);
+        } else {
+            println!("{} active contacts", active.count);
+        }
+
+        Ok(())
+    }
+    ```
+    */
+    pub async fn count(&self, query: &Query) -> Result<TotalCount> {
+        let query = Query {
+            limit: None,
+            order: None,
+            max_page_size: Some(1),
+            expand: Vec::new(),
+            count: true,
+            ..query.clone()
+        };
+
+        let page: Page<IgnoredAny> = self.retrieve_page(self.build_query_cursor("$select=versionnumber", &query)).await?;
+        page.total_count.ok_or_else(|| DataverseError::with_kind(
+            ErrorKind::Serialization,
+            String::from("Dataverse provided no @odata.count for the query"),
+        ))
+    }
+
+    /**
+    Returns the approximate number of records of the given tables by their logical names
+
+    The counts are read with the `RetrieveTotalRecordCount` function from a snapshot
+    dataverse refreshes every few hours, so they are not capped at 5000 but don't
+    include recent changes
+
+    # Examples
+    ```rust
+    use powerplatform_dataverse_service_client::{client::Client, result::Result};
+
+    async fn test() -> Result<()> {
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+
+        for (table, count) in client.retrieve_total_record_count(&["account", "contact"]).await? {
+            println!("{}: about {} records", table, count);
+        }
+
+        Ok(())
+    }
+    ```
+    */
+    pub async fn retrieve_total_record_count(&self, entity_names: &[&str]) -> Result<Vec<(String, i64)>> {
+        let function = RetrieveTotalRecordCount {
+            entity_names: entity_names.iter().map(|name| name.to_string()).collect(),
+        };
+
+        let counts = self.call_function(&function).await?.entity_record_count_collection;
+        Ok(counts.keys.into_iter().zip(counts.values).collect())
+    }
+
+    /**
+    Executes the given query with `$apply` transformations and returns the aggregated results
+
+    The results are deserialized into `R`, whose fields are the grouped columns and the
+    aliases of the aggregations. Since the results are no entities, `R` only needs
+    to implement `Deserialize`
+
+    This may fail for any of these reasons
+    - An authentication failure
+    - A serde deserialization error
+    - Any http client or server error, e.g. when more than 50.000 records are aggregated
+
+    # Examples
+    ```rust
+    use serde::Deserialize;
+    use uuid::Uuid;
+    use powerplatform_dataverse_service_client::{
+        client::Client,
+        query::{apply::{Aggregate, Transformation}, attribute::Attribute, filter::Filter, Query},
+        result::Result,
+    };
+
+    #[derive(Deserialize)]
+    struct RevenuePerOwner {
+        #[serde(rename = "_ownerid_value")]
+        owner_id: Uuid,
+        total: Option<f64>,
+        count: i64,
+    }
+
+    async fn test() -> Result<Vec<RevenuePerOwner>> {
+        let query = Query::new("opportunities")
+            .apply(Transformation::Filter(Filter::Equal("statecode", Attribute::Integer(0))))
+            .apply(Transformation::GroupBy(
+                vec!["_ownerid_value"],
+                vec![Aggregate::Sum("estimatedvalue", "total"), Aggregate::Count("count")],
+            ));
+
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+        client.aggregate(&query).await
+    }
+    ```
+    */
+    pub async fn aggregate<R: DeserializeOwned>(&self, query: &Query) -> Result<Vec<R>> {
+        let mut page: Page<R> = self.retrieve_page(self.build_query_cursor("", query)).await?;
+        let mut results = Vec::new();
+
+        while let Some(cursor) = page.cursor() {
+            let next_page = self.retrieve_page(cursor).await?;
+            results.append(&mut page.entities);
+            page = next_page;
+        }
+
+        results.append(&mut page.entities);
+        Ok(results)
+    }
+
+    /**
+    Executes the query with change tracking and returns the first page of all matching records
+
+    The query is sent with `Prefer: odata.track-changes`. Further pages are requested with
+    `resume_changes(...)` and the last page carries the token, which can be stored and
+    passed to `retrieve_changes(...)` later. Only the filter on the table level is supported
+    by dataverse for tracked queries, so `limit`, `order` and `expand` should not be used
+
+    This may fail for any of these reasons
+    - Change tracking is not enabled for the table (see `DataverseError::is_change_tracking_disabled()`)
+    - An authentication failure
+    - A serde deserialization error
+    - Any http client or server error
+
+    See the `changes` module for an example
+    */
+    pub async fn track_changes<E: ReadEntity>(&self, query: &Query) -> Result<Changes<E>> {
+        let cursor = self.build_query_cursor(&build_select::<E>(&query.expand), query);
+        self.retrieve_tracked_page(&cursor.next_link, query, cursor.query_fingerprint).await
+    }
+
+    /**
+    Returns the first page of the records that were created, updated or deleted since the
+    given token was issued
+
+    The query has to be the one the token was created with. It provides the entity set
+    name of deleted records and the page size, while the selected columns and the filter
+    are part of the token. Further pages are requested with `resume_changes(...)`
+
+    This may fail for any of these reasons
+    - The token was created for another query or entity type
+    - Change tracking was disabled for the table or the token expired (see `DataverseError::is_change_tracking_disabled()`)
+    - An authentication failure
+    - A serde deserialization error
+    - Any http client or server error
+
+    See the `changes` module for an example
+    */
+    pub async fn retrieve_changes<E: ReadEntity>(&self, query: &Query, token: &ChangeToken) -> Result<Changes<E>> {
+        self.check_change_fingerprint::<E>(query, &token.query_fingerprint)?;
+        self.retrieve_tracked_page(&token.delta_link, query, token.query_fingerprint.clone()).await
+    }
+
+    /**
+    Returns the page of changes the given cursor points to
+
+    The query has to be the one the cursor was created with, see `retrieve_changes(...)`
+
+    This may fail for any of these reasons
+    - The cursor was created for another query or entity type
+    - Change tracking was disabled for the table (see `DataverseError::is_change_tracking_disabled()`)
+    - An authentication failure
+    - A serde deserialization error
+    - Any http client or server error
+
+    See the `changes` module for an example
+    */
+    pub async fn resume_changes<E: ReadEntity>(&self, query: &Query, cursor: &ChangeCursor) -> Result<Changes<E>> {
+        self.check_change_fingerprint::<E>(query, &cursor.query_fingerprint)?;
+        self.retrieve_tracked_page(&cursor.next_link, query, cursor.query_fingerprint.clone()).await
+    }
+
+    /**
+    Returns the definition of the table with the given logical name
+
+    Attributes and relationships are only returned if they are expanded. The response
+    is cached, see `metadata_cache()`
+
+    This may fail for any of these reasons
+    - An authentication failure
+    - A serde deserialization error
+    - Any http client or server error, e.g. when the table does not exist
+
+    # Examples
+    ```rust
+    use powerplatform_dataverse_service_client::{
+        client::Client,
+        metadata::MetadataQuery,
+        query::expand::Expand,
+        result::Result,
+    };
+
+    async fn test() -> Result<()> {
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+        let query = MetadataQuery::new()
+            .select(&["EntitySetName", "PrimaryIdAttribute"])
+            .expand(Expand::new("ManyToManyRelationships", &["SchemaName", "Entity2LogicalName"]));
+
+        let contact = client.entity_metadata("contact", &query).await?;
+        println!("{:?} is the primary key of {:?}", contact.primary_id_attribute, contact.entity_set_name);
+        Ok(())
+    }
+    ```
+    */
+    pub async fn entity_metadata(&self, logical_name: &str, query: &MetadataQuery) -> Result<EntityMetadata> {
+        let value = self.retrieve_metadata(format!("{}{}", entity_path(logical_name), query)).await?;
+        serde_json::from_value(value).into_dataverse_result()
+    }
+
+    /**
+    Returns the definitions of all tables that match the filter of the given query
+
+    The response is cached, see `metadata_cache()`
+    */
+    pub async fn entity_definitions(&self, query: &MetadataQuery) -> Result<Vec<EntityMetadata>> {
+        let value = self.retrieve_metadata(format!("EntityDefinitions{}", query)).await?;
+        let collection: ColumnValue<Vec<EntityMetadata>> = serde_json::from_value(value).into_dataverse_result()?;
+        Ok(collection.value)
+    }
+
+    /**
+    Returns the definitions of the columns of the table with the given logical name
+
+    Use `MetadataQuery::cast(...)` to return only columns of a specific type together
+    with their type-specific properties, e.g. the `OptionSet` of choice columns.
+    The response is cached, see `metadata_cache()`
+
+    This may fail for any of these reasons
+    - An authentication failure
+    - A serde deserialization error
+    - Any http client or server error, e.g. when the table does not exist
+    */
+    pub async fn attributes(&self, logical_name: &str, query: &MetadataQuery) -> Result<Vec<AttributeMetadata>> {
+        let mut value = self.retrieve_metadata(format!("{}/Attributes{}", entity_path(logical_name), query)).await?;
+
+        // the attributes of a cast don't repeat the type they were cast to
+        if let (Some(attribute_type), Some(Value::Array(attributes))) = (query.cast, value.get_mut("value")) {
+            for attribute in attributes.iter_mut().filter_map(Value::as_object_mut) {
+                attribute
+                    .entry("@odata.type")
+                    .or_insert_with(|| Value::String(format!("#Microsoft.Dynamics.CRM.{}", attribute_type)));
+            }
+        }
+
+        let collection: ColumnValue<Vec<AttributeMetadata>> = serde_json::from_value(value).into_dataverse_result()?;
+        Ok(collection.value)
+    }
+
+    /**
+    Returns the definition of the global choice with the given name
+
+    The response is cached, see `metadata_cache()`
+
+    # Examples
+    ```rust
+    use powerplatform_dataverse_service_client::{client::Client, metadata::MetadataQuery, result::Result};
+
+    async fn test() -> Result<Option<i32>> {
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+        let budget_status = client.global_option_set("budgetstatus", &MetadataQuery::new()).await?;
+        Ok(budget_status.value("Can Buy"))
+    }
+    ```
+    */
+    pub async fn global_option_set(&self, name: &str, query: &MetadataQuery) -> Result<OptionSetMetadata> {
+        let value = self.retrieve_metadata(format!("{}{}", option_set_path(name), query)).await?;
+        serde_json::from_value(value).into_dataverse_result()
+    }
+
+    /**
+    Returns the cache of the metadata endpoints
+
+    The cache is shared by all handles of this client and is never invalidated automatically.
+    Invalidate it after changing the schema of the environment
+    */
+    pub fn metadata_cache(&self) -> &MetadataCache {
+        &self.metadata_cache
+    }
+
+    /**
+    Executes the given FetchXML query and returns its first page
+
+    The query is sent with the `fetchXml` query parameter, or within a `$batch` request
+    if the url gets too long (e.g. for `in` conditions with many values). Further pages
+    are requested with `retrieve_next_page(...)` like the pages of `retrieve_multiple(...)`,
+    the client sets the page number and the paging cookie of the FetchXML for them
+
+    This may fail for any of these reasons
+    - An authentication failure
+    - A serde deserialization error
+    - Any http client or server error, e.g. for invalid FetchXML
+
+    # Examples
+    ```rust
+    use serde::Deserialize;
+    use uuid::Uuid;
+    use powerplatform_dataverse_service_client::{
+        client::{Client, Page},
+        entity::ReadEntity,
+        query::fetch::FetchXml,
+        result::Result,
+        select::Select,
+    };
+
+    async fn test() -> Result<()> {
+        let fetch = FetchXml::new("accounts", r#"
+            <fetch count="100">
+              <entity name="account">
+                <attribute name="accountid" />
+                <attribute name="name" />
+                <link-entity name="contact" from="parentcustomerid" to="accountid" link-type="outer" alias="contact">
+                  <attribute name="fullname" />
+                </link-entity>
+              </entity>
+            </fetch>"#);
+
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+        let accounts: Page<Account> = client.retrieve_fetchxml(&fetch).await?;
+        Ok(())
+    }
+
+    #[derive(Deserialize)]
+    struct Account {
+        accountid: Uuid,
+        name: String,
+        #[serde(rename = "contact.fullname")]
+        contact_name: Option<String>,
+    }
+
+    impl ReadEntity for Account {}
+
+    impl Select for Account {
+        fn get_columns() -> &'static [&'static str] {
+            &["accountid", "name"]
+        }
+    }
+    ```
+    */
+    pub async fn retrieve_fetchxml<E: ReadEntity>(&self, fetch: &FetchXml) -> Result<Page<E>> {
+        self.retrieve_page(self.build_fetch_cursor(fetch)).await
+    }
+
+    /**
+    Executes the given FetchXML query and streams the entities of all its pages
+
+    See `retrieve_fetchxml(...)` for how the query is sent and `retrieve_stream(...)`
+    for how the pages are requested
+    */
+    pub fn retrieve_fetchxml_stream<'a, E>(&'a self, fetch: &FetchXml) -> EntityStream<'a, E>
+    where
+        E: ReadEntity + Send + 'a,
+        A: Send + Sync,
+    {
+        EntityStream::new(self, self.build_fetch_cursor(fetch))
+    }
+
+    /**
+    executes the batch against the dataverse environment
+
+    The requests inside the batch are sent to the Web-API version of this client
+
+    This function will fail if:
+    - the batch size exceeds 1000 calls
+    - the batch execution time exceeds 2 minutes
+
+    the second restriction is especially tricky to handle because the execution time
+    depends on the complexity of the entity in dataverse.
+    So it is possible to create 300 records of an entity with low complexity
+    but only 50 records of an entity with high complexity in that timeframe.
+
+    Based on experience a batch size of 50 should be safe for all entities though
+
+    # Examples
+    ```rust
+    use uuid::Uuid;
+    use serde::Serialize;
+    use powerplatform_dataverse_service_client::{
+        batch::Batch,
+        client::Client,
+        entity::WriteEntity,
+        reference::{Reference, ReferenceStruct},
+        result::{IntoDataverseResult, Result}
+    };
+
+    async fn test() -> Result<()> {
+        let testy_contact = Contact {
+            contactid: Uuid::parse_str("12345678-1234-1234-1234-123456789012").into_dataverse_result()?,
+            firstname: String::from("Testy"),
+            lastname: String::from("McTestface"),
+        };
+
+        let marianne_contact = Contact {
+            contactid: Uuid::parse_str("12345678-1234-1234-1234-123456789abc").into_dataverse_result()?,
+            firstname: String::from("Marianne"),
+            lastname: String::from("McTestface"),
+        };
+
+        // this batch creates both contacts in one call
+        let mut batch = Batch::new("https://instance.crm.dynamics.com/");
+        batch.create(&testy_contact)?;
+        batch.create(&marianne_contact)?;
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+        client.execute(&batch).await?;
+        Ok(())
+    }
+
+    #[derive(Serialize)]
+    struct Contact {
+        contactid: Uuid,
+        firstname: String,
+        lastname: String,
+    }
+
+    impl WriteEntity for Contact {}
+
+    impl Reference for Contact {
+        fn get_reference(&self) -> ReferenceStruct {
+            ReferenceStruct::new(
+                "contacts",
+                self.contactid,
+            )
+        }
+    }
+    ```
+    */
+    pub async fn execute(&self, batch: &Batch) -> Result<()> {
+        let url_path = self.build_simple_url("$batch");
+
+        self.request(
+            Method::POST, 
+            &url_path, 
+            move |request| {
+                Ok(request
+                    .header("Content-Type", format!("multipart/mixed; boundary=batch_{}", batch.get_batch_id()))
+                    .body(batch.render_for_client(&self.web_api_version, &self.additional_headers()))
+                )
+            }, 
+            handle_empty_response
+        ).await
+    }
+
+    /**
+    Tries to merge two entities with and deactivates the subordinate after the process
+
+    This method is only supported for the following entities (by logical or entity set name):
+    - account
+    - contact
+    - lead
+    - incident
+
+    Use `execute_action(...)` with a `MergeRequest` to perform parenting checks or to
+    move column values from the subordinate to the target
+
+    # Examples
+    ```rust
+    use uuid::Uuid;
     use powerplatform_dataverse_service_client::client::Client;
     use powerplatform_dataverse_service_client::result::{IntoDataverseResult, Result};
 
@@ -826,24 +2593,362 @@ impl<'url, A: Authenticate> Client<'url, A> {
     ```
     */
     pub async fn merge(&self, entity_name: impl Display, target: Uuid, subordinate: Uuid) -> Result<()> {
-        let url_path = self.build_simple_url("Merge");
+        let entity_name = entity_name.to_string();
+        let entity = MergeEntity::from_name(&entity_name).ok_or_else(|| DataverseError::new(format!(
+            "'{}' does not support merging. Only account, contact, lead and incident can be merged",
+            entity_name
+        )))?;
+
+        self.execute_action(&MergeRequest::new(entity, target, subordinate)).await
+    }
+
+    /**
+    Executes the given action and returns its response
+
+    Actions without a response use `()` as their response type
+
+    This may fail for any of these reasons
+    - An authentication failure
+    - A serde serialization or deserialization error
+    - Any http client or server error
+
+    # Examples
+    ```rust
+    use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
+    use powerplatform_dataverse_service_client::{
+        action::{Binding, DataverseAction},
+        client::Client,
+        reference::KeyReference,
+        result::Result,
+    };
+
+    #[derive(Serialize)]
+    struct ApproveInvoice {
+        #[serde(skip)]
+        invoice_id: Uuid,
+        #[serde(rename = "Comment")]
+        comment: String,
+    }
+
+    #[derive(Deserialize)]
+    struct ApproveInvoiceResponse {
+        #[serde(rename = "ApprovalNumber")]
+        approval_number: String,
+    }
+
+    impl DataverseAction for ApproveInvoice {
+        type Response = ApproveInvoiceResponse;
+
+        fn name(&self) -> &str {
+            "new_ApproveInvoice"
+        }
+
+        fn binding(&self) -> Binding {
+            Binding::Record(KeyReference::id("invoices", self.invoice_id))
+        }
+    }
+
+    async fn test() -> Result<String> {
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+        let response = client.execute_action(&ApproveInvoice {
+            invoice_id: Uuid::new_v4(),
+            comment: String::from("looks good"),
+        }).await?;
+
+        Ok(response.approval_number)
+    }
+    ```
+    */
+    pub async fn execute_action<T: DataverseAction>(&self, action: &T) -> Result<T::Response> {
+        let url_path = self.build_simple_url(action.binding().path(action.name()));
 
         self.request(
             Method::POST,
-            &url_path, 
+            &url_path,
             move |request| {
-                let entity_name = entity_name.to_string();
-                let merge_request = MergeRequest::new(&entity_name, target, subordinate, false);
-
                 Ok(request
                     .header("Content-Type", "application/json")
-                    .body(serde_json::to_vec(&merge_request).into_dataverse_result()?)
+                    .body(serde_json::to_vec(action).into_dataverse_result()?)
                 )
-            }, 
-            handle_empty_response,
+            },
+            handle_operation_response
+        ).await
+    }
+
+    /**
+    Calls the given function and returns its response
+
+    The parameters of the function are passed as parameter aliases in the url
+
+    # Examples
+    ```rust
+    use serde::Deserialize;
+    use powerplatform_dataverse_service_client::{
+        action::{DataverseFunction, Parameter},
+        client::Client,
+        query::attribute::Attribute,
+        result::Result,
+    };
+
+    struct GetTimeZoneCodeByLocalizedName(&'static str);
+
+    #[derive(Deserialize)]
+    struct TimeZoneCode {
+        #[serde(rename = "TimeZoneCode")]
+        code: i32,
+    }
+
+    impl DataverseFunction for GetTimeZoneCodeByLocalizedName {
+        type Response = TimeZoneCode;
+
+        fn name(&self) -> &str {
+            "GetTimeZoneCodeByLocalizedName"
+        }
+
+        fn parameters(&self) -> Vec<(&'static str, Parameter)> {
+            vec![
+                ("LocalizedStandardName", Attribute::String(String::from(self.0)).into()),
+                ("LocaleId", Attribute::Integer(1033).into()),
+            ]
+        }
+    }
+
+    async fn test() -> Result<i32> {
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+        let function = GetTimeZoneCodeByLocalizedName("Pacific Standard Time");
+        Ok(client.call_function(&function).await?.code)
+    }
+    ```
+    */
+    pub async fn call_function<T: DataverseFunction>(&self, function: &T) -> Result<T::Response> {
+        let url_path = self.build_simple_url(function_path(function));
+
+        self.request(
+            Method::GET,
+            &url_path,
+            Ok,
+            handle_operation_response
+        ).await
+    }
+
+    /**
+    Returns the ids of the calling user, its business unit and its organization
+
+    # Examples
+    ```rust
+    use powerplatform_dataverse_service_client::{client::Client, result::Result};
+
+    async fn test() -> Result<()> {
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+        let identity = client.who_am_i().await?;
+        println!("connected as {}", identity.user_id);
+        Ok(())
+    }
+    ```
+    */
+    pub async fn who_am_i(&self) -> Result<WhoAmIResponse> {
+        self.call_function(&WhoAmI).await
+    }
+
+    /**
+    Returns the version of the dataverse environment, e.g. `9.2.24044.196`
+    */
+    pub async fn retrieve_version(&self) -> Result<String> {
+        Ok(self.call_function(&RetrieveVersion).await?.version)
+    }
+
+    /**
+    Checks that this client is able to authenticate and to call dataverse
+
+    A token is acquired and `WhoAmI` is called, both steps are timed. Failures are
+    not returned as errors but explained by the `Diagnosis` of the report, so this
+    can be called at startup to fail fast with a helpful message
+
+    # Examples
+    ```rust
+    use powerplatform_dataverse_service_client::{client::Client, health::Diagnosis};
+
+    async fn test() {
+        let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+        let report = client.health_check().await;
+
+        match report.diagnosis {
+            Diagnosis::Healthy => println!("token: {:?}, request: {:?}", report.token_acquisition, report.request),
+            Diagnosis::MissingApplicationUser(_) => println!("please create an application user for this client"),
+            diagnosis => println!("{}", diagnosis),
+        }
+    }
+    ```
+    */
+    pub async fn health_check(&self) -> HealthReport {
+        let started = Instant::now();
+        let token = self.auth.get_valid_token().await;
+        let token_acquisition = Some(started.elapsed());
+
+        if let Err(error) = token {
+            return HealthReport {
+                token_acquisition,
+                request: None,
+                identity: None,
+                diagnosis: Diagnosis::from_token_error(error),
+            };
+        }
+
+        let started = Instant::now();
+        let identity = self.who_am_i().await;
+        let request = Some(started.elapsed());
+
+        match identity {
+            Ok(identity) => HealthReport {
+                token_acquisition,
+                request,
+                identity: Some(identity),
+                diagnosis: Diagnosis::Healthy,
+            },
+            Err(error) => HealthReport {
+                token_acquisition,
+                request,
+                identity: None,
+                diagnosis: Diagnosis::from_request_error(error),
+            },
+        }
+    }
+
+    pub(crate) async fn retrieve_page<E: DeserializeOwned>(&self, cursor: PageCursor) -> Result<Page<E>> {
+        let PageCursor { next_link: url, query_fingerprint, max_page_size, .. } = cursor;
+
+        if url.len() > MAX_URL_LENGTH {
+            return self.retrieve_page_in_batch(url, query_fingerprint, max_page_size).await;
+        }
+
+        let page_url = url.clone();
+        let handle_response = move |response: Response| async move {
+            if response.status().is_client_error() || response.status().is_server_error() {
+                return Err(DataverseError::from_response(response).await);
+            }
+    
+            let content = response.bytes().await.into_dataverse_result()?;
+            read_page(content.as_ref(), &page_url, query_fingerprint, max_page_size)
+        };
+
+        self.request(
+            Method::GET, 
+            &url, 
+            move |request| match max_page_size {
+                Some(size) => Ok(request.header("Prefer", format!("odata.maxpagesize={}", size))),
+                None => Ok(request),
+            },
+            handle_response
+        ).await
+    }
+
+    /// sends the GET request of a page whose url exceeds the length limit of dataverse within a `$batch` request
+    async fn retrieve_page_in_batch<E: DeserializeOwned>(&self, url: String, query_fingerprint: String, max_page_size: Option<u32>) -> Result<Page<E>> {
+        let batch_id = Uuid::new_v4();
+        let mut body = format!(
+            "--batch_{}\nContent-Type: application/http\nContent-Transfer-Encoding:binary\n\nGET {} HTTP/1.1\nAccept: application/json\n",
+            batch_id, url
+        );
+
+        if let Some(size) = max_page_size {
+            body.push_str(&format!("Prefer: odata.maxpagesize={}\n", size));
+        }
+
+        for (name, value) in self.additional_headers() {
+            body.push_str(&format!("{}: {}\n", name, value));
+        }
+
+        body.push_str(&format!("\n--batch_{}--", batch_id));
+
+        let handle_response = move |response: Response| async move {
+            if response.status().is_client_error() || response.status().is_server_error() {
+                return Err(DataverseError::from_response(response).await);
+            }
+
+            let content = response.text().await.into_dataverse_result()?;
+            let (status, body) = read_batch_response(&content).ok_or_else(|| DataverseError::with_kind(
+                ErrorKind::Serialization,
+                String::from("the $batch response contains no http response"),
+            ))?;
+
+            if status.is_client_error() || status.is_server_error() {
+                return Err(DataverseError::from_body(status, None, body.as_bytes()));
+            }
+
+            read_page(body.as_bytes(), &url, query_fingerprint, max_page_size)
+        };
+
+        self.request(
+            Method::POST,
+            &self.build_simple_url("$batch"),
+            move |request| {
+                Ok(request
+                    .header("Content-Type", format!("multipart/mixed; boundary=batch_{}", batch_id))
+                    .body(body)
+                )
+            },
+            handle_response
         ).await
     }
 
+    /// returns the response of the metadata endpoint with the given path from the cache or from dataverse
+    async fn retrieve_metadata(&self, path: String) -> Result<Value> {
+        if let Some(value) = self.metadata_cache.get(&path) {
+            return Ok(value);
+        }
+
+        let value: Value = self.request(
+            Method::GET,
+            &self.build_simple_url(&path),
+            Ok,
+            handle_operation_response
+        ).await?;
+
+        self.metadata_cache.insert(path, value.clone());
+        Ok(value)
+    }
+
+    /// checks that a change token or cursor was created for the given query and entity type
+    fn check_change_fingerprint<E: Select>(&self, query: &Query, query_fingerprint: &str) -> Result<()> {
+        let query_path = build_query_path(&build_select::<E>(&query.expand), query);
+        if query_fingerprint != fingerprint(&query_path) {
+            return Err(DataverseError::new(format!(
+                "the change token or cursor was not created for the query '{}'",
+                query_path
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// reads a single page of a tracked query, the last page has an `@odata.deltaLink` instead of a next link
+    async fn retrieve_tracked_page<E: ReadEntity>(&self, url: &str, query: &Query, query_fingerprint: String) -> Result<Changes<E>> {
+        let prefer = match query.max_page_size {
+            Some(size) => format!("odata.track-changes,odata.maxpagesize={}", size),
+            None => String::from("odata.track-changes"),
+        };
+
+        let page: TrackedPage = self.request(
+            Method::GET,
+            url,
+            |request| Ok(request.header("Prefer", prefer.as_str())),
+            handle_operation_response
+        ).await?;
+
+        if page.next_link.is_none() && page.delta_link.is_none() {
+            return Err(DataverseError::change_tracking_disabled(query.logical_name));
+        }
+
+        let changes = page
+            .values
+            .into_iter()
+            .map(|value| Change::read(value, query.logical_name))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Changes::new(changes, page.next_link, page.delta_link, query_fingerprint))
+    }
+
     async fn request<E, Fut>(
         &self,
         method: Method,
@@ -852,84 +2957,260 @@ impl<'url, A: Authenticate> Client<'url, A> {
         response_consumer: impl FnOnce(Response) -> Fut,
     ) -> Result<E> 
     where Fut: Future<Output = Result<E>>{
-        let token = self.auth.get_valid_token().await?;
+        let idempotent = self.retry_policy.is_idempotent(&method);
+        let mut url = url.to_string();
+        self.options.apply_to_url(&mut url);
 
-        let response = request_preparer(self.backend.request(method, url))?
-            .bearer_auth(token)
+        let mut request = request_preparer(self.backend.request(method, &url))?
             .header("OData-MaxVersion", "4.0")
             .header("OData-Version", "4.0")
-            .header("Accept", "application/json")
-            .send().await.into_dataverse_result()?;
+            .header("Accept", "application/json");
+
+        for (name, value) in self.additional_headers() {
+            request = request.header(name, value);
+        }
+        let request = &request;
+
+        let response = self.retry_policy.send(idempotent, move || async move {
+            let token = self.auth.get_valid_token().await?;
+            let request = request
+                .try_clone()
+                .ok_or_else(|| DataverseError::new(String::from("the request body can not be sent repeatedly")))?;
+
+            Ok(request.bearer_auth(token))
+        }).await?;
+
+        response_consumer(response).await
+    }
+
+    /// requests the bytes from `start` to `end` (inclusive) of a file
+    pub(crate) async fn fetch_file_block(&self, url: &str, start: u64, end: u64) -> Result<FileBlock> {
+        async fn handle_response(response: Response) -> Result<FileBlock> {
+            if response.status().is_client_error() || response.status().is_server_error() {
+                return Err(DataverseError::from_response(response).await);
+            }
+
+            let header = |name: &str| {
+                response
+                    .headers()
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+                    .map(String::from)
+            };
+
+            let partial = response.status() == StatusCode::PARTIAL_CONTENT;
+            let file_name = header("x-ms-file-name");
+            let file_size = header("x-ms-file-size")
+                .or_else(|| header("Content-Range").and_then(|range| range.rsplit('/').next().map(String::from)))
+                .and_then(|size| size.parse::<u64>().ok());
+            let data = response.bytes().await.into_dataverse_result()?.to_vec();
+
+            Ok(FileBlock { data, file_size, file_name, partial })
+        }
+
+        self.request(
+            Method::GET,
+            url,
+            move |request| Ok(request.header("Range", format!("bytes={}-{}", start, end))),
+            handle_response
+        ).await
+    }
+
+    /// the headers for impersonation and request options this client adds to its requests
+    fn additional_headers(&self) -> Vec<(&'static str, String)> {
+        let mut headers = self.options.headers();
+        headers.extend(self.caller.iter().map(CallerId::header));
+        headers
+    }
+
+    fn build_simple_url(&self, table_name: impl Display) -> String {
+        format!("{}api/data/v{}/{}", self.url, self.web_api_version, table_name)
+    }
+
+    fn build_targeted_url(&self, target: &KeyReference) -> String {
+        format!(
+            "{}api/data/v{}/{}",
+            self.url,
+            self.web_api_version,
+            target
+        )
+    }
+
+    /// builds the `{"@odata.id": "..."}` body that references the given record
+    fn build_odata_id(&self, target: &KeyReference) -> String {
+        serde_json::json!({ "@odata.id": self.build_targeted_url(target) }).to_string()
+    }
+
+    fn build_retrieve_url(&self, target: &KeyReference, select: &str) -> String {
+        format!(
+            "{}api/data/v{}/{}?{}",
+            self.url,
+            self.web_api_version,
+            target,
+            select
+        )
+    }
+
+    fn build_fetch_cursor(&self, fetch: &FetchXml) -> PageCursor {
+        let query_path = fetch.to_string();
+
+        PageCursor {
+            next_link: format!("{}api/data/v{}/{}", self.url, self.web_api_version, query_path),
+            paging_cookie: fetch::paging_cookie(&fetch.xml),
+            query_fingerprint: fingerprint(&query_path),
+            max_page_size: None,
+        }
+    }
+
+    fn build_query_cursor(&self, select: &str, query: &Query) -> PageCursor {
+        let query_path = build_query_path(select, query);
+
+        PageCursor {
+            next_link: format!("{}api/data/v{}/{}", self.url, self.web_api_version, query_path),
+            paging_cookie: None,
+            query_fingerprint: fingerprint(&query_path),
+            max_page_size: query.max_page_size,
+        }
+    }
+}
+
+fn build_query_path(select: &str, query: &Query) -> String {
+    // aggregated results consist of the grouped columns and aliases, so there is nothing to select
+    if select.is_empty() || !query.apply.is_empty() {
+        return query.to_string();
+    }
+
+    let query = query.to_string();
+    let separator = if query.contains('?') { '&' } else { '?' };
+    format!("{}{}{}", query, separator, select)
+}
+
+/// reads a page of entities and determines the url of the page after it
+fn read_page<E: DeserializeOwned>(content: &[u8], url: &str, query_fingerprint: String, max_page_size: Option<u32>) -> Result<Page<E>> {
+    let RetrieveMultipleResult { entities, next_link, fetch_paging_cookie, more_records, count, count_limit_exceeded } =
+        serde_json::from_slice(content).into_dataverse_result()?;
+    let total_count = count.map(|count| TotalCount {
+        count,
+        limit_exceeded: count_limit_exceeded.unwrap_or(false),
+    });
+
+    let next_link = match next_link {
+        Some(next_link) => Some(next_link),
+        None if more_records == Some(true) => Some(next_fetch_link(url, fetch_paging_cookie.as_deref())?),
+        None => None,
+    };
+
+    Ok(Page::new(entities, next_link, query_fingerprint, max_page_size, total_count))
+}
+
+/// builds the url of the page after the given page of a FetchXML query
+fn next_fetch_link(url: &str, paging_cookie: Option<&str>) -> Result<String> {
+    let next_xml = Url::parse(url)
+        .into_dataverse_result()?
+        .query_pairs()
+        .find(|(key, _)| key == "fetchXml")
+        .and_then(|(_, xml)| fetch::next_page(&xml, paging_cookie))
+        .ok_or_else(|| DataverseError::new(format!("dataverse reported more records for '{}' which is no FetchXML query", url)))?;
+
+    let base = url.split('?').next().unwrap_or(url);
+    Ok(format!("{}?fetchXml={}", base, encode_path(&next_xml)))
+}
+
+/// extracts the status and the body of the single response within a `$batch` response
+fn read_batch_response(content: &str) -> Option<(StatusCode, &str)> {
+    let response = &content[content.find("HTTP/1.1 ")? + 9..];
+    let status = StatusCode::from_u16(response.get(..3)?.parse().ok()?).ok()?;
+
+    let body_start = match (response.find("\r\n\r\n"), response.find("\n\n")) {
+        (Some(crlf), Some(lf)) if lf < crlf => lf + 2,
+        (Some(crlf), _) => crlf + 4,
+        (None, Some(lf)) => lf + 2,
+        (None, None) => return None,
+    };
 
-        response_consumer(response).await
-    }
+    let body = &response[body_start..];
+    let body_end = body.find("\n--batchresponse").unwrap_or(body.len());
+    Some((status, body[..body_end].trim()))
+}
 
-    fn build_simple_url(&self, table_name: impl Display) -> String {
-        format!("{}api/data/v{}/{}", self.url, VERSION, table_name)
-    }
+/// builds the `$select` and `$expand` options for the given struct and the additional expansions
+fn build_select<S: Select>(additional_expands: &[Expand]) -> String {
+    let mut expands = S::get_expands();
+    expands.extend_from_slice(additional_expands);
+    render_select(S::get_columns(), &expands)
+}
 
-    fn build_targeted_url(&self, table_name: impl Display, target_id: Uuid) -> String {
-        format!(
-            "{}api/data/v{}/{}({})",
-            self.url,
-            VERSION,
-            table_name,
-            target_id.as_hyphenated()
-        )
+/// renders the `$select` option of the given columns and the `$expand` option of the given expansions
+pub(crate) fn render_select(columns: &[&str], expands: &[Expand]) -> String {
+    if expands.is_empty() {
+        format!("$select={}", join_columns(columns))
+    } else {
+        format!("$select={}&$expand={}", join_columns(columns), ExpandList(expands))
     }
+}
 
-    fn build_retrieve_url(&self, table_name: impl Display, target_id: Uuid, columns: &[&str]) -> String {
-        let mut select = String::new();
-        let mut comma_required = false;
-
-        for column in columns {
-            if comma_required {
-                select.push(',');
-            }
+fn join_columns(columns: &[&str]) -> String {
+    let mut select = String::new();
+    let mut comma_required = false;
 
-            select.push_str(column);
-            comma_required = true;
+    for column in columns {
+        if comma_required {
+            select.push(',');
         }
 
-        format!(
-            "{}api/data/v{}/{}({})?$select={}",
-            self.url,
-            VERSION,
-            table_name,
-            target_id.as_hyphenated(),
-            select
-        )
+        select.push_str(column);
+        comma_required = true;
     }
 
-    fn build_query_url(&self, columns: &[&str], query: &Query) -> String {
-        let mut select = String::new();
-        let mut comma_required = false;
+    select
+}
 
-        for column in columns {
-            if comma_required {
-                select.push(',');
-            }
+/// the body dataverse uses for the value of a single column
+#[derive(Deserialize, Serialize)]
+pub(crate) struct ColumnValue<T> {
+    pub value: T,
+}
 
-            select.push_str(column);
-            comma_required = true;
-        }
+/// FNV-1a hash of the query path, which is stable across restarts and versions of this crate
+fn fingerprint(query_path: &str) -> String {
+    let hash = query_path.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
 
-        format!(
-            "{}api/data/v{}/{}&$select={}",
-            self.url, VERSION, query, select
-        )
-    }
+    format!("{:016x}", hash)
 }
 
 async fn handle_empty_response(response: Response) -> Result<()> {
     if response.status().is_client_error() || response.status().is_server_error() {
-        let error_message = response.text().await.unwrap_or_else(|_| String::from("no error details provided from server"));
-        return Err(DataverseError::new(error_message));
+        return Err(DataverseError::from_response(response).await);
     }
 
     Ok(())
 }
 
+/// deserializes the response of an action or function, where an empty body stands for `null`
+async fn handle_operation_response<T: DeserializeOwned>(response: Response) -> Result<T> {
+    if response.status().is_client_error() || response.status().is_server_error() {
+        return Err(DataverseError::from_response(response).await);
+    }
+
+    let content = response.bytes().await.into_dataverse_result()?;
+    if content.is_empty() {
+        return serde_json::from_str("null").into_dataverse_result();
+    }
+
+    serde_json::from_slice(content.as_ref()).into_dataverse_result()
+}
+
+async fn handle_entity_response<E: ReadEntity>(response: Response) -> Result<E> {
+    if response.status().is_client_error() || response.status().is_server_error() {
+        return Err(DataverseError::from_response(response).await);
+    }
+
+    let content = response.bytes().await.into_dataverse_result()?;
+    serde_json::from_slice(content.as_ref()).into_dataverse_result()
+}
+
 /**
 A page of retrieved entites by the `retrieve_multiple()` and `retrieve_next_page()`
 by a client instance 
@@ -937,14 +3218,21 @@ by a client instance
 #[derive(Debug)]
 pub struct Page<E> {
     pub entities: Vec<E>,
+    /// the total number of matching records if the query was created `with_count()`
+    pub total_count: Option<TotalCount>,
     next_link: Option<String>,
+    query_fingerprint: String,
+    max_page_size: Option<u32>,
 }
 
 impl<E> Page<E> {
-    fn new(entities: Vec<E>, next_link: Option<String>) -> Self {
+    fn new(entities: Vec<E>, next_link: Option<String>, query_fingerprint: String, max_page_size: Option<u32>, total_count: Option<TotalCount>) -> Self {
         Self {
             entities,
+            total_count,
             next_link,
+            query_fingerprint,
+            max_page_size,
         }
     }
 
@@ -953,16 +3241,975 @@ impl<E> Page<E> {
         self.next_link.is_some()
     }
 
+    /**
+    returns a serializable cursor to the page after this one or `None` if this is the last page
+
+    The cursor can be stored and later passed to `Client::resume_from(...)`
+    */
+    pub fn cursor(&self) -> Option<PageCursor> {
+        let next_link = self.next_link.as_ref()?;
+
+        Some(PageCursor {
+            next_link: next_link.clone(),
+            paging_cookie: paging_cookie(next_link),
+            query_fingerprint: self.query_fingerprint.clone(),
+            max_page_size: self.max_page_size,
+        })
+    }
+
     /// Transforms the page into its content as a `Vec`
     pub fn into_inner(self) -> Vec<E> {
         self.entities
     }
 }
 
+/**
+The total number of records that match a query
+
+Dataverse counts at most 5000 records. If more records match, `count` is 5000
+and `limit_exceeded` is set
+*/
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TotalCount {
+    /// the number of matching records, capped at 5000
+    pub count: u32,
+    /// indicates that more than 5000 records match (`@Microsoft.Dynamics.CRM.totalrecordcountlimitexceeded`)
+    pub limit_exceeded: bool,
+}
+
+/**
+A serializable position within a paged query
+
+Created by `Page::cursor()` and consumed by `Client::resume_from(...)`
+*/
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PageCursor {
+    /// the url of the next page as provided by `@odata.nextLink`
+    pub next_link: String,
+    /// the paging cookie dataverse encoded into the next link, if any
+    pub paging_cookie: Option<String>,
+    /// identifies the query and selected columns the cursor was created for
+    pub query_fingerprint: String,
+    /// the page size requested with `Prefer: odata.maxpagesize`
+    pub max_page_size: Option<u32>,
+}
+
+impl PageCursor {
+    /// Indicates if this cursor was created for the given query and entity type
+    pub fn is_for<E: Select>(&self, query: &Query) -> bool {
+        self.query_fingerprint == fingerprint(&build_query_path(&build_select::<E>(&query.expand), query))
+    }
+
+    /// Indicates if this cursor was created for the given FetchXML query
+    pub fn is_for_fetchxml(&self, fetch: &FetchXml) -> bool {
+        self.query_fingerprint == fingerprint(&fetch.to_string())
+    }
+}
+
+/// extracts the paging cookie from the `$skiptoken` or the FetchXML of a next link
+fn paging_cookie(next_link: &str) -> Option<String> {
+    let url = Url::parse(next_link).ok()?;
+    let mut pairs = url.query_pairs();
+
+    pairs.find_map(|(key, value)| match key.as_ref() {
+        "$skiptoken" => Some(value.into_owned()),
+        "fetchXml" => fetch::paging_cookie(&value),
+        _ => None,
+    })
+}
+
+#[derive(Deserialize)]
+struct TrackedPage {
+    #[serde(rename = "value")]
+    values: Vec<serde_json::Value>,
+    #[serde(rename = "@odata.nextLink")]
+    next_link: Option<String>,
+    #[serde(rename = "@odata.deltaLink")]
+    delta_link: Option<String>,
+}
+
 #[derive(Deserialize)]
 struct RetrieveMultipleResult<E> {
     #[serde(rename = "value")]
     entities: Vec<E>,
     #[serde(rename = "@odata.nextLink")]
     next_link: Option<String>,
-}
\ No newline at end of file
+    #[serde(rename = "@Microsoft.Dynamics.CRM.fetchxmlpagingcookie")]
+    fetch_paging_cookie: Option<String>,
+    #[serde(rename = "@Microsoft.Dynamics.CRM.morerecords")]
+    more_records: Option<bool>,
+    #[serde(rename = "@odata.count")]
+    count: Option<u32>,
+    #[serde(rename = "@Microsoft.Dynamics.CRM.totalrecordcountlimitexceeded")]
+    count_limit_exceeded: Option<bool>,
+}
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::StreamExt;
+    use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
+
+    use crate::{
+        action::{Binding, DataverseAction, WhoAmI},
+        changes::Change,
+        batch::Batch,
+        client::{CallerId, Client, Column, Page, PageCursor, TotalCount},
+        entity::{Conditional, DeepInsert, ReadEntity, UpsertMode, UpsertOutcome, Versioned, WriteEntity},
+        metadata::{AttributeDetails, MetadataQuery},
+        options::RequestOptions,
+        query::{apply::{Aggregate, Transformation}, attribute::Attribute, expand::Expand, fetch::FetchXml, filter::Filter, Query},
+        reference::{encode_path, Addressable, KeyReference, Reference, ReferenceStruct},
+        result::Result,
+        retry::RetryPolicy,
+        select::Select,
+        testing::{CannedResponse, StaticAuth, TestServer},
+    };
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Contact {
+        fullname: String,
+    }
+
+    impl ReadEntity for Contact {}
+
+    impl Select for Contact {
+        fn get_columns() -> &'static [&'static str] {
+            &["fullname"]
+        }
+    }
+
+    #[derive(Serialize)]
+    struct NewContact {
+        contactid: Uuid,
+        lastname: String,
+    }
+
+    impl WriteEntity for NewContact {}
+
+    impl Reference for NewContact {
+        fn get_reference(&self) -> ReferenceStruct {
+            ReferenceStruct::new("contacts", self.contactid)
+        }
+    }
+
+    #[derive(Serialize)]
+    struct ExternalContact {
+        employeeid: String,
+        lastname: String,
+    }
+
+    impl WriteEntity for ExternalContact {}
+
+    impl Addressable for ExternalContact {
+        fn get_key_reference(&self) -> KeyReference {
+            KeyReference::alternate("contacts", vec![("employeeid", Attribute::String(self.employeeid.clone()))])
+        }
+    }
+
+    fn contact_pages() -> Vec<CannedResponse> {
+        vec![
+            CannedResponse::new(200).json(r#"{"value":[{"fullname":"Testy"},{"fullname":"Marianne"}],"@odata.nextLink":"{server}api/data/v9.2/contacts?$skiptoken=2"}"#),
+            CannedResponse::new(200).json(r#"{"value":[{"fullname":"Jane"}]}"#),
+        ]
+    }
+
+    fn test_client(url: &str, retry_policy: RetryPolicy) -> Client<'static, StaticAuth> {
+        Client::new(url.to_string(), reqwest::Client::new(), StaticAuth)
+            .with_retry_policy(retry_policy.base_delay(Duration::from_millis(1)))
+    }
+
+    fn test_reference() -> ReferenceStruct {
+        ReferenceStruct::new("contacts", Uuid::parse_str("12345678-1234-1234-1234-123456789012").unwrap())
+    }
+
+    #[tokio::test]
+    async fn retries_throttled_requests() {
+        let server = TestServer::start(vec![
+            CannedResponse::new(429).header("Retry-After", "0"),
+            CannedResponse::new(429).header("Retry-After", "0"),
+            CannedResponse::new(204),
+        ])
+        .await;
+
+        let client = test_client(&server.url, RetryPolicy::new());
+        client.delete(&test_reference()).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(requests.iter().all(|request| request.method == "DELETE"
+            && request.path == "/api/data/v9.2/contacts(12345678-1234-1234-1234-123456789012)"
+            && request.header("Authorization") == Some("Bearer test-token")));
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let server = TestServer::start(vec![
+            CannedResponse::new(429).header("Retry-After", "0"),
+            CannedResponse::new(429).header("Retry-After", "0"),
+            CannedResponse::new(204),
+        ])
+        .await;
+
+        let client = test_client(&server.url, RetryPolicy::new().max_attempts(2));
+        let error = client.delete(&test_reference()).await.unwrap_err();
+        assert!(error.is_throttled());
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_transient_failures_of_non_idempotent_requests() {
+        let server = TestServer::start(vec![CannedResponse::new(502), CannedResponse::new(204)]).await;
+
+        let client = test_client(&server.url, RetryPolicy::new());
+        assert!(client.merge("account", Uuid::new_v4(), Uuid::new_v4()).await.is_err());
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_service_unavailable_with_retry_after_for_every_method() {
+        let server = TestServer::start(vec![
+            CannedResponse::new(503).header("Retry-After", "0"),
+            CannedResponse::new(204),
+        ])
+        .await;
+
+        let client = test_client(&server.url, RetryPolicy::new());
+        client.merge("account", Uuid::new_v4(), Uuid::new_v4()).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].body, requests[1].body);
+        assert!(requests[1].body_text().contains("\"Target\""));
+    }
+
+    #[tokio::test]
+    async fn streams_all_pages() {
+        let server = TestServer::start(contact_pages()).await;
+        let client = test_client(&server.url, RetryPolicy::new());
+
+        let contacts: Vec<Result<Contact>> = client.retrieve_stream(&Query::new("contacts")).collect().await;
+        let names: Vec<String> = contacts.into_iter().map(|contact| contact.unwrap().fullname).collect();
+        assert_eq!(names, vec!["Testy", "Marianne", "Jane"]);
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].path, "/api/data/v9.2/contacts?$skiptoken=2");
+    }
+
+    #[tokio::test]
+    async fn reads_and_caches_metadata() {
+        let server = TestServer::start(vec![
+            CannedResponse::new(200).json(r##"{"MetadataId":"00000000-0000-0000-0000-000000000001","LogicalName":"account","EntitySetName":"accounts","PrimaryIdAttribute":"accountid","ManyToOneRelationships":[{"SchemaName":"account_primary_contact","ReferencedEntity":"contact","ReferencingAttribute":"primarycontactid"}]}"##),
+            CannedResponse::new(200).json(r##"{"value":[{"LogicalName":"industrycode","OptionSet":{"Options":[{"Value":1,"Label":{"UserLocalizedLabel":{"Label":"Accounting","LanguageCode":1033}}}]}}]}"##),
+            CannedResponse::new(200).json(r##"{"Name":"budgetstatus","IsGlobal":true,"Options":[{"Value":2,"Label":{"LocalizedLabels":[{"Label":"Can Buy","LanguageCode":1033}]}}]}"##),
+            CannedResponse::new(200).json(r##"{"LogicalName":"account","EntitySetName":"accounts"}"##),
+        ])
+        .await;
+        let client = test_client(&server.url, RetryPolicy::new());
+        let query = MetadataQuery::new()
+            .select(&["LogicalName", "EntitySetName", "PrimaryIdAttribute"])
+            .expand(Expand::new("ManyToOneRelationships", &["SchemaName", "ReferencedEntity", "ReferencingAttribute"]));
+
+        let account = client.entity_metadata("account", &query).await.unwrap();
+        assert_eq!(account.entity_set_name.as_deref(), Some("accounts"));
+        assert_eq!(account.many_to_one_relationships[0].referenced_entity.as_deref(), Some("contact"));
+        assert_eq!(client.entity_metadata("account", &query).await.unwrap(), account);
+
+        let picklists = client
+            .attributes("account", &MetadataQuery::new().cast("PicklistAttributeMetadata").expand(Expand::new("OptionSet", &[])))
+            .await
+            .unwrap();
+        match &picklists[0].details {
+            AttributeDetails::Picklist(picklist) => assert_eq!(picklist.option_set.as_ref().unwrap().label(1), Some("Accounting")),
+            details => panic!("unexpected details {:?}", details),
+        }
+
+        let budget_status = client.global_option_set("budgetstatus", &MetadataQuery::new()).await.unwrap();
+        assert_eq!(budget_status.value("Can Buy"), Some(2));
+
+        client.impersonate(CallerId::SystemUserId(Uuid::nil())).metadata_cache().invalidate_entity("account");
+        client.entity_metadata("account", &query).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 4);
+        assert_eq!(
+            requests[0].path,
+            "/api/data/v9.2/EntityDefinitions(LogicalName='account')?$select=LogicalName,EntitySetName,PrimaryIdAttribute&$expand=ManyToOneRelationships($select=SchemaName,ReferencedEntity,ReferencingAttribute)"
+        );
+        assert_eq!(
+            requests[1].path,
+            "/api/data/v9.2/EntityDefinitions(LogicalName='account')/Attributes/Microsoft.Dynamics.CRM.PicklistAttributeMetadata?$expand=OptionSet"
+        );
+        assert_eq!(requests[2].path, "/api/data/v9.2/GlobalOptionSetDefinitions(Name='budgetstatus')");
+    }
+
+    #[tokio::test]
+    async fn tracks_changes_with_delta_links() {
+        let server = TestServer::start(vec![
+            CannedResponse::new(200).json(r#"{"value":[{"fullname":"Testy"}],"@odata.nextLink":"{server}api/data/v9.2/contacts?$select=fullname&$skiptoken=1"}"#),
+            CannedResponse::new(200).json(r#"{"value":[{"fullname":"Jane"}],"@odata.deltaLink":"{server}api/data/v9.2/contacts?$select=fullname&$deltatoken=100"}"#),
+            CannedResponse::new(200).json(r#"{"value":[{"fullname":"Marianne"},{"@odata.context":"{server}api/data/v9.2/$metadata#contacts/$deletedEntity","id":"00000000-0000-0000-0000-000000000001","reason":"deleted"}],"@odata.deltaLink":"{server}api/data/v9.2/contacts?$select=fullname&$deltatoken=101"}"#),
+            CannedResponse::new(200).json(r#"{"value":[{"fullname":"Testy"}]}"#),
+        ])
+        .await;
+        let client = test_client(&server.url, RetryPolicy::new());
+        let query = Query::new("contacts").max_page_size(1);
+
+        let first = client.track_changes::<Contact>(&query).await.unwrap();
+        assert_eq!(first.changes, vec![Change::Upserted(Contact { fullname: String::from("Testy") })]);
+        assert!(first.token().is_none());
+
+        let stored = serde_json::to_string(&first.cursor().unwrap()).unwrap();
+        let cursor = serde_json::from_str(&stored).unwrap();
+        let last = client.resume_changes::<Contact>(&query, &cursor).await.unwrap();
+        assert_eq!(last.changes.len(), 1);
+        assert!(last.cursor().is_none());
+        assert!(last.token().unwrap().delta_link.ends_with("$deltatoken=100"));
+
+        let stored = serde_json::to_string(last.token().unwrap()).unwrap();
+        let token = serde_json::from_str(&stored).unwrap();
+        let changes = client.retrieve_changes::<Contact>(&query, &token).await.unwrap();
+        assert_eq!(
+            changes.changes,
+            vec![
+                Change::Upserted(Contact { fullname: String::from("Marianne") }),
+                Change::Deleted(ReferenceStruct::new("contacts", Uuid::from_u128(1))),
+            ]
+        );
+        assert!(changes.token().unwrap().delta_link.ends_with("$deltatoken=101"));
+
+        assert!(client.retrieve_changes::<Contact>(&Query::new("accounts"), &token).await.is_err());
+        assert!(client.resume_changes::<Contact>(&Query::new("accounts"), &cursor).await.is_err());
+        let error = client.track_changes::<Contact>(&query).await.unwrap_err();
+        assert!(error.is_change_tracking_disabled());
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 4);
+        assert_eq!(requests[0].path, "/api/data/v9.2/contacts?$select=fullname");
+        assert_eq!(requests[0].header("Prefer"), Some("odata.track-changes,odata.maxpagesize=1"));
+        assert_eq!(requests[1].path, "/api/data/v9.2/contacts?$select=fullname&$skiptoken=1");
+        assert_eq!(requests[1].header("Prefer"), Some("odata.track-changes,odata.maxpagesize=1"));
+        assert_eq!(requests[2].path, "/api/data/v9.2/contacts?$select=fullname&$deltatoken=100");
+    }
+
+    #[tokio::test]
+    async fn counts_matching_records() {
+        let server = TestServer::start(vec![
+            CannedResponse::new(200).json(r#"{"@odata.count":2,"value":[{"fullname":"Testy"},{"fullname":"Marianne"}]}"#),
+            CannedResponse::new(200).json(r#"{"@odata.count":5000,"@Microsoft.Dynamics.CRM.totalrecordcountlimitexceeded":true,"value":[{"fullname":"Testy"}]}"#),
+            CannedResponse::new(200).json(r#"{"EntityRecordCountCollection":{"Count":2,"IsReadOnly":false,"Keys":["account","contact"],"Values":[120000,4]}}"#),
+        ])
+        .await;
+        let client = test_client(&server.url, RetryPolicy::new());
+        let query = Query::new("contacts").filter(Filter::Equal("statecode", Attribute::Integer(0))).limit(10);
+
+        let page: Page<Contact> = client.retrieve_multiple(&query.clone().with_count()).await.unwrap();
+        assert_eq!(page.total_count, Some(TotalCount { count: 2, limit_exceeded: false }));
+        assert_eq!(client.count(&query).await.unwrap(), TotalCount { count: 5000, limit_exceeded: true });
+        assert_eq!(
+            client.retrieve_total_record_count(&["account", "contact"]).await.unwrap(),
+            vec![(String::from("account"), 120000), (String::from("contact"), 4)]
+        );
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/api/data/v9.2/contacts?$top=10&$filter=statecode%20eq%200&$count=true&$select=fullname");
+        assert_eq!(requests[1].path, "/api/data/v9.2/contacts?$filter=statecode%20eq%200&$count=true&$select=versionnumber");
+        assert_eq!(requests[1].header("Prefer"), Some("odata.maxpagesize=1"));
+        assert_eq!(requests[2].path, "/api/data/v9.2/RetrieveTotalRecordCount(EntityNames=@p1)?@p1=%5B%22account%22%2C%22contact%22%5D");
+    }
+
+    #[tokio::test]
+    async fn aggregates_grouped_results() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct ContactsPerState {
+            statecode: i32,
+            count: i64,
+            accounts: i64,
+        }
+
+        let server = TestServer::start(vec![
+            CannedResponse::new(200).json(r#"{"value":[{"statecode":0,"count":12,"accounts":3},{"statecode":1,"count":2,"accounts":1}]}"#),
+        ])
+        .await;
+        let client = test_client(&server.url, RetryPolicy::new());
+        let query = Query::new("contacts")
+            .apply(Transformation::Filter(Filter::Equal("donotemail", Attribute::Boolean(false))))
+            .apply(Transformation::GroupBy(
+                vec!["statecode"],
+                vec![Aggregate::Count("count"), Aggregate::CountDistinct("_parentcustomerid_value", "accounts")],
+            ));
+
+        let results: Vec<ContactsPerState> = client.aggregate(&query).await.unwrap();
+        assert_eq!(results[0], ContactsPerState { statecode: 0, count: 12, accounts: 3 });
+        assert_eq!(results.len(), 2);
+
+        let requests = server.requests();
+        assert_eq!(
+            requests[0].path,
+            "/api/data/v9.2/contacts?$apply=filter(donotemail%20eq%20false)/groupby((statecode),aggregate($count%20as%20count,_parentcustomerid_value%20with%20countdistinct%20as%20accounts))"
+        );
+    }
+
+    #[tokio::test]
+    async fn pages_fetchxml_with_paging_cookies() {
+        let server = TestServer::start(vec![
+            CannedResponse::new(200).json(r#"{"value":[{"fullname":"Testy"}],"@Microsoft.Dynamics.CRM.fetchxmlpagingcookie":"<cookie pagenumber=\"2\" pagingcookie=\"%253ccookie%2520page%253d%25221%2522%253e%253c%252fcookie%253e\" istracking=\"False\" />","@Microsoft.Dynamics.CRM.morerecords":true}"#),
+            CannedResponse::new(200).json(r#"{"value":[{"fullname":"Jane"}],"@Microsoft.Dynamics.CRM.morerecords":false}"#),
+        ])
+        .await;
+        let client = test_client(&server.url, RetryPolicy::new());
+        let fetch = FetchXml::new("contacts", r#"<fetch count="1"><entity name="contact"><attribute name="fullname" /></entity></fetch>"#);
+
+        let contacts: Vec<Result<Contact>> = client.retrieve_fetchxml_stream(&fetch).collect().await;
+        let names: Vec<String> = contacts.into_iter().map(|contact| contact.unwrap().fullname).collect();
+        assert_eq!(names, vec!["Testy", "Jane"]);
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].path, format!("/api/data/v9.2/{}", fetch));
+        assert_eq!(
+            requests[1].path,
+            format!(
+                "/api/data/v9.2/contacts?fetchXml={}",
+                encode_path(r#"<fetch count="1" page="2" paging-cookie="&lt;cookie page=&quot;1&quot;&gt;&lt;/cookie&gt;"><entity name="contact"><attribute name="fullname" /></entity></fetch>"#)
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn sends_long_fetchxml_within_a_batch() {
+        let batch_response = |response: &str| {
+            CannedResponse::new(200).body(
+                format!("--batchresponse_1\r\nContent-Type: application/http\r\nContent-Transfer-Encoding: binary\r\n\r\n{}\r\n--batchresponse_1--\r\n", response).into_bytes(),
+                "multipart/mixed; boundary=batchresponse_1",
+            )
+        };
+        let server = TestServer::start(vec![
+            batch_response("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{\"value\":[{\"fullname\":\"Testy\"}]}"),
+            batch_response("HTTP/1.1 400 Bad Request\r\nContent-Type: application/json\r\n\r\n{\"error\":{\"code\":\"0x80041103\",\"message\":\"invalid fetch\"}}"),
+        ])
+        .await;
+        let client = test_client(&server.url, RetryPolicy::new());
+        let values: String = (0..3000).map(|index| format!("<value>{:08}-0000-0000-0000-000000000000</value>", index)).collect();
+        let fetch = FetchXml::new(
+            "contacts",
+            format!(r#"<fetch><entity name="contact"><attribute name="fullname" /><filter><condition attribute="contactid" operator="in">{}</condition></filter></entity></fetch>"#, values),
+        );
+
+        let page: Page<Contact> = client.retrieve_fetchxml(&fetch).await.unwrap();
+        assert_eq!(page.entities, vec![Contact { fullname: String::from("Testy") }]);
+        let error = client.retrieve_fetchxml::<Contact>(&fetch).await.unwrap_err();
+        assert_eq!(error.service_code(), Some("0x80041103"));
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/api/data/v9.2/$batch");
+        assert!(requests[0].body_text().contains(&format!("GET {}api/data/v9.2/{} HTTP/1.1", server.url, fetch)));
+    }
+
+    #[tokio::test]
+    async fn retrieve_all_respects_the_cap() {
+        let server = TestServer::start(contact_pages()).await;
+        let client = test_client(&server.url, RetryPolicy::new());
+        let contacts: Vec<Contact> = client.retrieve_all(&Query::new("contacts"), Some(2)).await.unwrap();
+        assert_eq!(contacts.len(), 2);
+
+        let server = TestServer::start(contact_pages()).await;
+        let client = test_client(&server.url, RetryPolicy::new());
+        let contacts: Vec<Contact> = client.retrieve_all(&Query::new("contacts"), None).await.unwrap();
+        assert_eq!(contacts.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn no_next_page_after_the_last_page() {
+        let server = TestServer::start(vec![CannedResponse::new(200).json(r#"{"value":[]}"#)]).await;
+        let client = test_client(&server.url, RetryPolicy::new());
+
+        let page: Page<Contact> = client.retrieve_multiple(&Query::new("contacts")).await.unwrap();
+        assert!(!page.is_incomplete());
+        assert!(client.retrieve_next_page(&page).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn requests_the_max_page_size_on_every_page() {
+        let server = TestServer::start(contact_pages()).await;
+        let client = test_client(&server.url, RetryPolicy::new());
+
+        let query = Query::new("contacts").max_page_size(2);
+        let contacts: Vec<Contact> = client.retrieve_all(&query, None).await.unwrap();
+        assert_eq!(contacts.len(), 3);
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/api/data/v9.2/contacts?$select=fullname");
+        assert!(requests.iter().all(|request| request.header("Prefer") == Some("odata.maxpagesize=2")));
+    }
+
+    #[tokio::test]
+    async fn resumes_from_a_saved_cursor() {
+        let server = TestServer::start(contact_pages()).await;
+        let client = test_client(&server.url, RetryPolicy::new());
+
+        let query = Query::new("contacts").max_page_size(2);
+        let page: Page<Contact> = client.retrieve_multiple(&query).await.unwrap();
+        let saved = serde_json::to_string(&page.cursor().unwrap()).unwrap();
+
+        let cursor: PageCursor = serde_json::from_str(&saved).unwrap();
+        assert_eq!(cursor.paging_cookie.as_deref(), Some("2"));
+        assert!(cursor.is_for::<Contact>(&query));
+        assert!(!cursor.is_for::<Contact>(&Query::new("accounts")));
+
+        let page: Page<Contact> = client.resume_from(&cursor).await.unwrap();
+        assert_eq!(page.entities, vec![Contact { fullname: String::from("Jane") }]);
+        assert_eq!(server.requests()[1].header("Prefer"), Some("odata.maxpagesize=2"));
+
+        let foreign_client = test_client("https://other.crm.dynamics.com/", RetryPolicy::new());
+        assert!(foreign_client.resume_from::<Contact>(&cursor).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn create_and_update_return_the_written_record() {
+        let server = TestServer::start(vec![
+            CannedResponse::new(201).json(r#"{"@odata.etag":"W/\"1\"","fullname":"Testy McTestface"}"#),
+            CannedResponse::new(200).json(r#"{"fullname":"Testy McTestface"}"#),
+        ])
+        .await;
+        let client = test_client(&server.url, RetryPolicy::new());
+        let contact = NewContact { contactid: Uuid::nil(), lastname: String::from("McTestface") };
+
+        let created: Contact = client.create_and_retrieve(&contact).await.unwrap();
+        let updated: Contact = client.update_and_retrieve(&contact).await.unwrap();
+        assert_eq!(created.fullname, "Testy McTestface");
+        assert_eq!(updated, created);
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/api/data/v9.2/contacts?$select=fullname");
+        assert_eq!(requests[1].method, "PATCH");
+        assert_eq!(requests[1].path, "/api/data/v9.2/contacts(00000000-0000-0000-0000-000000000000)?$select=fullname");
+        assert!(requests.iter().all(|request| request.header("Prefer") == Some("return=representation")));
+    }
+
+    #[tokio::test]
+    async fn writes_with_etags() {
+        let server = TestServer::start(vec![
+            CannedResponse::new(200).json(r#"{"@odata.etag":"W/\"42\"","fullname":"Testy"}"#),
+            CannedResponse::new(412).json(r#"{"error":{"code":"0x80060882","message":"The version of the existing record doesn't match"}}"#),
+            CannedResponse::new(204),
+            CannedResponse::new(304),
+        ])
+        .await;
+        let client = test_client(&server.url, RetryPolicy::new());
+
+        let contact: Versioned<Contact> = client.retrieve(&test_reference()).await.unwrap();
+        assert_eq!(contact.etag.as_deref(), Some("W/\"42\""));
+        assert_eq!(contact.entity.fullname, "Testy");
+
+        let new_contact = NewContact { contactid: test_reference().entity_id, lastname: String::from("McTestface") };
+        let error = client.update_if_match(&new_contact, "W/\"42\"").await.unwrap_err();
+        assert!(error.is_concurrency_conflict());
+
+        client.delete_if_match(&test_reference(), "W/\"43\"").await.unwrap();
+
+        let not_modified: Conditional<Contact> = client.retrieve_if_none_match(&test_reference(), "W/\"43\"").await.unwrap();
+        assert_eq!(not_modified, Conditional::NotModified);
+
+        let requests = server.requests();
+        assert_eq!(requests[1].header("If-Match"), Some("W/\"42\""));
+        assert_eq!(requests[2].method, "DELETE");
+        assert_eq!(requests[2].header("If-Match"), Some("W/\"43\""));
+        assert_eq!(requests[3].header("If-None-Match"), Some("W/\"43\""));
+    }
+
+    #[tokio::test]
+    async fn addresses_records_by_alternate_keys() {
+        let server = TestServer::start(vec![
+            CannedResponse::new(204),
+            CannedResponse::new(200).json(r#"{"fullname":"Testy"}"#),
+            CannedResponse::new(204),
+        ])
+        .await;
+        let client = test_client(&server.url, RetryPolicy::new());
+        let external_contact = ExternalContact { employeeid: String::from("E-100"), lastname: String::from("McTestface") };
+        let reference = external_contact.get_key_reference();
+
+        client.upsert(&external_contact).await.unwrap();
+        let contact: Contact = client.retrieve(&reference).await.unwrap();
+        client.delete(&reference).await.unwrap();
+        assert_eq!(contact.fullname, "Testy");
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "PATCH");
+        assert_eq!(requests[0].path, "/api/data/v9.2/contacts(employeeid='E-100')");
+        assert_eq!(requests[0].body_text(), r#"{"employeeid":"E-100","lastname":"McTestface"}"#);
+        assert_eq!(requests[1].path, "/api/data/v9.2/contacts(employeeid='E-100')?$select=fullname");
+        assert_eq!(requests[2].method, "DELETE");
+        assert_eq!(requests[2].path, "/api/data/v9.2/contacts(employeeid='E-100')");
+    }
+
+    #[tokio::test]
+    async fn reports_upsert_outcomes() {
+        let server = TestServer::start(vec![
+            CannedResponse::new(201).json(r#"{"employeeid":"E-100"}"#),
+            CannedResponse::new(200).json(r#"{"employeeid":"E-100"}"#),
+            CannedResponse::new(412).json(r#"{"error":{"code":"0x80040237","message":"A record with matching key values already exists."}}"#),
+            CannedResponse::new(204),
+        ])
+        .await;
+        let client = test_client(&server.url, RetryPolicy::new());
+        let contact = ExternalContact { employeeid: String::from("E-100"), lastname: String::from("McTestface") };
+
+        assert_eq!(client.upsert_with_mode(&contact, UpsertMode::CreateOrUpdate).await.unwrap(), UpsertOutcome::Created);
+        assert_eq!(client.upsert_with_mode(&contact, UpsertMode::CreateOrUpdate).await.unwrap(), UpsertOutcome::Updated);
+        assert!(client.upsert_with_mode(&contact, UpsertMode::CreateOnly).await.unwrap_err().is_duplicate_key());
+        assert_eq!(client.upsert_with_mode(&contact, UpsertMode::UpdateOnly).await.unwrap(), UpsertOutcome::Updated);
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/api/data/v9.2/contacts(employeeid='E-100')?$select=employeeid");
+        assert_eq!(requests[0].header("Prefer"), Some("return=representation"));
+        assert_eq!(requests[2].path, "/api/data/v9.2/contacts(employeeid='E-100')");
+        assert_eq!(requests[2].header("If-None-Match"), Some("*"));
+        assert_eq!(requests[2].header("Prefer"), None);
+        assert_eq!(requests[3].header("If-Match"), Some("*"));
+    }
+
+    #[tokio::test]
+    async fn impersonates_the_caller_in_requests_and_batches() {
+        let server = TestServer::start(vec![CannedResponse::new(204), CannedResponse::new(200), CannedResponse::new(204)]).await;
+        let client = test_client(&server.url, RetryPolicy::new());
+        let user_id = Uuid::parse_str("87654321-4321-4321-4321-210987654321").unwrap();
+        let on_behalf = client.impersonate(CallerId::AadObjectId(user_id));
+
+        on_behalf.delete(&test_reference()).await.unwrap();
+
+        let mut batch = Batch::new("https://instance.crm.dynamics.com/");
+        batch.delete(&test_reference()).unwrap();
+        batch.delete(&test_reference()).unwrap();
+        on_behalf.execute(&batch).await.unwrap();
+        client.delete(&test_reference()).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].header("CallerObjectId"), Some("87654321-4321-4321-4321-210987654321"));
+        assert_eq!(requests[1].header("CallerObjectId"), Some("87654321-4321-4321-4321-210987654321"));
+        assert_eq!(requests[1].body_text().matches("HTTP/1.1\nCallerObjectId: 87654321-4321-4321-4321-210987654321\n\n").count(), 2);
+        assert_eq!(requests[2].header("CallerObjectId"), None);
+        assert_eq!(on_behalf.get_caller(), Some(CallerId::AadObjectId(user_id)));
+        assert_eq!(client.get_caller(), None);
+        assert_eq!(CallerId::SystemUserId(user_id).header().0, "MSCRMCallerID");
+    }
+
+    #[tokio::test]
+    async fn executes_batches_with_the_web_api_version_of_the_client() {
+        let server = TestServer::start(vec![CannedResponse::new(200)]).await;
+        let client = Client::builder(&server.url)
+            .https_only(false)
+            .web_api_version("9.1")
+            .auth(StaticAuth)
+            .build()
+            .unwrap();
+
+        let account = ReferenceStruct::new("accounts", Uuid::nil());
+        let mut batch = Batch::new("https://instance.crm.dynamics.com/");
+        batch.associate(&account, "contact_customer_accounts", &test_reference()).unwrap();
+        batch.disassociate(&account, "contact_customer_accounts", &test_reference()).unwrap();
+        client.execute(&batch).await.unwrap();
+
+        let requests = server.requests();
+        let body = requests[0].body_text();
+        assert_eq!(requests[0].path, "/api/data/v9.1/$batch");
+        assert!(body.contains("POST https://instance.crm.dynamics.com/api/data/v9.1/accounts(00000000-0000-0000-0000-000000000000)/contact_customer_accounts/$ref HTTP/1.1\n"));
+        assert!(body.contains("{\"@odata.id\":\"https://instance.crm.dynamics.com/api/data/v9.1/contacts("));
+        assert!(body.contains("$ref?$id=https%3A%2F%2Finstance.crm.dynamics.com%2Fapi%2Fdata%2Fv9.1%2Fcontacts%28"));
+        assert!(!body.contains("v9.2"));
+        assert!(batch.to_string().contains("POST https://instance.crm.dynamics.com/api/data/v9.2/accounts("));
+    }
+
+    #[tokio::test]
+    async fn sends_request_options() {
+        let server = TestServer::start(vec![CannedResponse::new(204), CannedResponse::new(200)]).await;
+        let client = test_client(&server.url, RetryPolicy::new());
+        let options = RequestOptions::new().bypass_custom_plugin_execution(true).solution_unique_name("Core");
+        let migration = client.using_options(options.clone()).impersonate(CallerId::SystemUserId(Uuid::nil()));
+
+        migration.delete(&test_reference()).await.unwrap();
+        let mut batch = Batch::new("https://instance.crm.dynamics.com/");
+        batch.delete(&test_reference()).unwrap();
+        migration.execute(&batch).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(migration.get_options(), &options);
+        assert_eq!(requests[0].header("MSCRM.BypassCustomPluginExecution"), Some("true"));
+        assert_eq!(requests[0].header("MSCRM.SolutionUniqueName"), Some("Core"));
+        assert_eq!(requests[0].header("MSCRMCallerID"), Some("00000000-0000-0000-0000-000000000000"));
+        assert!(requests[1].body_text().contains(
+            "HTTP/1.1\nMSCRM.BypassCustomPluginExecution: true\nMSCRM.SolutionUniqueName: Core\nMSCRMCallerID: 00000000-0000-0000-0000-000000000000\n\n"
+        ));
+    }
+
+    #[tokio::test]
+    async fn associates_records_through_navigation_properties() {
+        let server = TestServer::start(vec![
+            CannedResponse::new(204),
+            CannedResponse::new(204),
+            CannedResponse::new(204),
+            CannedResponse::new(204),
+            CannedResponse::new(204),
+            CannedResponse::new(400).json(r#"{"error":{"code":"0x8006088a","message":"Resource not found for the segment 'unknown_relationship'."}}"#),
+        ])
+        .await;
+        let client = test_client(&server.url, RetryPolicy::new());
+        let account = ReferenceStruct::new("accounts", Uuid::nil());
+        let keyed_contact = KeyReference::alternate("contacts", vec![("employeeid", Attribute::String(String::from("A&B=1")))]);
+
+        client.associate(&account, "contact_customer_accounts", &test_reference()).await.unwrap();
+        client.disassociate(&account, "contact_customer_accounts", &test_reference()).await.unwrap();
+        client.set_reference(&test_reference(), "parentcustomerid_account", &account).await.unwrap();
+        client.clear_reference(&test_reference(), "parentcustomerid_account").await.unwrap();
+        client.disassociate(&account, "contact_customer_accounts", &keyed_contact).await.unwrap();
+        let error = client.associate(&account, "unknown_relationship", &test_reference()).await.unwrap_err();
+        assert!(error.is_missing_relationship());
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/api/data/v9.2/accounts(00000000-0000-0000-0000-000000000000)/contact_customer_accounts/$ref");
+        assert_eq!(
+            requests[0].body_text(),
+            format!(r#"{{"@odata.id":"{}api/data/v9.2/contacts(12345678-1234-1234-1234-123456789012)"}}"#, server.url)
+        );
+        assert_eq!(requests[1].method, "DELETE");
+        assert_eq!(
+            requests[1].path,
+            format!(
+                "/api/data/v9.2/accounts(00000000-0000-0000-0000-000000000000)/contact_customer_accounts/$ref?$id={}",
+                encode_path(&format!("{}api/data/v9.2/contacts(12345678-1234-1234-1234-123456789012)", server.url))
+            )
+        );
+        assert_eq!(requests[2].method, "PUT");
+        assert_eq!(requests[3].method, "DELETE");
+        assert_eq!(requests[3].path, "/api/data/v9.2/contacts(12345678-1234-1234-1234-123456789012)/parentcustomerid_account/$ref");
+        // reqwest additionally encodes the quotes of the key literal in the query
+        assert_eq!(
+            requests[4].path,
+            format!(
+                "/api/data/v9.2/accounts(00000000-0000-0000-0000-000000000000)/contact_customer_accounts/$ref?$id={}api%2Fdata%2Fv9.2%2Fcontacts%28employeeid%3D%27A%2526B%253D1%27%29",
+                encode_path(&server.url)
+            )
+        );
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct AccountWithContacts {
+        name: String,
+        primarycontactid: Option<Contact>,
+        contact_customer_accounts: Vec<Contact>,
+    }
+
+    impl ReadEntity for AccountWithContacts {}
+
+    impl Select for AccountWithContacts {
+        fn get_columns() -> &'static [&'static str] {
+            &["name"]
+        }
+
+        fn get_expands() -> Vec<Expand> {
+            vec![
+                Expand::of::<Contact>("primarycontactid"),
+                Expand::of::<Contact>("contact_customer_accounts").limit(2),
+            ]
+        }
+    }
+
+    #[tokio::test]
+    async fn expands_related_records() {
+        let server = TestServer::start(vec![
+            CannedResponse::new(200).json(
+                r#"{"name":"Testy Inc.","primarycontactid":null,"contact_customer_accounts":[{"fullname":"Testy"},{"fullname":"Jane"}]}"#,
+            ),
+            CannedResponse::new(200).json(
+                r#"{"value":[{"name":"Testy Inc.","primarycontactid":{"fullname":"Testy"},"contact_customer_accounts":[]}]}"#,
+            ),
+        ])
+        .await;
+        let client = test_client(&server.url, RetryPolicy::new());
+
+        let account: AccountWithContacts = client.retrieve(&ReferenceStruct::new("accounts", Uuid::nil())).await.unwrap();
+        assert_eq!(account.primarycontactid, None);
+        assert_eq!(account.contact_customer_accounts.len(), 2);
+
+        let query = Query::new("accounts").expand(
+            Expand::new("owninguser", &["fullname"]).filter(Filter::Equal("isdisabled", Attribute::Boolean(false))),
+        );
+        let page: Page<AccountWithContacts> = client.retrieve_multiple(&query).await.unwrap();
+        assert_eq!(page.entities[0].primarycontactid, Some(Contact { fullname: String::from("Testy") }));
+        assert!(page.cursor().is_none());
+
+        let requests = server.requests();
+        let expected = "$select=name&$expand=primarycontactid($select=fullname),contact_customer_accounts($select=fullname;$top=2)";
+        assert_eq!(
+            requests[0].path,
+            format!("/api/data/v9.2/accounts(00000000-0000-0000-0000-000000000000)?{}", expected)
+        );
+        assert_eq!(
+            requests[1].path,
+            format!("/api/data/v9.2/accounts?{},owninguser($select=fullname;$filter=isdisabled%20eq%20false)", expected)
+        );
+    }
+
+    #[derive(Serialize)]
+    struct NewAccount {
+        accountid: Uuid,
+        name: String,
+        primarycontactid: NewPrimaryContact,
+        #[serde(rename = "Account_Annotation")]
+        notes: Vec<NewNote>,
+    }
+
+    #[derive(Serialize)]
+    struct NewPrimaryContact {
+        lastname: String,
+    }
+
+    #[derive(Serialize)]
+    struct NewNote {
+        subject: String,
+    }
+
+    impl WriteEntity for NewAccount {}
+
+    impl Reference for NewAccount {
+        fn get_reference(&self) -> ReferenceStruct {
+            ReferenceStruct::new("accounts", self.accountid)
+        }
+    }
+
+    #[tokio::test]
+    async fn deep_insert_returns_all_created_ids() {
+        let server = TestServer::start(vec![CannedResponse::new(201).json(
+            r#"{"accountid":"00000000-0000-0000-0000-000000000001","primarycontactid":{"contactid":"00000000-0000-0000-0000-000000000002"},"Account_Annotation":[{"annotationid":"00000000-0000-0000-0000-000000000003"},{"annotationid":"00000000-0000-0000-0000-000000000004"}]}"#,
+        )])
+        .await;
+        let client = test_client(&server.url, RetryPolicy::new());
+        let account = NewAccount {
+            accountid: Uuid::from_u128(1),
+            name: String::from("Testy Inc."),
+            primarycontactid: NewPrimaryContact { lastname: String::from("McTestface") },
+            notes: vec![
+                NewNote { subject: String::from("first") },
+                NewNote { subject: String::from("second") },
+            ],
+        };
+        let layout = DeepInsert::new("accountid")
+            .related("primarycontactid", DeepInsert::new("contactid"))
+            .related("Account_Annotation", DeepInsert::new("annotationid"));
+
+        let created = client.deep_insert(&account, &layout).await.unwrap();
+        assert_eq!(created.related("Account_Annotation").len(), 2);
+        assert_eq!(
+            created.all_ids(),
+            (1..=4).map(Uuid::from_u128).collect::<Vec<_>>()
+        );
+
+        let request = &server.requests()[0];
+        assert_eq!(request.header("Prefer"), Some("return=representation"));
+        assert_eq!(
+            request.path,
+            "/api/data/v9.2/accounts?$select=accountid&$expand=primarycontactid($select=contactid),Account_Annotation($select=annotationid)"
+        );
+
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "accountid": "00000000-0000-0000-0000-000000000001",
+                "name": "Testy Inc.",
+                "primarycontactid": { "lastname": "McTestface" },
+                "Account_Annotation": [{ "subject": "first" }, { "subject": "second" }]
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn reads_writes_and_clears_single_columns() {
+        let server = TestServer::start(vec![
+            CannedResponse::new(200).json(r#"{"@odata.context":"{server}$metadata#contacts(...)/emailaddress1","value":"testy@example.com"}"#),
+            CannedResponse::new(204),
+            CannedResponse::new(204),
+            CannedResponse::new(204),
+            CannedResponse::new(204),
+        ])
+        .await;
+        let client = test_client(&server.url, RetryPolicy::new());
+
+        let email: Option<String> = client.get_column(&test_reference(), "emailaddress1").await.unwrap();
+        assert_eq!(email.as_deref(), Some("testy@example.com"));
+        let phone: Option<String> = client.get_column(&test_reference(), "telephone1").await.unwrap();
+        assert_eq!(phone, None);
+        client.set_column(&test_reference(), "donotemail", &true).await.unwrap();
+        client.clear_column(&test_reference(), "telephone1").await.unwrap();
+        client.clear_column(&test_reference(), Column::Lookup("parentcustomerid_account")).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(requests[0].path, "/api/data/v9.2/contacts(12345678-1234-1234-1234-123456789012)/emailaddress1");
+        assert_eq!(requests[2].method, "PUT");
+        assert_eq!(requests[2].body_text(), r#"{"value":true}"#);
+        assert_eq!(requests[3].method, "DELETE");
+        assert_eq!(requests[3].path, "/api/data/v9.2/contacts(12345678-1234-1234-1234-123456789012)/telephone1");
+        assert_eq!(requests[4].method, "DELETE");
+        assert_eq!(requests[4].path, "/api/data/v9.2/contacts(12345678-1234-1234-1234-123456789012)/parentcustomerid_account/$ref");
+    }
+
+    #[derive(Serialize)]
+    struct QualifyLead {
+        #[serde(skip)]
+        lead_id: Uuid,
+        #[serde(rename = "CreateContact")]
+        create_contact: bool,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct QualifyLeadResponse {
+        value: Vec<serde_json::Value>,
+    }
+
+    impl DataverseAction for QualifyLead {
+        type Response = QualifyLeadResponse;
+
+        fn name(&self) -> &str {
+            "QualifyLead"
+        }
+
+        fn binding(&self) -> Binding {
+            Binding::Record(KeyReference::id("leads", self.lead_id))
+        }
+    }
+
+    #[tokio::test]
+    async fn executes_actions_and_functions() {
+        let server = TestServer::start(vec![
+            CannedResponse::new(200).json(r#"{"value":[{"contactid":"00000000-0000-0000-0000-000000000001"}]}"#),
+            CannedResponse::new(200).json(r#"{"UserId":"00000000-0000-0000-0000-000000000002","BusinessUnitId":"00000000-0000-0000-0000-000000000003","OrganizationId":"00000000-0000-0000-0000-000000000004"}"#),
+            CannedResponse::new(204),
+        ])
+        .await;
+        let client = test_client(&server.url, RetryPolicy::new());
+
+        let qualified = client.execute_action(&QualifyLead { lead_id: Uuid::nil(), create_contact: true }).await.unwrap();
+        assert_eq!(qualified.value.len(), 1);
+        let who_am_i = client.call_function(&WhoAmI).await.unwrap();
+        assert_eq!(who_am_i.user_id, Uuid::from_u128(2));
+        client.merge("account", Uuid::nil(), Uuid::nil()).await.unwrap();
+        assert!(client.merge("opportunity", Uuid::nil(), Uuid::nil()).await.is_err());
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/api/data/v9.2/leads(00000000-0000-0000-0000-000000000000)/Microsoft.Dynamics.CRM.QualifyLead");
+        assert_eq!(requests[0].body_text(), r#"{"CreateContact":true}"#);
+        assert_eq!(requests[1].method, "GET");
+        assert_eq!(requests[1].path, "/api/data/v9.2/WhoAmI()");
+        assert_eq!(requests[2].path, "/api/data/v9.2/Merge");
+        assert!(requests[2].body_text().contains(r#""accountid":"00000000-0000-0000-0000-000000000000""#));
+        assert_eq!(requests.len(), 3);
+    }
+}
@@ -1,6 +1,20 @@
-use serde::{Serialize, de::DeserializeOwned};
+use std::borrow::Cow;
 
-use crate::{reference::Reference, select::Select};
+use serde::{de::{DeserializeOwned, Error}, Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    client::render_select,
+    error::{DataverseError, ErrorKind},
+    query::expand::Expand,
+    reference::Addressable,
+    result::IntoDataverseResult,
+    select::Select,
+};
+
+#[cfg(feature = "derive")]
+pub use powerplatform_dataverse_service_client_derive::{ReadEntity, WriteEntity};
 
 /**
 Supertrait for entities that can be retrieved from a Microsoft
@@ -48,6 +62,9 @@ the following functions in `Client`:
 - `update(...)`
 - `upsert(...)`
 
+Entities addressed by their primary id implement `Reference`, entities addressed
+by an alternate key implement `Addressable` directly
+
 # Examples
 ```rust
 use serde::Serialize;
@@ -76,4 +93,276 @@ impl Reference for Contact {
 }
 ```
 */
-pub trait WriteEntity: Serialize + Reference {}
+pub trait WriteEntity: Serialize + Addressable {}
+
+/**
+Wraps a retrieved entity together with its `@odata.etag`
+
+The ETag identifies the version of the record when it was retrieved and can be
+passed to `Client::update_if_match(...)` or `Client::delete_if_match(...)` so the
+write fails when someone else changed the record in the meantime
+
+# Examples
+```rust
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use powerplatform_dataverse_service_client::{
+    client::Client,
+    entity::{ReadEntity, Versioned, WriteEntity},
+    reference::{Reference, ReferenceStruct},
+    result::{IntoDataverseResult, Result},
+    select::Select
+};
+
+async fn test() -> Result<()> {
+    let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+    let reference = ReferenceStruct::new(
+        "contacts",
+        Uuid::parse_str("12345678-1234-1234-1234-123456789012").into_dataverse_result()?
+    );
+
+    let mut contact: Versioned<Contact> = client.retrieve(&reference).await?;
+    contact.entity.lastname = String::from("McTestface");
+
+    match client.update_if_match(&contact.entity, contact.etag.as_deref().unwrap_or("*")).await {
+        Err(error) if error.is_concurrency_conflict() => println!("someone else was faster"),
+        result => result?,
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize, Serialize)]
+struct Contact {
+    contactid: Uuid,
+    lastname: String,
+}
+
+impl ReadEntity for Contact {}
+impl WriteEntity for Contact {}
+
+impl Select for Contact {
+    fn get_columns() -> &'static [&'static str] {
+        &["contactid", "lastname"]
+    }
+}
+
+impl Reference for Contact {
+    fn get_reference(&self) -> ReferenceStruct {
+        ReferenceStruct::new("contacts", self.contactid)
+    }
+}
+```
+*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Versioned<E> {
+    pub entity: E,
+    pub etag: Option<String>,
+}
+
+impl<'de, E: DeserializeOwned> Deserialize<'de> for Versioned<E> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de> {
+        let mut value = serde_json::Value::deserialize(deserializer)?;
+        let etag = value
+            .as_object_mut()
+            .and_then(|object| object.remove("@odata.etag"))
+            .and_then(|etag| etag.as_str().map(String::from));
+
+        let entity = E::deserialize(value).map_err(D::Error::custom)?;
+        Ok(Self { entity, etag })
+    }
+}
+
+impl<E: Select> Select for Versioned<E> {
+    fn get_columns() -> &'static [&'static str] {
+        E::get_columns()
+    }
+
+    fn get_expands() -> Vec<Expand> {
+        E::get_expands()
+    }
+}
+
+impl<E: ReadEntity> ReadEntity for Versioned<E> {}
+
+/**
+The result of a conditional retrieve with `Client::retrieve_if_none_match(...)`
+*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Conditional<E> {
+    /// The record changed since the given ETag and was retrieved again
+    Modified(E),
+
+    /// The record did not change since the given ETag, so no body was transferred
+    NotModified,
+}
+
+/**
+Controls whether an upsert may create a new record, update an existing one or both
+
+Used with `Client::upsert_with_mode(...)` and `Batch::upsert_with_mode(...)`
+*/
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum UpsertMode {
+    /// Creates the record if it doesn't exist and updates it otherwise
+    CreateOrUpdate,
+
+    /// Only creates the record and fails if it already exists (`If-None-Match: *`)
+    CreateOnly,
+
+    /// Only updates the record and fails if it doesn't exist (`If-Match: *`)
+    UpdateOnly,
+}
+
+impl Default for UpsertMode {
+    fn default() -> Self {
+        UpsertMode::CreateOrUpdate
+    }
+}
+
+impl UpsertMode {
+    /// the precondition header that enforces this mode, if any
+    pub(crate) fn precondition(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            UpsertMode::CreateOrUpdate => None,
+            UpsertMode::CreateOnly => Some(("If-None-Match", "*")),
+            UpsertMode::UpdateOnly => Some(("If-Match", "*")),
+        }
+    }
+}
+
+/**
+Reports what an upsert did with the record
+*/
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum UpsertOutcome {
+    /// A new record was created (`201 Created`)
+    Created,
+
+    /// An existing record was updated
+    Updated,
+}
+
+/**
+Describes the records a deep insert creates so their ids can be read from the response
+
+Each level names the primary id column of the created records and the navigation
+properties under which related records were embedded into the written entity. The
+layout mirrors the nesting of the written entity, see `Client::deep_insert(...)`
+
+# Examples
+```rust
+use powerplatform_dataverse_service_client::entity::DeepInsert;
+
+let layout = DeepInsert::new("accountid")
+    .related("contact_customer_accounts", DeepInsert::new("contactid"))
+    .related("Account_Annotation", DeepInsert::new("annotationid"));
+
+assert_eq!(
+    layout.to_select(),
+    "$select=accountid&$expand=contact_customer_accounts($select=contactid),Account_Annotation($select=annotationid)"
+);
+```
+*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeepInsert {
+    pub primary_id: &'static str,
+    pub related: Vec<(&'static str, DeepInsert)>,
+}
+
+impl DeepInsert {
+    /// Describes created records with the given primary id column and no related records
+    pub fn new(primary_id: &'static str) -> Self {
+        Self {
+            primary_id,
+            related: Vec::new(),
+        }
+    }
+
+    /// adds records that were embedded under the given navigation property
+    pub fn related(mut self, navigation_property: &'static str, related: DeepInsert) -> Self {
+        self.related.push((navigation_property, related));
+        self
+    }
+
+    /// renders the `$select` and `$expand` options that return the ids of all created records
+    pub fn to_select(&self) -> String {
+        render_select(&[self.primary_id], &self.expands())
+    }
+
+    /// describes the related records as expansions that select only their primary ids
+    fn expands(&self) -> Vec<Expand> {
+        self.related
+            .iter()
+            .map(|(navigation_property, related)| Expand {
+                columns: Cow::Owned(vec![related.primary_id]),
+                expand: related.expands(),
+                ..Expand::new(navigation_property, &[])
+            })
+            .collect()
+    }
+
+    /// reads the ids of the created records from the returned representation
+    pub(crate) fn read_ids(&self, value: &Value) -> crate::result::Result<CreatedRecord> {
+        let entity_id = value
+            .get(self.primary_id)
+            .and_then(Value::as_str)
+            .ok_or_else(|| DataverseError::with_kind(
+                ErrorKind::Serialization,
+                format!("Dataverse provided no '{}' for a created record", self.primary_id),
+            ))?;
+
+        let mut related = Vec::with_capacity(self.related.len());
+        for (navigation_property, layout) in &self.related {
+            let records = match value.get(*navigation_property) {
+                Some(Value::Array(values)) => values
+                    .iter()
+                    .map(|value| layout.read_ids(value))
+                    .collect::<crate::result::Result<Vec<_>>>()?,
+                Some(Value::Null) | None => Vec::new(),
+                Some(value) => vec![layout.read_ids(value)?],
+            };
+
+            related.push((*navigation_property, records));
+        }
+
+        Ok(CreatedRecord {
+            entity_id: Uuid::parse_str(entity_id).into_dataverse_result()?,
+            related,
+        })
+    }
+}
+
+/**
+The ids of the records a deep insert created, nested like the written entity
+*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CreatedRecord {
+    pub entity_id: Uuid,
+    pub related: Vec<(&'static str, Vec<CreatedRecord>)>,
+}
+
+impl CreatedRecord {
+    /// returns the records that were created under the given navigation property
+    pub fn related(&self, navigation_property: &str) -> &[CreatedRecord] {
+        self.related
+            .iter()
+            .find(|(name, _)| *name == navigation_property)
+            .map(|(_, records)| records.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// returns the ids of this record and of all related records, parents before their children
+    pub fn all_ids(&self) -> Vec<Uuid> {
+        let mut ids = vec![self.entity_id];
+
+        for (_, records) in &self.related {
+            for record in records {
+                ids.extend(record.all_ids());
+            }
+        }
+
+        ids
+    }
+}
@@ -1,24 +1,371 @@
-use std::{error::Error, fmt::Display};
+use std::{any::Any, error::Error, fmt::Display, sync::Arc};
+
+use reqwest::{Response, StatusCode};
+use serde::Deserialize;
+
+/// Header Dataverse uses to identify a request in its service logs
+pub static SERVICE_REQUEST_ID_HEADER: &str = "x-ms-service-request-id";
+
+/// OData error code of a table that has change tracking disabled
+const CHANGE_TRACKING_DISABLED_CODE: &str = "0x80044353";
+
+/**
+Classifies where a `DataverseError` originated
+*/
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// The request could not be sent or the response could not be received
+    Transport,
+
+    /// No valid token could be acquired for the request
+    Authentication,
+
+    /// A payload could not be serialized or deserialized
+    Serialization,
+
+    /// Dataverse answered with an http client or server error
+    Http,
+
+    /// Any other error, mostly invalid arguments detected by this crate
+    Other,
+}
+
+/**
+The error details Dataverse provides in the body of a failed response
+
+```json
+{ "error": { "code": "0x80040217", "message": "account With Id = ... Does Not Exist" } }
+```
+*/
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct ServiceError {
+    pub code: String,
+    pub message: String,
+}
 
 /**
 The Error that is returned if any of the operations in this crate
 fails.
+
+Besides the message the error carries its `ErrorKind` and, when Dataverse answered
+with an error response, the http status, the parsed OData error and the service request id.
+The helpers `is_not_found()`, `is_throttled()`, `is_concurrency_conflict()`,
+`is_duplicate_key()`, `is_missing_relationship()` and `is_change_tracking_disabled()` allow branching on common failures without matching on strings
+
+# Examples
+```rust
+use powerplatform_dataverse_service_client::{
+    auth::Authenticate,
+    client::Client,
+    reference::ReferenceStruct,
+    result::Result,
+};
+
+async fn delete_if_present<A: Authenticate>(client: &Client<'_, A>, reference: &ReferenceStruct) -> Result<()> {
+    match client.delete(reference).await {
+        Err(error) if error.is_not_found() => Ok(()),
+        result => result,
+    }
+}
+```
 */
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug)]
 pub struct DataverseError {
+    pub kind: ErrorKind,
     pub message: String,
+    pub status: Option<StatusCode>,
+    pub service_error: Option<ServiceError>,
+    pub request_id: Option<String>,
+    source: Option<Arc<dyn Error + Send + Sync>>,
 }
 
 impl DataverseError {
+    /// creates an error of kind `ErrorKind::Other` with the given message
     pub fn new(message: String) -> Self {
-        Self { message }
+        Self::with_kind(ErrorKind::Other, message)
+    }
+
+    /// creates an error of the given kind with the given message
+    pub fn with_kind(kind: ErrorKind, message: String) -> Self {
+        Self {
+            kind,
+            message,
+            status: None,
+            service_error: None,
+            request_id: None,
+            source: None,
+        }
+    }
+
+    /**
+    creates the error for a tracked query of a table that has change tracking disabled
+
+    It carries the service code Dataverse uses for this case, because Dataverse itself
+    just answers without a delta link
+    */
+    pub(crate) fn change_tracking_disabled(logical_name: &str) -> Self {
+        let message = format!(
+            "Dataverse provided no delta link, change tracking is not enabled for '{}'",
+            logical_name
+        );
+
+        Self {
+            service_error: Some(ServiceError {
+                code: String::from(CHANGE_TRACKING_DISABLED_CODE),
+                message: message.clone(),
+            }),
+            ..Self::new(message)
+        }
+    }
+
+    /// creates an error of kind `ErrorKind::Other` from the message of an error that only implements `Display`
+    pub fn from_display(error: impl Display) -> Self {
+        Self::new(error.to_string())
+    }
+
+    /**
+    wraps the given error and keeps it as the source of this error
+
+    The kind is derived from the type of the wrapped error:
+    - `reqwest::Error` becomes `ErrorKind::Transport` (or `ErrorKind::Serialization` for decode errors)
+    - `serde_json::Error`, `uuid::Error` and `std::fmt::Error` become `ErrorKind::Serialization`
+    - a `DataverseError` is returned unchanged
+    */
+    pub fn from_source<E: Error + Send + Sync + 'static>(error: E) -> Self {
+        let any: &dyn Any = &error;
+
+        if let Some(error) = any.downcast_ref::<DataverseError>() {
+            return error.clone();
+        }
+
+        let mut status = None;
+        let kind = if let Some(error) = any.downcast_ref::<reqwest::Error>() {
+            status = error.status();
+            if error.is_decode() {
+                ErrorKind::Serialization
+            } else {
+                ErrorKind::Transport
+            }
+        } else if any.is::<serde_json::Error>() || any.is::<uuid::Error>() || any.is::<std::fmt::Error>() {
+            ErrorKind::Serialization
+        } else {
+            ErrorKind::Other
+        };
+
+        let message = error.to_string();
+        Self {
+            status,
+            source: Some(Arc::new(error)),
+            ..Self::with_kind(kind, message)
+        }
+    }
+
+    /**
+    reads a failed response from Dataverse into an error of kind `ErrorKind::Http`
+
+    The body is parsed as an OData error if possible, otherwise the raw body is used as message
+    */
+    pub async fn from_response(response: Response) -> Self {
+        let status = response.status();
+        let request_id = response
+            .headers()
+            .get(SERVICE_REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from);
+
+        let body = match response.bytes().await {
+            Ok(body) => body,
+            Err(error) => return Self { status: Some(status), request_id, ..Self::from_source(error) },
+        };
+
+        Self::from_body(status, request_id, &body)
+    }
+
+    /// reads a failed response from its status and body, e.g. a response within a `$batch` response
+    pub(crate) fn from_body(status: StatusCode, request_id: Option<String>, body: &[u8]) -> Self {
+        let service_error = serde_json::from_slice::<ODataError>(body)
+            .ok()
+            .map(|odata_error| odata_error.error);
+
+        let message = match &service_error {
+            Some(service_error) => service_error.message.clone(),
+            None if body.is_empty() => String::from("no error details provided from server"),
+            None => String::from_utf8_lossy(body).into_owned(),
+        };
+
+        Self {
+            status: Some(status),
+            service_error,
+            request_id,
+            ..Self::with_kind(ErrorKind::Http, message)
+        }
+    }
+
+    /// changes the kind of this error
+    pub fn into_kind(mut self, kind: ErrorKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// returns the OData error code Dataverse provided, e.g. `0x80040217`
+    pub fn service_code(&self) -> Option<&str> {
+        self.service_error.as_ref().map(|error| error.code.as_str())
+    }
+
+    /// Indicates that the addressed record or resource does not exist
+    pub fn is_not_found(&self) -> bool {
+        self.status == Some(StatusCode::NOT_FOUND) || self.has_code(&["0x80040217"])
+    }
+
+    /// Indicates that the request was rejected by the service protection limits
+    pub fn is_throttled(&self) -> bool {
+        self.status == Some(StatusCode::TOO_MANY_REQUESTS)
+            || self.has_code(&["0x80072321", "0x80072322", "0x80072326"])
+    }
+
+    /// Indicates that the record was changed by someone else since it was read
+    pub fn is_concurrency_conflict(&self) -> bool {
+        (self.status == Some(StatusCode::PRECONDITION_FAILED) && !self.is_duplicate_key())
+            || self.has_code(&["0x80060882"])
+    }
+
+    /// Indicates that a record with the same primary or alternate key already exists
+    pub fn is_duplicate_key(&self) -> bool {
+        self.has_code(&["0x80040237", "0x80060892"])
+    }
+
+    /**
+    Indicates that a navigation property (relationship) or property in the request url does not exist
+
+    Dataverse reports these with `400 Bad Request`, e.g. for `associate(...)` with a
+    misspelled relationship name
+    */
+    pub fn is_missing_relationship(&self) -> bool {
+        self.has_code(&["0x8006088a", "0x80060888"])
+    }
+
+    /**
+    Indicates that changes of a table can't be tracked, because change tracking is not
+    enabled for the table or the change token expired
+
+    Dataverse ignores the change tracking preference for tables without change tracking,
+    which `Client::track_changes(...)` reports as this error as well. An expired token
+    requires a new initial synchronization
+    */
+    pub fn is_change_tracking_disabled(&self) -> bool {
+        self.has_code(&["0x80044352", CHANGE_TRACKING_DISABLED_CODE])
+    }
+
+    fn has_code(&self, codes: &[&str]) -> bool {
+        match self.service_code() {
+            Some(code) => codes.iter().any(|candidate| candidate.eq_ignore_ascii_case(code)),
+            None => false,
+        }
     }
 }
 
-impl Error for DataverseError {}
+impl Error for DataverseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| source.as_ref() as &(dyn Error + 'static))
+    }
+}
 
 impl Display for DataverseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(status) = self.status {
+            f.write_fmt(format_args!("{} ", status))?;
+        }
+
+        if let Some(code) = self.service_code() {
+            f.write_fmt(format_args!("({}) ", code))?;
+        }
+
         f.write_str(&self.message)
     }
 }
+
+#[derive(Deserialize)]
+struct ODataError {
+    error: ServiceError,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use reqwest::StatusCode;
+
+    use crate::{
+        error::{DataverseError, ErrorKind},
+        result::IntoDataverseResult,
+        testing::{CannedResponse, TestServer},
+    };
+
+    #[tokio::test]
+    async fn parses_odata_errors() {
+        let server = TestServer::start(vec![CannedResponse::new(404)
+            .header("x-ms-service-request-id", "abc-123")
+            .json(r#"{"error":{"code":"0x80040217","message":"contact With Id = 1 Does Not Exist"}}"#)])
+        .await;
+
+        let response = reqwest::get(&server.url).await.unwrap();
+        let error = DataverseError::from_response(response).await;
+
+        assert_eq!(error.kind, ErrorKind::Http);
+        assert_eq!(error.status, Some(StatusCode::NOT_FOUND));
+        assert_eq!(error.service_code(), Some("0x80040217"));
+        assert_eq!(error.request_id.as_deref(), Some("abc-123"));
+        assert_eq!(error.message, "contact With Id = 1 Does Not Exist");
+        assert!(error.is_not_found());
+        assert!(!error.is_throttled());
+        assert_eq!(
+            error.to_string(),
+            "404 Not Found (0x80040217) contact With Id = 1 Does Not Exist"
+        );
+    }
+
+    #[tokio::test]
+    async fn classifies_duplicate_keys_and_conflicts() {
+        let server = TestServer::start(vec![
+            CannedResponse::new(412).json(r#"{"error":{"code":"0x80040237","message":"duplicate"}}"#),
+            CannedResponse::new(412).json(r#"{"error":{"code":"0x80060882","message":"version mismatch"}}"#),
+        ])
+        .await;
+
+        let duplicate = DataverseError::from_response(reqwest::get(&server.url).await.unwrap()).await;
+        let conflict = DataverseError::from_response(reqwest::get(&server.url).await.unwrap()).await;
+
+        assert!(duplicate.is_duplicate_key());
+        assert!(!duplicate.is_concurrency_conflict());
+        assert!(conflict.is_concurrency_conflict());
+        assert!(!conflict.is_duplicate_key());
+    }
+
+    #[tokio::test]
+    async fn classifies_disabled_change_tracking() {
+        let server = TestServer::start(vec![
+            CannedResponse::new(400).json(r#"{"error":{"code":"0x80044352","message":"The version stamp associated with the client has expired. Please perform a full sync."}}"#),
+        ])
+        .await;
+
+        let expired = DataverseError::from_response(reqwest::get(&server.url).await.unwrap()).await;
+
+        assert!(expired.is_change_tracking_disabled());
+        let disabled = DataverseError::change_tracking_disabled("contacts");
+        assert!(disabled.is_change_tracking_disabled());
+        assert_eq!(disabled.service_code(), Some("0x80044353"));
+        assert!(!DataverseError::new(String::from("change tracking is not enabled")).is_change_tracking_disabled());
+    }
+
+    #[test]
+    fn keeps_the_source_error() {
+        let error = serde_json::from_str::<u32>("no number")
+            .into_dataverse_result()
+            .unwrap_err();
+
+        assert_eq!(error.kind, ErrorKind::Serialization);
+        assert!(error.source().unwrap().is::<serde_json::Error>());
+    }
+}
@@ -0,0 +1,361 @@
+/*!
+Module for transferring the content of file and image columns
+
+Files are uploaded in blocks with the chunked transfer protocol of dataverse
+(`x-ms-transfer-mode: chunked`) and downloaded in blocks with range requests, so
+only one block is held in memory at a time regardless of the size of the file.
+
+See `Client::upload_file(...)`, `Client::download_file(...)` and `Client::download_image(...)`
+
+# Examples
+```rust
+use uuid::Uuid;
+use powerplatform_dataverse_service_client::{
+    auth::Authenticate,
+    client::Client,
+    file::{FileUpload, DEFAULT_BLOCK_SIZE},
+    reference::ReferenceStruct,
+    result::Result,
+};
+
+async fn copy_document<A: Authenticate + Send + Sync>(client: &Client<'_, A>) -> Result<()> {
+    let source = ReferenceStruct::new("accounts", Uuid::new_v4());
+    let target = ReferenceStruct::new("accounts", Uuid::new_v4());
+
+    let download = client.download_file(&source, "cr_document", DEFAULT_BLOCK_SIZE).await?;
+    let upload = FileUpload::new(download.file_name().unwrap_or("document.pdf"), download.size());
+    client.upload_file(&target, "cr_document", &upload, download).await
+}
+```
+*/
+
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::Stream;
+use tokio::io::{AsyncRead, ReadBuf};
+
+use crate::{
+    auth::Authenticate,
+    client::Client,
+    result::Result,
+};
+
+/// The block size dataverse uses for chunked transfers unless told otherwise (4 MiB)
+pub const DEFAULT_BLOCK_SIZE: u32 = 4 * 1024 * 1024;
+
+/**
+Describes a file that is uploaded into a file or image column
+
+Unless a block size is given, the block size proposed by dataverse is used
+*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileUpload {
+    pub file_name: String,
+    pub size: u64,
+    pub block_size: Option<u32>,
+}
+
+impl FileUpload {
+    /// Describes a file with the given name and size in bytes
+    pub fn new(file_name: impl Into<String>, size: u64) -> Self {
+        Self {
+            file_name: file_name.into(),
+            size,
+            block_size: None,
+        }
+    }
+
+    /// uploads the file in blocks of at most `block_size` bytes
+    pub fn block_size(mut self, block_size: u32) -> Self {
+        self.block_size = Some(block_size);
+        self
+    }
+}
+
+/**
+Selects which version of an image column is downloaded
+*/
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ImageSize {
+    /// The image as it was uploaded (only available if the column stores full images)
+    Full,
+
+    /// The thumbnail of at most 144x144 pixels dataverse generates for every image
+    Thumbnail,
+}
+
+/// A block of a file as it was returned by dataverse
+pub(crate) struct FileBlock {
+    pub data: Vec<u8>,
+    pub file_size: Option<u64>,
+    pub file_name: Option<String>,
+    pub partial: bool,
+}
+
+type BlockFuture<'a> = Pin<Box<dyn Future<Output = Result<FileBlock>> + Send + 'a>>;
+
+/**
+The content of a file or image column that is downloaded block by block
+
+Implements `Stream` with one item per block and `AsyncRead`, so it can be passed to
+`tokio::io::copy(...)` or directly to `Client::upload_file(...)`. The next block is only
+requested after the current block was consumed
+*/
+pub struct FileDownload<'a> {
+    fetch_block: Box<dyn Fn(u64) -> BlockFuture<'a> + Send + 'a>,
+    file_name: Option<String>,
+    size: u64,
+    offset: u64,
+    current: Option<Vec<u8>>,
+    pending: Option<BlockFuture<'a>>,
+    read_buffer: Vec<u8>,
+    read_position: usize,
+}
+
+impl<'a> FileDownload<'a> {
+    pub(crate) async fn start<A: Authenticate + Send + Sync>(client: &'a Client<'_, A>, url: String, block_size: u32) -> Result<FileDownload<'a>> {
+        let block_size = u64::from(block_size.max(1));
+        let fetch_block: Box<dyn Fn(u64) -> BlockFuture<'a> + Send + 'a> = Box::new(move |offset| {
+            let url = url.clone();
+            Box::pin(async move { client.fetch_file_block(&url, offset, offset + block_size - 1).await })
+        });
+
+        let first = fetch_block(0).await?;
+        let size = match (first.partial, first.file_size) {
+            (true, Some(size)) => size,
+            _ => first.data.len() as u64,
+        };
+
+        Ok(Self {
+            fetch_block,
+            file_name: first.file_name,
+            size,
+            offset: first.data.len() as u64,
+            current: Some(first.data),
+            pending: None,
+            read_buffer: Vec::new(),
+            read_position: 0,
+        })
+    }
+
+    /// returns the name of the file as it was uploaded, if dataverse provided it
+    pub fn file_name(&self) -> Option<&str> {
+        self.file_name.as_deref()
+    }
+
+    /// returns the size of the whole file in bytes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// reads all remaining blocks into memory
+    pub async fn into_bytes(self) -> Result<Vec<u8>> {
+        use futures::TryStreamExt;
+
+        let mut content = Vec::with_capacity(self.size as usize);
+        let mut blocks = self;
+        while let Some(block) = blocks.try_next().await? {
+            content.extend_from_slice(&block);
+        }
+
+        Ok(content)
+    }
+}
+
+impl<'a> Stream for FileDownload<'a> {
+    type Item = Result<Vec<u8>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        if let Some(block) = this.current.take() {
+            return Poll::Ready(Some(Ok(block)));
+        }
+
+        if this.offset >= this.size {
+            return Poll::Ready(None);
+        }
+
+        let pending = match this.pending.as_mut() {
+            Some(pending) => pending,
+            None => this.pending.insert((this.fetch_block)(this.offset)),
+        };
+
+        match pending.as_mut().poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(block) => {
+                this.pending = None;
+
+                match block {
+                    Ok(block) if block.data.is_empty() => {
+                        this.offset = this.size;
+                        Poll::Ready(None)
+                    }
+                    Ok(block) => {
+                        this.offset += block.data.len() as u64;
+                        Poll::Ready(Some(Ok(block.data)))
+                    }
+                    Err(error) => {
+                        this.offset = this.size;
+                        Poll::Ready(Some(Err(error)))
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'a> AsyncRead for FileDownload<'a> {
+    // `io::Error::other(...)` is not available in the minimum supported rust version
+    #[allow(clippy::io_other_error)]
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        while this.read_position >= this.read_buffer.len() {
+            match Pin::new(&mut *this).poll_next(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(None) => return Poll::Ready(Ok(())),
+                Poll::Ready(Some(Err(error))) => return Poll::Ready(Err(io::Error::new(io::ErrorKind::Other, error))),
+                Poll::Ready(Some(Ok(block))) => {
+                    this.read_buffer = block;
+                    this.read_position = 0;
+                }
+            }
+        }
+
+        let available = &this.read_buffer[this.read_position..];
+        let count = available.len().min(buf.remaining());
+        buf.put_slice(&available[..count]);
+        this.read_position += count;
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::TryStreamExt;
+    use tokio::io::AsyncReadExt;
+    use uuid::Uuid;
+
+    use crate::{
+        client::Client,
+        file::{FileUpload, ImageSize},
+        reference::ReferenceStruct,
+        retry::RetryPolicy,
+        testing::{CannedResponse, StaticAuth, TestServer},
+    };
+
+    fn test_client(url: &str) -> Client<'_, StaticAuth> {
+        Client::new(url, reqwest::Client::new(), StaticAuth).with_retry_policy(RetryPolicy::none())
+    }
+
+    #[tokio::test]
+    async fn uploads_in_blocks() {
+        let server = TestServer::start(vec![
+            CannedResponse::new(200)
+                .header("Location", "/api/data/v9.2/accounts(00000000-0000-0000-0000-000000000000)/cr_document?sessiontoken=abc")
+                .header("x-ms-chunk-size", "4194304"),
+            CannedResponse::new(206),
+            CannedResponse::new(206),
+            CannedResponse::new(204),
+        ])
+        .await;
+        let client = test_client(&server.url);
+        let account = ReferenceStruct::new("accounts", Uuid::nil());
+
+        let upload = FileUpload::new("my report.txt", 10).block_size(4);
+        client.upload_file(&account, "cr_document", &upload, &b"0123456789"[..]).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 4);
+        assert_eq!(requests[0].path, "/api/data/v9.2/accounts(00000000-0000-0000-0000-000000000000)/cr_document?x-ms-file-name=my%20report.txt");
+        assert_eq!(requests[0].header("x-ms-transfer-mode"), Some("chunked"));
+        assert_eq!(requests[1].path, "/api/data/v9.2/accounts(00000000-0000-0000-0000-000000000000)/cr_document?sessiontoken=abc");
+        assert_eq!(requests[1].header("Content-Range"), Some("bytes 0-3/10"));
+        assert_eq!(requests[3].header("Content-Range"), Some("bytes 8-9/10"));
+        assert_eq!(requests[3].body_text(), "89");
+    }
+
+    #[tokio::test]
+    async fn rejects_a_size_mismatch_before_the_last_block() {
+        let server = TestServer::start(vec![
+            CannedResponse::new(200).header("Location", "/upload?sessiontoken=abc"),
+            CannedResponse::new(206),
+            CannedResponse::new(200).header("Location", "/upload?sessiontoken=def"),
+            CannedResponse::new(206),
+        ])
+        .await;
+        let client = test_client(&server.url);
+        let account = ReferenceStruct::new("accounts", Uuid::nil());
+
+        let smaller = FileUpload::new("report.txt", 20).block_size(8);
+        let error = client.upload_file(&account, "cr_document", &smaller, &b"0123456789"[..]).await.unwrap_err();
+        assert!(error.message.contains("has 10 bytes"));
+
+        let larger = FileUpload::new("report.txt", 6).block_size(4);
+        let error = client.upload_file(&account, "cr_document", &larger, &b"0123456789"[..]).await.unwrap_err();
+        assert!(error.message.contains("larger than the announced size"));
+
+        let empty = FileUpload::new("report.txt", 0);
+        assert!(client.upload_file(&account, "cr_document", &empty, &b""[..]).await.is_err());
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 4);
+        assert_eq!(requests[1].header("Content-Range"), Some("bytes 0-7/20"));
+        assert_eq!(requests[3].header("Content-Range"), Some("bytes 0-3/6"));
+    }
+
+    #[tokio::test]
+    async fn downloads_with_range_requests() {
+        let block = |data: &str, range: &str| {
+            CannedResponse::new(206)
+                .header("x-ms-file-name", "report.txt")
+                .header("Content-Range", range)
+                .body(data.as_bytes().to_vec(), "application/octet-stream")
+        };
+        let server = TestServer::start(vec![
+            block("0123", "bytes 0-3/10"),
+            block("4567", "bytes 4-7/10"),
+            block("89", "bytes 8-9/10"),
+            block("0123", "bytes 0-3/10"),
+            block("4567", "bytes 4-7/10"),
+            block("89", "bytes 8-9/10"),
+        ])
+        .await;
+        let client = test_client(&server.url);
+        let account = ReferenceStruct::new("accounts", Uuid::nil());
+
+        let download = client.download_file(&account, "cr_document", 4).await.unwrap();
+        assert_eq!(download.file_name(), Some("report.txt"));
+        assert_eq!(download.size(), 10);
+        let blocks: Vec<Vec<u8>> = download.try_collect().await.unwrap();
+        assert_eq!(blocks, vec![b"0123".to_vec(), b"4567".to_vec(), b"89".to_vec()]);
+
+        let mut content = String::new();
+        let mut download = client.download_image(&account, "entityimage", ImageSize::Full, 4).await.unwrap();
+        download.read_to_string(&mut content).await.unwrap();
+        assert_eq!(content, "0123456789");
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/api/data/v9.2/accounts(00000000-0000-0000-0000-000000000000)/cr_document/$value");
+        assert_eq!(requests[2].header("Range"), Some("bytes=8-11"));
+        assert_eq!(requests[3].path, "/api/data/v9.2/accounts(00000000-0000-0000-0000-000000000000)/entityimage/$value?size=full");
+    }
+
+    #[tokio::test]
+    async fn downloads_thumbnails_without_ranges() {
+        let server = TestServer::start(vec![CannedResponse::new(200).body(vec![1, 2, 3], "image/jpeg")]).await;
+        let client = test_client(&server.url);
+        let account = ReferenceStruct::new("accounts", Uuid::nil());
+
+        let download = client.download_image(&account, "entityimage", ImageSize::Thumbnail, 4).await.unwrap();
+        assert_eq!(download.size(), 3);
+        assert_eq!(download.into_bytes().await.unwrap(), vec![1, 2, 3]);
+        assert_eq!(server.requests().len(), 1);
+    }
+}
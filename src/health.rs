@@ -0,0 +1,216 @@
+/*!
+Module for validating the configuration of a client before it is used
+
+Clients authenticate lazily, so a wrong organization url or invalid credentials only
+show up with the first real request. `Client::health_check()` acquires a token and calls
+`WhoAmI` right away and explains what went wrong
+
+# Examples
+```rust
+use powerplatform_dataverse_service_client::client::Client;
+
+async fn startup() {
+    let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+    let report = client.health_check().await;
+
+    if !report.is_healthy() {
+        panic!("dataverse is not usable: {}", report.diagnosis);
+    }
+}
+```
+*/
+
+use std::{error::Error, fmt::Display, time::Duration};
+
+use reqwest::StatusCode;
+
+use crate::{
+    action::WhoAmIResponse,
+    error::{DataverseError, ErrorKind},
+};
+
+/**
+The result of `Client::health_check()`
+
+The durations are only present for the steps that were executed
+*/
+#[derive(Clone, Debug)]
+pub struct HealthReport {
+    /// how long the acquisition of the token took
+    pub token_acquisition: Option<Duration>,
+    /// how long the `WhoAmI` call took
+    pub request: Option<Duration>,
+    /// the identity of the application user, if the `WhoAmI` call succeeded
+    pub identity: Option<WhoAmIResponse>,
+    pub diagnosis: Diagnosis,
+}
+
+impl HealthReport {
+    /// Indicates that the client is able to authenticate and to call dataverse
+    pub fn is_healthy(&self) -> bool {
+        matches!(self.diagnosis, Diagnosis::Healthy)
+    }
+}
+
+/**
+Explains why a client is not able to work with dataverse
+*/
+#[derive(Clone, Debug)]
+pub enum Diagnosis {
+    /// A token was acquired and dataverse answered the call
+    Healthy,
+
+    /// The organization url is malformed, can't be resolved or does not point to a dataverse environment
+    InvalidUrl(DataverseError),
+
+    /// No token could be acquired, usually because of a wrong tenant, client id or secret
+    InvalidCredentials(DataverseError),
+
+    /// A token was acquired but dataverse rejected it, usually because no application user
+    /// exists for the client id or it has no security role
+    MissingApplicationUser(DataverseError),
+
+    /// Dataverse could not be reached or answered with a server error
+    Unavailable(DataverseError),
+}
+
+impl Diagnosis {
+    /**
+    derives the diagnosis from the error of the token acquisition
+
+    The scope of the token is derived from the organization url, so Entra ID rejects a
+    mistyped organization host with `AADSTS500011` (`invalid_resource`) before dataverse
+    is called at all. Every other rejection, e.g. `AADSTS7000215` for an invalid secret or
+    `AADSTS700016` for an unknown client id, is caused by the credentials
+    */
+    pub(crate) fn from_token_error(error: DataverseError) -> Self {
+        match error.kind {
+            ErrorKind::Transport => Diagnosis::Unavailable(error),
+            _ if is_unknown_resource(&error) => Diagnosis::InvalidUrl(error),
+            _ => Diagnosis::InvalidCredentials(error),
+        }
+    }
+
+    /// derives the diagnosis from the error of the `WhoAmI` call
+    pub(crate) fn from_request_error(error: DataverseError) -> Self {
+        match error.status {
+            Some(StatusCode::UNAUTHORIZED) | Some(StatusCode::FORBIDDEN) => Diagnosis::MissingApplicationUser(error),
+            Some(StatusCode::NOT_FOUND) => Diagnosis::InvalidUrl(error),
+            Some(_) => Diagnosis::Unavailable(error),
+            None if is_invalid_target(&error) => Diagnosis::InvalidUrl(error),
+            None if error.kind == ErrorKind::Authentication => Diagnosis::InvalidCredentials(error),
+            None => Diagnosis::Unavailable(error),
+        }
+    }
+}
+
+impl Display for Diagnosis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Diagnosis::Healthy => f.write_str("healthy"),
+            Diagnosis::InvalidUrl(error) => f.write_fmt(format_args!("the organization url is invalid or unreachable: {}", error)),
+            Diagnosis::InvalidCredentials(error) => f.write_fmt(format_args!("no token could be acquired, check the tenant, client id and secret: {}", error)),
+            Diagnosis::MissingApplicationUser(error) => f.write_fmt(format_args!("the token was rejected, check that an application user with a security role exists: {}", error)),
+            Diagnosis::Unavailable(error) => f.write_fmt(format_args!("dataverse is unavailable: {}", error)),
+        }
+    }
+}
+
+/// checks if Entra ID found no resource for the requested token scope
+fn is_unknown_resource(error: &DataverseError) -> bool {
+    error.message.contains("AADSTS500011") || error.message.contains("invalid_resource")
+}
+
+/// checks if the request could not be built or the host could not be connected
+fn is_invalid_target(error: &DataverseError) -> bool {
+    match error.source().and_then(|source| source.downcast_ref::<reqwest::Error>()) {
+        Some(error) => error.is_builder() || error.is_connect(),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::StatusCode;
+    use uuid::Uuid;
+
+    use crate::{
+        client::Client,
+        error::{DataverseError, ErrorKind},
+        health::Diagnosis,
+        retry::RetryPolicy,
+        testing::{CannedResponse, StaticAuth, TestServer},
+    };
+
+    #[tokio::test]
+    async fn reports_a_healthy_client() {
+        let server = TestServer::start(vec![
+            CannedResponse::new(200).json(r#"{"UserId":"00000000-0000-0000-0000-000000000001","BusinessUnitId":"00000000-0000-0000-0000-000000000002","OrganizationId":"00000000-0000-0000-0000-000000000003"}"#),
+        ])
+        .await;
+        let client = Client::new(server.url.clone(), reqwest::Client::new(), StaticAuth).with_retry_policy(RetryPolicy::none());
+
+        let report = client.health_check().await;
+        assert!(report.is_healthy());
+        assert!(report.token_acquisition.is_some());
+        assert!(report.request.is_some());
+        assert_eq!(report.identity.unwrap().user_id, Uuid::from_u128(1));
+        assert_eq!(server.requests()[0].path, "/api/data/v9.2/WhoAmI()");
+    }
+
+    #[tokio::test]
+    async fn diagnoses_failures() {
+        let server = TestServer::start(vec![
+            CannedResponse::new(403).json(r#"{"error":{"code":"0x80072560","message":"The user is not a member of the organization."}}"#),
+            CannedResponse::new(404),
+            CannedResponse::new(503),
+        ])
+        .await;
+        let client = Client::new(server.url.clone(), reqwest::Client::new(), StaticAuth).with_retry_policy(RetryPolicy::none());
+
+        assert!(matches!(client.health_check().await.diagnosis, Diagnosis::MissingApplicationUser(_)));
+        assert!(matches!(client.health_check().await.diagnosis, Diagnosis::InvalidUrl(_)));
+        assert!(matches!(client.health_check().await.diagnosis, Diagnosis::Unavailable(_)));
+
+        let report = Client::new_dummy().health_check().await;
+        assert!(matches!(report.diagnosis, Diagnosis::InvalidCredentials(_)));
+        assert!(report.request.is_none());
+
+        let unreachable = Client::new("http://127.0.0.1:1/", reqwest::Client::new(), StaticAuth).with_retry_policy(RetryPolicy::none());
+        assert!(matches!(unreachable.health_check().await.diagnosis, Diagnosis::InvalidUrl(_)));
+    }
+
+    fn token_error(status: StatusCode, body: &str) -> DataverseError {
+        DataverseError::from_body(status, None, body.as_bytes()).into_kind(ErrorKind::Authentication)
+    }
+
+    #[test]
+    fn diagnoses_an_unknown_resource_as_invalid_url() {
+        let error = token_error(
+            StatusCode::BAD_REQUEST,
+            r#"{"error":"invalid_resource","error_description":"AADSTS500011: The resource principal named https://instnace.crm.dynamics.com/ was not found in the tenant.","error_codes":[500011]}"#,
+        );
+
+        assert!(matches!(Diagnosis::from_token_error(error), Diagnosis::InvalidUrl(_)));
+    }
+
+    #[test]
+    fn diagnoses_an_invalid_secret_as_invalid_credentials() {
+        let error = token_error(
+            StatusCode::UNAUTHORIZED,
+            r#"{"error":"invalid_client","error_description":"AADSTS7000215: Invalid client secret provided.","error_codes":[7000215]}"#,
+        );
+
+        assert!(matches!(Diagnosis::from_token_error(error), Diagnosis::InvalidCredentials(_)));
+    }
+
+    #[test]
+    fn diagnoses_an_unknown_client_as_invalid_credentials() {
+        let error = token_error(
+            StatusCode::BAD_REQUEST,
+            r#"{"error":"unauthorized_client","error_description":"AADSTS700016: Application with identifier '<clientid>' was not found in the directory.","error_codes":[700016]}"#,
+        );
+
+        assert!(matches!(Diagnosis::from_token_error(error), Diagnosis::InvalidCredentials(_)));
+    }
+}
@@ -171,10 +171,24 @@ impl Reference for Contact {
 pub mod action;
 pub mod auth;
 pub mod batch;
+pub mod builder;
+pub mod changes;
 pub mod client;
 pub mod entity;
 pub mod error;
+pub mod file;
+pub mod health;
+pub mod metadata;
+pub mod options;
 pub mod query;
 pub mod reference;
 pub mod result;
+pub mod retry;
 pub mod select;
+pub mod stream;
+
+#[cfg(feature = "derive")]
+pub use powerplatform_dataverse_service_client_derive::DataverseEntity;
+
+#[cfg(test)]
+mod testing;
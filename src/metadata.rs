@@ -0,0 +1,580 @@
+/*!
+Module for reading the definitions of tables, columns, relationships and choices
+
+Dataverse describes its schema with the `EntityDefinitions` and `GlobalOptionSetDefinitions`
+endpoints. The structs of this module cover the commonly used properties of these
+definitions. Properties that were not selected with a `MetadataQuery` are `None` or empty
+
+Metadata rarely changes, so the client keeps every response in a `MetadataCache` that is
+shared by all handles of the client. The cache is never invalidated automatically, use
+`Client::metadata_cache()` after changing the schema
+
+# Examples
+```rust
+use powerplatform_dataverse_service_client::{
+    client::Client,
+    metadata::{AttributeDetails, MetadataQuery},
+    query::expand::Expand,
+    result::Result,
+};
+
+async fn test() -> Result<()> {
+    let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+
+    let account = client.entity_metadata(
+        "account",
+        &MetadataQuery::new()
+            .select(&["LogicalName", "EntitySetName", "PrimaryIdAttribute"])
+            .expand(Expand::new("Attributes", &["LogicalName", "AttributeType"])),
+    ).await?;
+    println!("accounts are stored in '{:?}'", account.entity_set_name);
+
+    let picklists = client.attributes(
+        "account",
+        &MetadataQuery::new()
+            .cast("PicklistAttributeMetadata")
+            .select(&["LogicalName"])
+            .expand(Expand::new("OptionSet", &[])),
+    ).await?;
+
+    for attribute in picklists {
+        if let AttributeDetails::Picklist(picklist) = &attribute.details {
+            println!("{:?} has {} options", attribute.logical_name, picklist.option_set.as_ref().map_or(0, |set| set.options.len()));
+        }
+    }
+
+    Ok(())
+}
+```
+*/
+
+use std::{collections::HashMap, fmt::Display, sync::Mutex};
+
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    query::{
+        attribute::Attribute,
+        expand::{Expand, ExpandList},
+        filter::Filter,
+    },
+    reference::KeyLiteral,
+};
+
+/**
+Represents the query options for the metadata endpoints
+
+Metadata properties and navigation properties use pascal case, e.g. `LogicalName`
+or `Attributes`. The `$filter` option is only supported by the endpoints that return
+collections and only with the operators `eq`, `ne`, `and`, `or` and `not`
+
+# Examples
+```rust
+use powerplatform_dataverse_service_client::{
+    metadata::MetadataQuery,
+    query::{attribute::Attribute, filter::Filter},
+};
+
+let query = MetadataQuery::new()
+    .select(&["LogicalName", "EntitySetName"])
+    .filter(Filter::Equal("IsCustomEntity", Attribute::Boolean(true)));
+
+assert_eq!(query.to_string(), "?$select=LogicalName,EntitySetName&$filter=IsCustomEntity eq true");
+```
+*/
+#[derive(Clone, Debug, Default)]
+pub struct MetadataQuery {
+    pub columns: &'static [&'static str],
+    pub expand: Vec<Expand>,
+    pub filter: Option<Filter>,
+    pub cast: Option<&'static str>,
+}
+
+impl MetadataQuery {
+    /// Creates a query that returns all properties and expands nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// returns only the given properties
+    pub fn select(mut self, columns: &'static [&'static str]) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    /// expands the given navigation property, e.g. `Attributes` or `OptionSet`
+    pub fn expand(mut self, expand: Expand) -> Self {
+        self.expand.push(expand);
+        self
+    }
+
+    /// returns only the definitions that match the given filter
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /**
+    casts the returned attributes to the given type, e.g. `PicklistAttributeMetadata`
+
+    Type-specific properties like `OptionSet` can only be selected and expanded after a cast
+    */
+    pub fn cast(mut self, attribute_type: &'static str) -> Self {
+        self.cast = Some(attribute_type);
+        self
+    }
+}
+
+impl Display for MetadataQuery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut options = Vec::new();
+
+        if !self.columns.is_empty() {
+            options.push(format!("$select={}", self.columns.join(",")));
+        }
+
+        if let Some(filter) = &self.filter {
+            options.push(format!("$filter={}", filter));
+        }
+
+        if !self.expand.is_empty() {
+            options.push(format!("$expand={}", ExpandList(&self.expand)));
+        }
+
+        if let Some(attribute_type) = self.cast {
+            f.write_fmt(format_args!("/Microsoft.Dynamics.CRM.{}", attribute_type))?;
+        }
+
+        if !options.is_empty() {
+            f.write_fmt(format_args!("?{}", options.join("&")))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// the path of the definition of the given table
+pub(crate) fn entity_path(logical_name: &str) -> String {
+    format!("EntityDefinitions(LogicalName={})", KeyLiteral(&Attribute::String(logical_name.to_string())))
+}
+
+/// the path of the definition of the given global choice
+pub(crate) fn option_set_path(name: &str) -> String {
+    format!("GlobalOptionSetDefinitions(Name={})", KeyLiteral(&Attribute::String(name.to_string())))
+}
+
+/**
+Keeps the responses of the metadata endpoints by their path
+
+The cache is shared by all handles of a client and has to be invalidated explicitly
+after the schema was changed, e.g. after importing a solution
+*/
+#[derive(Debug, Default)]
+pub struct MetadataCache {
+    entries: Mutex<HashMap<String, Value>>,
+}
+
+impl MetadataCache {
+    /// removes all cached definitions
+    pub fn invalidate_all(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// removes the cached definitions of the given table, its attributes and all lists of tables
+    pub fn invalidate_entity(&self, logical_name: &str) {
+        let path = entity_path(logical_name);
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|key, _| !key.starts_with(&path) && !key.starts_with("EntityDefinitions?") && key != "EntityDefinitions");
+    }
+
+    /// removes the cached definition of the given global choice
+    pub fn invalidate_global_option_set(&self, name: &str) {
+        let path = option_set_path(name);
+        self.entries.lock().unwrap().retain(|key, _| !key.starts_with(&path));
+    }
+
+    /// returns the number of cached responses
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Indicates that no responses are cached
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn get(&self, path: &str) -> Option<Value> {
+        self.entries.lock().unwrap().get(path).cloned()
+    }
+
+    pub(crate) fn insert(&self, path: String, value: Value) {
+        self.entries.lock().unwrap().insert(path, value);
+    }
+}
+
+/**
+A label in the languages of the environment
+*/
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Label {
+    #[serde(default)]
+    pub localized_labels: Vec<LocalizedLabel>,
+    pub user_localized_label: Option<LocalizedLabel>,
+}
+
+impl Label {
+    /// returns the label in the language of the calling user or the first available label
+    pub fn text(&self) -> Option<&str> {
+        self.user_localized_label
+            .as_ref()
+            .or_else(|| self.localized_labels.first())
+            .map(|label| label.label.as_str())
+    }
+}
+
+/// A label in a single language
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LocalizedLabel {
+    pub label: String,
+    pub language_code: i32,
+}
+
+/**
+The definition of a table (`EntityMetadata`)
+
+`attributes` and the relationships are only filled if they were expanded
+*/
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct EntityMetadata {
+    pub metadata_id: Uuid,
+    pub logical_name: Option<String>,
+    pub schema_name: Option<String>,
+    pub entity_set_name: Option<String>,
+    pub logical_collection_name: Option<String>,
+    pub primary_id_attribute: Option<String>,
+    pub primary_name_attribute: Option<String>,
+    pub primary_image_attribute: Option<String>,
+    pub object_type_code: Option<i32>,
+    pub ownership_type: Option<String>,
+    pub display_name: Option<Label>,
+    pub display_collection_name: Option<Label>,
+    pub description: Option<Label>,
+    pub is_custom_entity: Option<bool>,
+    pub is_activity: Option<bool>,
+    pub is_intersect: Option<bool>,
+    pub change_tracking_enabled: Option<bool>,
+    pub attributes: Vec<AttributeMetadata>,
+    pub one_to_many_relationships: Vec<OneToManyRelationshipMetadata>,
+    pub many_to_one_relationships: Vec<OneToManyRelationshipMetadata>,
+    pub many_to_many_relationships: Vec<ManyToManyRelationshipMetadata>,
+}
+
+/**
+The definition of a column (`AttributeMetadata`)
+
+The properties all columns share are fields of this struct, the properties of the
+specific column type are part of `details`
+*/
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct AttributeMetadata {
+    pub metadata_id: Uuid,
+    pub logical_name: Option<String>,
+    pub schema_name: Option<String>,
+    pub entity_logical_name: Option<String>,
+    pub attribute_type: Option<String>,
+    pub attribute_of: Option<String>,
+    pub display_name: Option<Label>,
+    pub description: Option<Label>,
+    pub is_primary_id: Option<bool>,
+    pub is_primary_name: Option<bool>,
+    pub is_custom_attribute: Option<bool>,
+    pub is_valid_for_create: Option<bool>,
+    pub is_valid_for_read: Option<bool>,
+    pub is_valid_for_update: Option<bool>,
+    #[serde(flatten)]
+    pub details: AttributeDetails,
+}
+
+/**
+The type-specific properties of a column, selected by its `@odata.type`
+*/
+#[derive(Clone, Debug, PartialEq)]
+pub enum AttributeDetails {
+    String(StringAttributeMetadata),
+    Memo(StringAttributeMetadata),
+    Integer(IntegerAttributeMetadata),
+    BigInt(IntegerAttributeMetadata),
+    Decimal(NumberAttributeMetadata),
+    Double(NumberAttributeMetadata),
+    Money(NumberAttributeMetadata),
+    DateTime(DateTimeAttributeMetadata),
+    Boolean(BooleanAttributeMetadata),
+    Picklist(PicklistAttributeMetadata),
+    MultiSelectPicklist(PicklistAttributeMetadata),
+    State(PicklistAttributeMetadata),
+    Status(PicklistAttributeMetadata),
+    Lookup(LookupAttributeMetadata),
+    File(FileAttributeMetadata),
+    Image(FileAttributeMetadata),
+
+    /// Any other type of column with the name of its type, e.g. `UniqueIdentifierAttributeMetadata`
+    Other(String),
+}
+
+impl Default for AttributeDetails {
+    fn default() -> Self {
+        AttributeDetails::Other(String::new())
+    }
+}
+
+impl<'de> Deserialize<'de> for AttributeDetails {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de> {
+        use serde::de::Error;
+
+        let value = Value::deserialize(deserializer)?;
+        let odata_type = value
+            .get("@odata.type")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .trim_start_matches("#Microsoft.Dynamics.CRM.")
+            .to_string();
+
+        let details = match odata_type.as_str() {
+            "StringAttributeMetadata" => AttributeDetails::String(serde_json::from_value(value).map_err(D::Error::custom)?),
+            "MemoAttributeMetadata" => AttributeDetails::Memo(serde_json::from_value(value).map_err(D::Error::custom)?),
+            "IntegerAttributeMetadata" => AttributeDetails::Integer(serde_json::from_value(value).map_err(D::Error::custom)?),
+            "BigIntAttributeMetadata" => AttributeDetails::BigInt(serde_json::from_value(value).map_err(D::Error::custom)?),
+            "DecimalAttributeMetadata" => AttributeDetails::Decimal(serde_json::from_value(value).map_err(D::Error::custom)?),
+            "DoubleAttributeMetadata" => AttributeDetails::Double(serde_json::from_value(value).map_err(D::Error::custom)?),
+            "MoneyAttributeMetadata" => AttributeDetails::Money(serde_json::from_value(value).map_err(D::Error::custom)?),
+            "DateTimeAttributeMetadata" => AttributeDetails::DateTime(serde_json::from_value(value).map_err(D::Error::custom)?),
+            "BooleanAttributeMetadata" => AttributeDetails::Boolean(serde_json::from_value(value).map_err(D::Error::custom)?),
+            "PicklistAttributeMetadata" => AttributeDetails::Picklist(serde_json::from_value(value).map_err(D::Error::custom)?),
+            "MultiSelectPicklistAttributeMetadata" => AttributeDetails::MultiSelectPicklist(serde_json::from_value(value).map_err(D::Error::custom)?),
+            "StateAttributeMetadata" => AttributeDetails::State(serde_json::from_value(value).map_err(D::Error::custom)?),
+            "StatusAttributeMetadata" => AttributeDetails::Status(serde_json::from_value(value).map_err(D::Error::custom)?),
+            "LookupAttributeMetadata" => AttributeDetails::Lookup(serde_json::from_value(value).map_err(D::Error::custom)?),
+            "FileAttributeMetadata" => AttributeDetails::File(serde_json::from_value(value).map_err(D::Error::custom)?),
+            "ImageAttributeMetadata" => AttributeDetails::Image(serde_json::from_value(value).map_err(D::Error::custom)?),
+            _ => AttributeDetails::Other(odata_type),
+        };
+
+        Ok(details)
+    }
+}
+
+/// The properties of text columns (`StringAttributeMetadata` and `MemoAttributeMetadata`)
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct StringAttributeMetadata {
+    pub max_length: Option<i32>,
+    pub format: Option<String>,
+}
+
+/// The properties of whole number columns (`IntegerAttributeMetadata` and `BigIntAttributeMetadata`)
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct IntegerAttributeMetadata {
+    pub min_value: Option<i64>,
+    pub max_value: Option<i64>,
+    pub format: Option<String>,
+}
+
+/// The properties of decimal, floating point and currency columns
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct NumberAttributeMetadata {
+    pub precision: Option<i32>,
+    pub min_value: Option<f64>,
+    pub max_value: Option<f64>,
+}
+
+/// The properties of date and time columns (`DateTimeAttributeMetadata`)
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct DateTimeAttributeMetadata {
+    pub format: Option<String>,
+}
+
+/// The properties of yes/no columns (`BooleanAttributeMetadata`), the `OptionSet` has to be expanded
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct BooleanAttributeMetadata {
+    pub default_value: Option<bool>,
+    pub option_set: Option<OptionSetMetadata>,
+}
+
+/**
+The properties of choice columns (`PicklistAttributeMetadata`, `MultiSelectPicklistAttributeMetadata`,
+`StateAttributeMetadata` and `StatusAttributeMetadata`)
+
+`OptionSet` and `GlobalOptionSet` have to be expanded. Choice columns that use a global
+choice return the same options in both
+*/
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct PicklistAttributeMetadata {
+    pub default_form_value: Option<i32>,
+    pub option_set: Option<OptionSetMetadata>,
+    pub global_option_set: Option<OptionSetMetadata>,
+}
+
+/// The properties of lookup columns (`LookupAttributeMetadata`)
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct LookupAttributeMetadata {
+    /// the logical names of the tables the lookup can reference
+    pub targets: Vec<String>,
+}
+
+/// The properties of file and image columns (`FileAttributeMetadata` and `ImageAttributeMetadata`)
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct FileAttributeMetadata {
+    pub max_size_in_kb: Option<i32>,
+    pub can_store_full_image: Option<bool>,
+}
+
+/**
+The definition of a one-to-many relationship (`OneToManyRelationshipMetadata`)
+
+The same definition describes the relationship from the referencing table, where it is
+listed as a many-to-one relationship
+*/
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct OneToManyRelationshipMetadata {
+    pub metadata_id: Uuid,
+    pub schema_name: Option<String>,
+    pub referenced_entity: Option<String>,
+    pub referenced_attribute: Option<String>,
+    pub referencing_entity: Option<String>,
+    pub referencing_attribute: Option<String>,
+    pub referenced_entity_navigation_property_name: Option<String>,
+    pub referencing_entity_navigation_property_name: Option<String>,
+}
+
+/// The definition of a many-to-many relationship (`ManyToManyRelationshipMetadata`)
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct ManyToManyRelationshipMetadata {
+    pub metadata_id: Uuid,
+    pub schema_name: Option<String>,
+    pub intersect_entity_name: Option<String>,
+    pub entity1_logical_name: Option<String>,
+    pub entity1_intersect_attribute: Option<String>,
+    pub entity1_navigation_property_name: Option<String>,
+    pub entity2_logical_name: Option<String>,
+    pub entity2_intersect_attribute: Option<String>,
+    pub entity2_navigation_property_name: Option<String>,
+}
+
+/**
+The definition of a choice (`OptionSetMetadata` or `BooleanOptionSetMetadata`)
+
+Choices list their values in `options`, yes/no choices in `true_option` and `false_option`
+*/
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct OptionSetMetadata {
+    pub metadata_id: Uuid,
+    pub name: Option<String>,
+    pub is_global: Option<bool>,
+    pub option_set_type: Option<String>,
+    pub display_name: Option<Label>,
+    pub options: Vec<OptionMetadata>,
+    pub true_option: Option<OptionMetadata>,
+    pub false_option: Option<OptionMetadata>,
+}
+
+impl OptionSetMetadata {
+    /// returns the label of the option with the given value
+    pub fn label(&self, value: i32) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|option| option.value == value)
+            .and_then(|option| option.label.as_ref())
+            .and_then(Label::text)
+    }
+
+    /// returns the value of the option with the given label
+    pub fn value(&self, label: &str) -> Option<i32> {
+        self.options
+            .iter()
+            .find(|option| option.label.as_ref().and_then(Label::text) == Some(label))
+            .map(|option| option.value)
+    }
+}
+
+/// A single option of a choice
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct OptionMetadata {
+    pub value: i32,
+    pub label: Option<Label>,
+    pub color: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        metadata::{AttributeDetails, AttributeMetadata, MetadataCache, MetadataQuery},
+        query::expand::Expand,
+    };
+
+    #[test]
+    fn renders_casts_and_options() {
+        let query = MetadataQuery::new()
+            .cast("PicklistAttributeMetadata")
+            .select(&["LogicalName"])
+            .expand(Expand::new("OptionSet", &["Options"]));
+
+        assert_eq!(
+            query.to_string(),
+            "/Microsoft.Dynamics.CRM.PicklistAttributeMetadata?$select=LogicalName&$expand=OptionSet($select=Options)"
+        );
+        assert_eq!(MetadataQuery::new().to_string(), "");
+    }
+
+    #[test]
+    fn reads_type_specific_properties() {
+        let attributes: Vec<AttributeMetadata> = serde_json::from_str(r##"[
+            {"@odata.type":"#Microsoft.Dynamics.CRM.StringAttributeMetadata","MetadataId":"00000000-0000-0000-0000-000000000001","LogicalName":"name","MaxLength":160},
+            {"@odata.type":"#Microsoft.Dynamics.CRM.LookupAttributeMetadata","LogicalName":"parentcustomerid","Targets":["account","contact"]},
+            {"@odata.type":"#Microsoft.Dynamics.CRM.UniqueIdentifierAttributeMetadata","LogicalName":"accountid"},
+            {"LogicalName":"name"}
+        ]"##).unwrap();
+
+        assert_eq!(attributes[0].logical_name.as_deref(), Some("name"));
+        assert!(matches!(&attributes[0].details, AttributeDetails::String(details) if details.max_length == Some(160)));
+        assert!(matches!(&attributes[1].details, AttributeDetails::Lookup(details) if details.targets == ["account", "contact"]));
+        assert_eq!(attributes[2].details, AttributeDetails::Other(String::from("UniqueIdentifierAttributeMetadata")));
+        assert_eq!(attributes[3].details, AttributeDetails::Other(String::new()));
+    }
+
+    #[test]
+    fn invalidates_cached_entries() {
+        let cache = MetadataCache::default();
+        cache.insert(String::from("EntityDefinitions(LogicalName='account')"), serde_json::Value::Null);
+        cache.insert(String::from("EntityDefinitions(LogicalName='account')/Attributes"), serde_json::Value::Null);
+        cache.insert(String::from("EntityDefinitions(LogicalName='contact')"), serde_json::Value::Null);
+        cache.insert(String::from("EntityDefinitions?$select=LogicalName"), serde_json::Value::Null);
+        cache.insert(String::from("GlobalOptionSetDefinitions(Name='budgetstatus')"), serde_json::Value::Null);
+
+        cache.invalidate_entity("account");
+        assert_eq!(cache.len(), 2);
+        cache.invalidate_global_option_set("budgetstatus");
+        assert!(cache.get("EntityDefinitions(LogicalName='contact')").is_some());
+        cache.invalidate_all();
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,194 @@
+/*!
+Module for optional execution settings of requests to Microsoft Dataverse
+
+A `RequestOptions` value controls how Dataverse executes a request, e.g. whether
+custom plugins run or duplicate detection is performed. The options are rendered
+as http headers of the request (and of the parts of a `Batch`)
+
+# Examples
+```rust
+use powerplatform_dataverse_service_client::{
+    client::Client,
+    options::{BypassBusinessLogic, RequestOptions},
+};
+
+let client = Client::new_dummy(); // Please replace this with your preferred authentication method
+let migration = client.using_options(
+    RequestOptions::new()
+        .bypass_business_logic_execution(BypassBusinessLogic::All)
+        .suppress_duplicate_detection(true)
+        .tag("migration-2024"),
+);
+```
+*/
+
+use crate::reference::encode_path;
+
+/**
+Selects which custom business logic dataverse skips for a request
+
+Requires the privilege `prvBypassCustomBusinessLogic` for the calling user
+*/
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BypassBusinessLogic {
+    /// skips synchronous plugins and real-time workflows
+    CustomSync,
+
+    /// skips asynchronous plugins, workflows and flows
+    CustomAsync,
+
+    /// skips both synchronous and asynchronous custom logic
+    All,
+}
+
+impl BypassBusinessLogic {
+    fn as_header_value(&self) -> &'static str {
+        match self {
+            BypassBusinessLogic::CustomSync => "CustomSync",
+            BypassBusinessLogic::CustomAsync => "CustomAsync",
+            BypassBusinessLogic::All => "CustomSync,CustomAsync",
+        }
+    }
+}
+
+/**
+Optional execution settings for requests to dataverse
+
+Options that are not set are not sent, so dataverse uses its defaults for them.
+Use `Client::using_options(...)` to apply them to the requests of a client and
+`Batch::set_options(...)` to apply them to individual operations of a batch
+
+- `bypass_custom_plugin_execution` sends `MSCRM.BypassCustomPluginExecution`
+- `bypass_business_logic_execution` sends `MSCRM.BypassBusinessLogicExecution`
+- `suppress_duplicate_detection` sends `MSCRM.SuppressDuplicateDetection`
+- `solution_unique_name` sends `MSCRM.SolutionUniqueName`
+- `tag` is passed to plugins as the shared variable `tag`. Dataverse only accepts it
+  as the query parameter `tag`, so it is appended to the request url instead of a header
+*/
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RequestOptions {
+    pub bypass_custom_plugin_execution: Option<bool>,
+    pub bypass_business_logic_execution: Option<BypassBusinessLogic>,
+    pub suppress_duplicate_detection: Option<bool>,
+    pub solution_unique_name: Option<String>,
+    pub tag: Option<String>,
+}
+
+impl RequestOptions {
+    /// Creates options that leave every setting at the dataverse default
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// enables or disables the custom synchronous plugins for the request
+    pub fn bypass_custom_plugin_execution(mut self, bypass: bool) -> Self {
+        self.bypass_custom_plugin_execution = Some(bypass);
+        self
+    }
+
+    /// skips the given custom business logic for the request
+    pub fn bypass_business_logic_execution(mut self, bypass: BypassBusinessLogic) -> Self {
+        self.bypass_business_logic_execution = Some(bypass);
+        self
+    }
+
+    /// enables or disables the duplicate detection rules for the request
+    pub fn suppress_duplicate_detection(mut self, suppress: bool) -> Self {
+        self.suppress_duplicate_detection = Some(suppress);
+        self
+    }
+
+    /// adds the created or updated solution components to the given unmanaged solution
+    pub fn solution_unique_name(mut self, solution_unique_name: impl Into<String>) -> Self {
+        self.solution_unique_name = Some(solution_unique_name.into());
+        self
+    }
+
+    /// passes the given value to plugins as the shared variable `tag`
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tag = Some(tag.into());
+        self
+    }
+
+    /// returns the http headers these options are rendered as
+    pub fn headers(&self) -> Vec<(&'static str, String)> {
+        let mut headers = Vec::new();
+
+        if let Some(bypass) = self.bypass_custom_plugin_execution {
+            headers.push(("MSCRM.BypassCustomPluginExecution", bypass.to_string()));
+        }
+
+        if let Some(bypass) = self.bypass_business_logic_execution {
+            headers.push(("MSCRM.BypassBusinessLogicExecution", String::from(bypass.as_header_value())));
+        }
+
+        if let Some(suppress) = self.suppress_duplicate_detection {
+            headers.push(("MSCRM.SuppressDuplicateDetection", suppress.to_string()));
+        }
+
+        if let Some(solution_unique_name) = &self.solution_unique_name {
+            headers.push(("MSCRM.SolutionUniqueName", solution_unique_name.clone()));
+        }
+
+        headers
+    }
+
+    /**
+    appends the query parameters of these options to the given url or path
+
+    Urls provided by dataverse (e.g. `@odata.nextLink`) may already echo the tag,
+    so it is not appended to a url that already has a `tag` parameter
+    */
+    pub(crate) fn apply_to_url(&self, url: &mut String) {
+        let has_tag = url
+            .split_once('?')
+            .into_iter()
+            .flat_map(|(_, query)| query.split('&'))
+            .any(|parameter| parameter.starts_with("tag="));
+
+        if let (Some(tag), false) = (&self.tag, has_tag) {
+            url.push(if url.contains('?') { '&' } else { '?' });
+            url.push_str("tag=");
+            url.push_str(&encode_path(tag));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::options::{BypassBusinessLogic, RequestOptions};
+
+    #[test]
+    fn renders_only_the_set_options() {
+        assert!(RequestOptions::new().headers().is_empty());
+
+        let options = RequestOptions::new()
+            .bypass_custom_plugin_execution(true)
+            .bypass_business_logic_execution(BypassBusinessLogic::All)
+            .suppress_duplicate_detection(false)
+            .solution_unique_name("Migration")
+            .tag("run 1");
+
+        assert_eq!(
+            options.headers(),
+            vec![
+                ("MSCRM.BypassCustomPluginExecution", String::from("true")),
+                ("MSCRM.BypassBusinessLogicExecution", String::from("CustomSync,CustomAsync")),
+                ("MSCRM.SuppressDuplicateDetection", String::from("false")),
+                ("MSCRM.SolutionUniqueName", String::from("Migration")),
+            ]
+        );
+
+        let mut url = String::from("contacts?$select=fullname");
+        options.apply_to_url(&mut url);
+        assert_eq!(url, "contacts?$select=fullname&tag=run%201");
+
+        options.apply_to_url(&mut url);
+        assert_eq!(url, "contacts?$select=fullname&tag=run%201");
+
+        assert_eq!(
+            RequestOptions::new().bypass_custom_plugin_execution(false).headers(),
+            vec![("MSCRM.BypassCustomPluginExecution", String::from("false"))]
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use std::fmt::Display;
+
+use super::filter::Filter;
+
+/**
+Represents a transformation of the `$apply` option used in `Query` structures
+
+Transformations are applied in the order they were added to the query, so a
+`Filter` transformation before a `GroupBy` restricts the records that are grouped.
+Lookup columns are grouped by their value property, e.g. `_ownerid_value`
+
+# Examples
+```rust
+use powerplatform_dataverse_service_client::query::{
+    apply::{Aggregate, Transformation},
+    attribute::Attribute,
+    filter::Filter,
+    Query,
+};
+
+let query = Query::new("opportunities")
+    .apply(Transformation::Filter(Filter::Equal("statecode", Attribute::Integer(0))))
+    .apply(Transformation::GroupBy(
+        vec!["_ownerid_value", "statuscode"],
+        vec![Aggregate::Sum("estimatedvalue", "total"), Aggregate::Count("count")],
+    ));
+
+assert_eq!(
+    query.to_string(),
+    "opportunities?$apply=filter(statecode eq 0)/groupby((_ownerid_value,statuscode),aggregate(estimatedvalue with sum as total,$count as count))"
+);
+```
+*/
+#[derive(Clone, Debug)]
+pub enum Transformation {
+    /// Restricts the records to those that match the predicate defined in the given filter
+    Filter(Filter),
+
+    /// Groups the records by the given columns and aggregates the records of each group
+    GroupBy(Vec<&'static str>, Vec<Aggregate>),
+
+    /// Aggregates all records into a single result
+    Aggregate(Vec<Aggregate>),
+}
+
+impl Display for Transformation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Transformation::Filter(filter) => f.write_fmt(format_args!("filter({})", filter)),
+            Transformation::GroupBy(columns, aggregates) => {
+                f.write_fmt(format_args!("groupby(({})", columns.join(",")))?;
+
+                if !aggregates.is_empty() {
+                    f.write_fmt(format_args!(",aggregate({})", AggregateList(aggregates)))?;
+                }
+
+                f.write_str(")")
+            }
+            Transformation::Aggregate(aggregates) => f.write_fmt(format_args!("aggregate({})", AggregateList(aggregates))),
+        }
+    }
+}
+
+/**
+Represents an aggregation of a column used in `Transformation` structures
+
+Each aggregation is returned under the given alias, which is the name of
+the field in the deserialized result
+*/
+#[derive(Clone, Debug)]
+pub enum Aggregate {
+    /// Sums up the values of the column (column, alias)
+    Sum(&'static str, &'static str),
+
+    /// Averages the values of the column (column, alias)
+    Average(&'static str, &'static str),
+
+    /// Determines the smallest value of the column (column, alias)
+    Min(&'static str, &'static str),
+
+    /// Determines the largest value of the column (column, alias)
+    Max(&'static str, &'static str),
+
+    /// Counts the distinct values of the column (column, alias)
+    CountDistinct(&'static str, &'static str),
+
+    /// Counts the records (alias)
+    Count(&'static str),
+}
+
+impl Display for Aggregate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Aggregate::*;
+        match self {
+            Sum(column, alias) => f.write_fmt(format_args!("{} with sum as {}", column, alias)),
+            Average(column, alias) => f.write_fmt(format_args!("{} with average as {}", column, alias)),
+            Min(column, alias) => f.write_fmt(format_args!("{} with min as {}", column, alias)),
+            Max(column, alias) => f.write_fmt(format_args!("{} with max as {}", column, alias)),
+            CountDistinct(column, alias) => f.write_fmt(format_args!("{} with countdistinct as {}", column, alias)),
+            Count(alias) => f.write_fmt(format_args!("$count as {}", alias)),
+        }
+    }
+}
+
+/// renders a list of aggregations separated by commas
+struct AggregateList<'a>(&'a [Aggregate]);
+
+impl<'a> Display for AggregateList<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, aggregate) in self.0.iter().enumerate() {
+            if index > 0 {
+                f.write_str(",")?;
+            }
+
+            f.write_fmt(format_args!("{}", aggregate))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::query::{
+        apply::{Aggregate, Transformation},
+        attribute::Attribute,
+        filter::Filter,
+    };
+
+    #[test]
+    fn group_without_aggregates() {
+        let transformation = Transformation::GroupBy(vec!["statecode"], Vec::new());
+        assert_eq!(transformation.to_string(), "groupby((statecode))");
+    }
+
+    #[test]
+    fn aggregate_all_records() {
+        let transformation = Transformation::Aggregate(vec![
+            Aggregate::Average("revenue", "average"),
+            Aggregate::Min("revenue", "lowest"),
+            Aggregate::Max("revenue", "highest"),
+            Aggregate::CountDistinct("_ownerid_value", "owners"),
+        ]);
+
+        assert_eq!(
+            transformation.to_string(),
+            "aggregate(revenue with average as average,revenue with min as lowest,revenue with max as highest,_ownerid_value with countdistinct as owners)"
+        );
+    }
+
+    #[test]
+    fn filter_transformation() {
+        let transformation = Transformation::Filter(
+            Filter::GreaterThan("revenue", Attribute::Integer(1000)).and(Filter::Equal("statecode", Attribute::Integer(0))),
+        );
+
+        assert_eq!(transformation.to_string(), "filter(revenue gt 1000 and statecode eq 0)");
+    }
+}
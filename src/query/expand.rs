@@ -0,0 +1,206 @@
+use std::{borrow::Cow, fmt::Display};
+
+use crate::select::Select;
+
+use super::{filter::Filter, order::Order};
+
+/**
+Represents the expansion of a navigation property used in `Query` structures and `Select` implementations
+
+Single-valued navigation properties (lookups) deserialize into an `Option<T>` and
+collection-valued navigation properties deserialize into a `Vec<T>`. The expanded records
+can be restricted with their own `$select`, `$filter`, `$orderby` and `$top` options, where
+`$filter`, `$orderby` and `$top` are only supported by collection-valued navigation properties
+
+# Examples
+```rust
+use serde::Deserialize;
+use uuid::Uuid;
+use powerplatform_dataverse_service_client::{
+    entity::ReadEntity,
+    query::{attribute::Attribute, expand::Expand, filter::Filter, order::Order},
+    select::Select,
+};
+
+#[derive(Deserialize)]
+struct Account {
+    accountid: Uuid,
+    name: String,
+    primarycontactid: Option<Contact>,
+    contact_customer_accounts: Vec<Contact>,
+}
+
+impl ReadEntity for Account {}
+
+impl Select for Account {
+    fn get_columns() -> &'static [&'static str] {
+        &["accountid", "name"]
+    }
+
+    fn get_expands() -> Vec<Expand> {
+        vec![
+            Expand::of::<Contact>("primarycontactid"),
+            Expand::of::<Contact>("contact_customer_accounts")
+                .filter(Filter::Equal("statecode", Attribute::Integer(0)))
+                .order(vec![Order::Ascending("fullname")])
+                .limit(10),
+        ]
+    }
+}
+
+#[derive(Deserialize)]
+struct Contact {
+    contactid: Uuid,
+    fullname: String,
+}
+
+impl Select for Contact {
+    fn get_columns() -> &'static [&'static str] {
+        &["contactid", "fullname"]
+    }
+}
+
+assert_eq!(
+    Account::get_expands()[1].to_string(),
+    "contact_customer_accounts($select=contactid,fullname;$filter=statecode eq 0;$orderby=fullname asc;$top=10)"
+);
+```
+*/
+#[derive(Clone, Debug)]
+pub struct Expand {
+    pub navigation_property: &'static str,
+    pub columns: Cow<'static, [&'static str]>,
+    pub filter: Option<Filter>,
+    pub order: Option<Vec<Order>>,
+    pub limit: Option<u32>,
+    pub expand: Vec<Expand>,
+}
+
+impl Expand {
+    /// Creates an expansion of the given navigation property that selects the given columns (or all if empty)
+    pub fn new(navigation_property: &'static str, columns: &'static [&'static str]) -> Self {
+        Self {
+            navigation_property,
+            columns: Cow::Borrowed(columns),
+            filter: None,
+            order: None,
+            limit: None,
+            expand: Vec::new(),
+        }
+    }
+
+    /**
+    Creates an expansion of the given navigation property that selects the columns
+    and expansions of the given struct
+    */
+    pub fn of<S: Select>(navigation_property: &'static str) -> Self {
+        Self {
+            expand: S::get_expands(),
+            ..Self::new(navigation_property, S::get_columns())
+        }
+    }
+
+    /// filters the expanded records to those that match the predicate defined in the given filter
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// orders the expanded records by the given attributes and directions
+    pub fn order(mut self, order: Vec<Order>) -> Self {
+        self.order = Some(order);
+        self
+    }
+
+    /// limits the expanded records to at most `n` records
+    pub fn limit(mut self, count: u32) -> Self {
+        self.limit = Some(count);
+        self
+    }
+
+    /// expands a navigation property of the expanded records
+    pub fn expand(mut self, expand: Expand) -> Self {
+        self.expand.push(expand);
+        self
+    }
+}
+
+impl Display for Expand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut options = Vec::new();
+
+        if !self.columns.is_empty() {
+            options.push(format!("$select={}", self.columns.join(",")));
+        }
+
+        if let Some(filter) = &self.filter {
+            options.push(format!("$filter={}", filter));
+        }
+
+        if let Some(order) = &self.order {
+            let order: Vec<String> = order.iter().map(|column| column.to_string()).collect();
+            options.push(format!("$orderby={}", order.join(",")));
+        }
+
+        if let Some(limit) = self.limit {
+            options.push(format!("$top={}", limit));
+        }
+
+        if !self.expand.is_empty() {
+            options.push(format!("$expand={}", ExpandList(&self.expand)));
+        }
+
+        if options.is_empty() {
+            f.write_str(self.navigation_property)
+        } else {
+            f.write_fmt(format_args!("{}({})", self.navigation_property, options.join(";")))
+        }
+    }
+}
+
+/// renders a list of expansions separated by commas as used in the `$expand` option
+pub(crate) struct ExpandList<'a>(pub &'a [Expand]);
+
+impl<'a> Display for ExpandList<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, expand) in self.0.iter().enumerate() {
+            if index > 0 {
+                f.write_str(",")?;
+            }
+
+            f.write_fmt(format_args!("{}", expand))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::query::{attribute::Attribute, expand::Expand, filter::Filter, order::Order};
+
+    #[test]
+    fn single_valued_expand() {
+        let expand = Expand::new("parentcustomerid_account", &["accountid", "name"]);
+        assert_eq!(expand.to_string(), "parentcustomerid_account($select=accountid,name)");
+    }
+
+    #[test]
+    fn expand_without_options() {
+        assert_eq!(Expand::new("OptionSet", &[]).to_string(), "OptionSet");
+    }
+
+    #[test]
+    fn nested_expand() {
+        let expand = Expand::new("contact_customer_accounts", &["fullname"])
+            .filter(Filter::Equal("statecode", Attribute::Integer(0)))
+            .order(vec![Order::Ascending("fullname"), Order::Descending("createdon")])
+            .limit(5)
+            .expand(Expand::new("owninguser", &["fullname"]));
+
+        assert_eq!(
+            expand.to_string(),
+            "contact_customer_accounts($select=fullname;$filter=statecode eq 0;$orderby=fullname asc,createdon desc;$top=5;$expand=owninguser($select=fullname))"
+        );
+    }
+}
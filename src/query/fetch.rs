@@ -0,0 +1,268 @@
+use std::fmt::Display;
+
+use crate::reference::encode_path;
+
+/**
+Represents a query written in FetchXML for use in `Client::retrieve_fetchxml(...)`
+
+FetchXML covers queries OData can't express, like outer joins, filters on linked
+tables, distinct aggregates and `in` conditions with many values. The FetchXML is
+sent as it is, so the columns of the `Select` implementation of the retrieved struct
+are not applied. Columns of linked tables are returned with the alias of the
+`link-entity` as prefix, e.g. `contact.fullname`
+
+The FetchXML references the table by its logical name while the url of the request
+needs the entity set name, so both have to be given
+
+The page size is controlled by the `count` attribute of the `fetch` element. Further
+pages are requested with the `page` and `paging-cookie` attributes, which are set by
+the client automatically
+
+# Examples
+```rust
+use powerplatform_dataverse_service_client::query::fetch::FetchXml;
+
+let fetch = FetchXml::new("accounts", r#"
+<fetch count="500">
+  <entity name="account">
+    <attribute name="name" />
+    <link-entity name="contact" from="parentcustomerid" to="accountid" link-type="outer" alias="contact">
+      <attribute name="fullname" />
+    </link-entity>
+  </entity>
+</fetch>"#);
+
+assert!(fetch.to_string().starts_with("accounts?fetchXml=%0A%3Cfetch%20count%3D%22500%22%3E"));
+```
+*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FetchXml {
+    pub entity_set: &'static str,
+    pub xml: String,
+}
+
+impl FetchXml {
+    /// Creates a query with the given FetchXML against the given entity set
+    pub fn new(entity_set: &'static str, xml: impl Into<String>) -> Self {
+        Self {
+            entity_set,
+            xml: xml.into(),
+        }
+    }
+}
+
+impl Display for FetchXml {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{}?fetchXml={}", self.entity_set, encode_path(&self.xml)))
+    }
+}
+
+/**
+builds the FetchXML of the page after the page requested with the given FetchXML
+
+`cookie` is the value of the `@Microsoft.Dynamics.CRM.fetchxmlpagingcookie` annotation.
+Without a cookie only the page number is advanced, which dataverse requires for
+queries it can't page with a cookie (e.g. ordered by columns of linked tables)
+*/
+pub(crate) fn next_page(xml: &str, cookie: Option<&str>) -> Option<String> {
+    let mut fetch = Element::find(xml, "fetch")?;
+    let page = match fetch.attribute("page") {
+        Some(page) => page.trim().parse::<u32>().ok()?,
+        None => 1,
+    };
+
+    fetch.set_attribute("page", (page + 1).to_string());
+    match cookie.and_then(read_paging_cookie) {
+        Some(paging_cookie) => fetch.set_attribute("paging-cookie", paging_cookie),
+        None => fetch.remove_attribute("paging-cookie"),
+    }
+
+    Some(format!("{}{}{}", &xml[..fetch.start], fetch, &xml[fetch.end..]))
+}
+
+/// returns the paging cookie a FetchXML was sent with
+pub(crate) fn paging_cookie(xml: &str) -> Option<String> {
+    Element::find(xml, "fetch")?.attribute("paging-cookie")
+}
+
+/**
+reads the paging cookie from the `@Microsoft.Dynamics.CRM.fetchxmlpagingcookie` annotation
+
+The annotation is a `cookie` element whose `pagingcookie` attribute contains the actual
+cookie encoded twice, e.g. `<cookie pagenumber="2" pagingcookie="%253ccookie%2520page..." />`
+*/
+fn read_paging_cookie(annotation: &str) -> Option<String> {
+    let encoded = Element::find(annotation, "cookie")?.attribute("pagingcookie")?;
+    Some(percent_decode(&percent_decode(&encoded)))
+}
+
+/// the start tag of an xml element with its raw attributes
+struct Element<'a> {
+    name: &'a str,
+    start: usize,
+    end: usize,
+    attributes: Vec<(&'a str, char, String)>,
+    closing: &'a str,
+}
+
+impl<'a> Element<'a> {
+    /// finds the first start tag of the element with the given name
+    fn find(xml: &'a str, name: &'a str) -> Option<Self> {
+        let open = format!("<{}", name);
+        let mut offset = 0;
+
+        let start = loop {
+            let position = offset + xml[offset..].find(&open)?;
+            let next = xml[position + open.len()..].chars().next()?;
+
+            if next.is_whitespace() || next == '>' || next == '/' {
+                break position;
+            }
+
+            offset = position + open.len();
+        };
+
+        let mut attributes = Vec::new();
+        let mut position = start + open.len();
+
+        loop {
+            let rest = &xml[position..];
+            let trimmed = rest.trim_start();
+            position += rest.len() - trimmed.len();
+
+            if trimmed.starts_with("/>") || trimmed.starts_with('>') {
+                let closing_length = if trimmed.starts_with('>') { 1 } else { 2 };
+
+                return Some(Self {
+                    name,
+                    start,
+                    end: position + closing_length,
+                    attributes,
+                    closing: &xml[position..position + closing_length],
+                });
+            }
+
+            let name_length = trimmed.find(|c: char| c == '=' || c.is_whitespace())?;
+            let attribute_name = &trimmed[..name_length];
+            let value = trimmed[name_length..].trim_start().strip_prefix('=')?.trim_start();
+            let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+            let value_length = value[1..].find(quote)?;
+
+            attributes.push((attribute_name, quote, String::from(&value[1..1 + value_length])));
+            position = xml.len() - value.len() + value_length + 2;
+        }
+    }
+
+    /// returns the unescaped value of the given attribute
+    fn attribute(&self, name: &str) -> Option<String> {
+        self.attributes
+            .iter()
+            .find(|(attribute, _, _)| *attribute == name)
+            .map(|(_, _, value)| unescape_xml(value))
+    }
+
+    fn set_attribute(&mut self, name: &'a str, value: String) {
+        let value = escape_xml(&value);
+
+        match self.attributes.iter_mut().find(|(attribute, _, _)| *attribute == name) {
+            Some(attribute) => *attribute = (name, '"', value),
+            None => self.attributes.push((name, '"', value)),
+        }
+    }
+
+    fn remove_attribute(&mut self, name: &str) {
+        self.attributes.retain(|(attribute, _, _)| *attribute != name);
+    }
+}
+
+impl<'a> Display for Element<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("<{}", self.name))?;
+
+        for (name, quote, value) in &self.attributes {
+            f.write_fmt(format_args!(" {}={}{}{}", name, quote, value, quote))?;
+        }
+
+        f.write_str(self.closing)
+    }
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn unescape_xml(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let escaped = match bytes.get(index..index + 3) {
+            Some([b'%', high, low]) => std::str::from_utf8(&[*high, *low])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::query::fetch::{next_page, paging_cookie, FetchXml};
+
+    #[test]
+    fn renders_the_encoded_fetch() {
+        let fetch = FetchXml::new("accounts", r#"<fetch><entity name="account" /></fetch>"#);
+        assert_eq!(
+            fetch.to_string(),
+            "accounts?fetchXml=%3Cfetch%3E%3Centity%20name%3D%22account%22%20%2F%3E%3C%2Ffetch%3E"
+        );
+    }
+
+    #[test]
+    fn advances_pages_with_the_paging_cookie() {
+        let cookie = r#"<cookie pagenumber="2" pagingcookie="%253ccookie%2520page%253d%25221%2522%253e%253caccountid%2520last%253d%2522%257b1%257d%2522%2520first%253d%2522%257b0%257d%2522%2520%252f%253e%253c%252fcookie%253e" istracking="False" />"#;
+        let xml = "<?xml version='1.0'?>\n<fetch count='2'>\n  <entity name='account' />\n</fetch>";
+
+        let second = next_page(xml, Some(cookie)).unwrap();
+        assert_eq!(
+            second,
+            "<?xml version='1.0'?>\n<fetch count='2' page=\"2\" paging-cookie=\"&lt;cookie page=&quot;1&quot;&gt;&lt;accountid last=&quot;{1}&quot; first=&quot;{0}&quot; /&gt;&lt;/cookie&gt;\">\n  <entity name='account' />\n</fetch>"
+        );
+        assert_eq!(
+            paging_cookie(&second).as_deref(),
+            Some(r#"<cookie page="1"><accountid last="{1}" first="{0}" /></cookie>"#)
+        );
+
+        let third = next_page(&second, None).unwrap();
+        assert_eq!(third, "<?xml version='1.0'?>\n<fetch count='2' page=\"3\">\n  <entity name='account' />\n</fetch>");
+        assert!(next_page("<entity name='account' />", None).is_none());
+    }
+}
@@ -52,9 +52,12 @@ impl Select for Contact {
 
 use std::fmt::Display;
 
-use self::{filter::Filter, order::Order};
+use self::{apply::Transformation, expand::Expand, filter::Filter, order::Order};
 
+pub mod apply;
 pub mod attribute;
+pub mod expand;
+pub mod fetch;
 pub mod filter;
 pub mod order;
 
@@ -115,6 +118,10 @@ pub struct Query {
     pub limit: Option<u32>,
     pub filter: Option<Filter>,
     pub order: Option<Vec<Order>>,
+    pub max_page_size: Option<u32>,
+    pub expand: Vec<Expand>,
+    pub apply: Vec<Transformation>,
+    pub count: bool,
 }
 
 impl Query {
@@ -133,6 +140,10 @@ impl Query {
             limit: None,
             filter: None,
             order: None,
+            max_page_size: None,
+            expand: Vec::new(),
+            apply: Vec::new(),
+            count: false,
         }
     }
 
@@ -154,6 +165,51 @@ impl Query {
         self.order = Some(order);
         self
     }
+
+    /**
+    requests pages of at most `n` entities from the server
+
+    Unlike `limit(...)` this does not restrict the total number of entities. It is sent
+    as `Prefer: odata.maxpagesize=n` header and not as part of the query string
+    */
+    pub fn max_page_size(mut self, count: u32) -> Self {
+        self.max_page_size = Some(count);
+        self
+    }
+
+    /**
+    expands the given navigation property in addition to the expansions of the `Select` implementation
+
+    Like `$select` the `$expand` option is added by the client when the query is executed,
+    so it is not part of the rendered query
+    */
+    pub fn expand(mut self, expand: Expand) -> Self {
+        self.expand.push(expand);
+        self
+    }
+
+    /**
+    appends the given transformation to the `$apply` option of this query
+
+    Queries with transformations return the aggregated results instead of entities,
+    so neither `$select` nor `$expand` are added when they are executed. Use
+    `Client::aggregate(...)` to retrieve them
+    */
+    pub fn apply(mut self, transformation: Transformation) -> Self {
+        self.apply.push(transformation);
+        self
+    }
+
+    /**
+    requests the total number of matching records with `$count=true`
+
+    The number is returned in `Page::total_count`. Dataverse counts at most 5000 records,
+    larger results are reported with a count of 5000 and `limit_exceeded` set
+    */
+    pub fn with_count(mut self) -> Self {
+        self.count = true;
+        self
+    }
 }
 
 impl Display for Query {
@@ -161,6 +217,19 @@ impl Display for Query {
         let mut first_item = true;
         f.write_str(self.logical_name)?;
 
+        if !self.apply.is_empty() {
+            f.write_str("?$apply=")?;
+            first_item = false;
+
+            for (index, transformation) in self.apply.iter().enumerate() {
+                if index > 0 {
+                    f.write_str("/")?;
+                }
+
+                f.write_fmt(format_args!("{}", transformation))?;
+            }
+        }
+
         if let Some(limit) = self.limit {
             if first_item {
                 f.write_str("?")?;
@@ -186,6 +255,7 @@ impl Display for Query {
         if let Some(order) = &self.order {
             if first_item {
                 f.write_str("?")?;
+                first_item = false;
             } else {
                 f.write_str("&")?;
             }
@@ -205,13 +275,18 @@ impl Display for Query {
             }
         }
 
+        if self.count {
+            f.write_str(if first_item { "?" } else { "&" })?;
+            f.write_str("$count=true")?;
+        }
+
         Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::query::{attribute::Attribute, Filter, Order, Query};
+    use crate::query::{apply::{Aggregate, Transformation}, attribute::Attribute, Filter, Order, Query};
 
     #[test]
     fn empty_query() {
@@ -257,4 +332,26 @@ mod tests {
             "testy?$top=5&$filter=name eq 'Testface'&$orderby=name asc,rank desc"
         );
     }
+
+    #[test]
+    fn apply_query() {
+        let query: Query = Query::new("testy")
+            .apply(Transformation::GroupBy(vec!["statecode"], vec![Aggregate::Count("count")]))
+            .order(vec![Order::Descending("count")]);
+        assert_eq!(query.to_string(), "testy?$apply=groupby((statecode),aggregate($count as count))&$orderby=count desc");
+    }
+
+    #[test]
+    fn count_query() {
+        assert_eq!(Query::new("testy").with_count().to_string(), "testy?$count=true");
+
+        let query: Query = Query::new("testy").limit(5).with_count();
+        assert_eq!(query.to_string(), "testy?$top=5&$count=true");
+    }
+
+    #[test]
+    fn max_page_size_is_not_part_of_the_query() {
+        let query: Query = Query::new("testy").limit(5).max_page_size(2);
+        assert_eq!(query.to_string(), "testy?$top=5");
+    }
 }
@@ -1,8 +1,11 @@
 use std::fmt::Display;
 
+use chrono::SecondsFormat;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::query::attribute::Attribute;
+
 /**
 trait for getting a reference to an entity record from a struct
 */
@@ -45,3 +48,239 @@ impl Reference for ReferenceStruct {
         *self
     }
 }
+
+/**
+trait for getting the key of an entity record from a struct
+
+Every struct implementing `Reference` automatically implements this trait by addressing
+the record with its primary id. Implement it directly for structs that are addressed
+by an alternate key instead
+
+# Examples
+```rust
+use serde::Serialize;
+use powerplatform_dataverse_service_client::{
+    entity::WriteEntity,
+    query::attribute::Attribute,
+    reference::{Addressable, KeyReference}
+};
+
+#[derive(Serialize)]
+struct Account {
+    accountnumber: String,
+    name: String,
+}
+
+impl WriteEntity for Account {}
+
+impl Addressable for Account {
+    fn get_key_reference(&self) -> KeyReference {
+        KeyReference::alternate(
+            "accounts",
+            vec![("accountnumber", Attribute::String(self.accountnumber.clone()))],
+        )
+    }
+}
+```
+*/
+pub trait Addressable {
+    /// creates a KeyReference structure pointing to the entity record in Microsoft Dataverse
+    fn get_key_reference(&self) -> KeyReference;
+}
+
+impl<R: Reference> Addressable for R {
+    fn get_key_reference(&self) -> KeyReference {
+        KeyReference::from(self.get_reference())
+    }
+}
+
+/**
+the key of an entity record which is either its primary id or a set of alternate key values
+*/
+#[derive(Clone, Debug, PartialEq)]
+pub enum RecordKey {
+    /// Indicates the primary id of the record
+    Id(Uuid),
+
+    /// Indicates the values of an alternate key, with more than one value for compound keys
+    Alternate(Vec<(&'static str, Attribute)>),
+}
+
+impl Display for RecordKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordKey::Id(entity_id) => f.write_fmt(format_args!("({})", entity_id.as_hyphenated())),
+            RecordKey::Alternate(values) => {
+                f.write_str("(")?;
+
+                for (index, (name, value)) in values.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(",")?;
+                    }
+
+                    f.write_fmt(format_args!("{}={}", name, KeyLiteral(value)))?;
+                }
+
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+/**
+a reference to an entity record that is addressed either by its primary id or by an alternate key
+
+Its `Display` implementation renders the url segment of the record like
+`accounts(accountnumber='A-100')`
+
+# Examples
+```rust
+use uuid::Uuid;
+use powerplatform_dataverse_service_client::{
+    query::attribute::Attribute,
+    reference::KeyReference
+};
+
+let by_id = KeyReference::id("accounts", Uuid::nil());
+assert_eq!(by_id.to_string(), "accounts(00000000-0000-0000-0000-000000000000)");
+
+let by_key = KeyReference::alternate(
+    "accounts",
+    vec![
+        ("accountnumber", Attribute::String(String::from("A-100"))),
+        ("region", Attribute::Integer(3)),
+    ],
+);
+assert_eq!(by_key.to_string(), "accounts(accountnumber='A-100',region=3)");
+```
+*/
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyReference {
+    pub entity_name: &'static str,
+    pub key: RecordKey,
+}
+
+impl KeyReference {
+    /// creates a reference to the record with the given primary id
+    pub fn id(entity_name: &'static str, entity_id: Uuid) -> Self {
+        Self {
+            entity_name,
+            key: RecordKey::Id(entity_id),
+        }
+    }
+
+    /// creates a reference to the record with the given alternate key values
+    pub fn alternate(entity_name: &'static str, values: Vec<(&'static str, Attribute)>) -> Self {
+        Self {
+            entity_name,
+            key: RecordKey::Alternate(values),
+        }
+    }
+
+    /// returns the primary id if the record is addressed by it
+    pub fn entity_id(&self) -> Option<Uuid> {
+        match self.key {
+            RecordKey::Id(entity_id) => Some(entity_id),
+            RecordKey::Alternate(_) => None,
+        }
+    }
+}
+
+impl From<ReferenceStruct> for KeyReference {
+    fn from(reference: ReferenceStruct) -> Self {
+        KeyReference::id(reference.entity_name, reference.entity_id)
+    }
+}
+
+impl Display for KeyReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{}{}", self.entity_name, self.key))
+    }
+}
+
+impl Addressable for KeyReference {
+    fn get_key_reference(&self) -> KeyReference {
+        self.clone()
+    }
+}
+
+/// renders an attribute as OData key literal that can be placed into an url path
+pub(crate) struct KeyLiteral<'a>(pub &'a Attribute);
+
+impl<'a> Display for KeyLiteral<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Attribute::Null => f.write_str("null"),
+            Attribute::Boolean(value) => f.write_fmt(format_args!("{}", value)),
+            Attribute::Integer(value) => f.write_fmt(format_args!("{}", value)),
+            Attribute::Decimal(value) => f.write_fmt(format_args!("{}", value)),
+            Attribute::Uuid(value) => f.write_fmt(format_args!("{}", value.as_hyphenated())),
+            Attribute::DateTime(value) => f.write_str(&encode_path(&value.to_rfc3339_opts(SecondsFormat::Secs, true))),
+            Attribute::String(value) => f.write_fmt(format_args!("'{}'", encode_path(&value.replace('\'', "''")))),
+        }
+    }
+}
+
+/// percent-encodes everything except unreserved characters and single quotes
+pub(crate) fn encode_path(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'\'' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::{
+        query::attribute::Attribute,
+        reference::{Addressable, KeyReference, ReferenceStruct},
+    };
+
+    #[test]
+    fn primary_id() {
+        let reference = ReferenceStruct::new("contacts", Uuid::nil()).get_key_reference();
+        assert_eq!(reference.to_string(), "contacts(00000000-0000-0000-0000-000000000000)");
+        assert_eq!(reference.entity_id(), Some(Uuid::nil()));
+    }
+
+    #[test]
+    fn compound_alternate_key() {
+        let reference = KeyReference::alternate(
+            "new_contracts",
+            vec![
+                ("new_customer", Attribute::Uuid(Uuid::nil())),
+                ("new_number", Attribute::Integer(7)),
+                ("new_active", Attribute::Boolean(true)),
+            ],
+        );
+
+        assert_eq!(
+            reference.to_string(),
+            "new_contracts(new_customer=00000000-0000-0000-0000-000000000000,new_number=7,new_active=true)"
+        );
+        assert_eq!(reference.entity_id(), None);
+    }
+
+    #[test]
+    fn escapes_string_literals() {
+        let reference = KeyReference::alternate(
+            "accounts",
+            vec![("accountnumber", Attribute::String(String::from("O'Neil & Sons/#1 50%")))],
+        );
+
+        assert_eq!(
+            reference.to_string(),
+            "accounts(accountnumber='O''Neil%20%26%20Sons%2F%231%2050%25')"
+        );
+    }
+}
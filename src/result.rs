@@ -1,15 +1,32 @@
-use std::fmt::Display;
+use std::error::Error;
 
 use crate::error::DataverseError;
 
 pub type Result<T> = std::result::Result<T, DataverseError>;
 
+/**
+Converts results into results of this crate, keeping the original error as source
+
+Only errors implementing `std::error::Error` can be converted this way since version 0.3.
+Errors that only implement `Display` (e.g. `String`) are converted with
+`map_err(DataverseError::from_display)` instead
+
+# Examples
+```rust
+use powerplatform_dataverse_service_client::{error::DataverseError, result::Result};
+
+fn parse_region(region: &str) -> Result<u8> {
+    let parsed: std::result::Result<u8, String> = region.parse().map_err(|_| format!("unknown region '{}'", region));
+    parsed.map_err(DataverseError::from_display)
+}
+```
+*/
 pub trait IntoDataverseResult<T> {
     fn into_dataverse_result(self) -> Result<T>;
 }
 
-impl<T, E: Display> IntoDataverseResult<T> for core::result::Result<T, E> {
+impl<T, E: Error + Send + Sync + 'static> IntoDataverseResult<T> for core::result::Result<T, E> {
     fn into_dataverse_result(self) -> Result<T> {
-        self.map_err(|error| DataverseError::new(error.to_string()))
+        self.map_err(DataverseError::from_source)
     }
 }
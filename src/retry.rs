@@ -0,0 +1,223 @@
+/*!
+Module for retrying requests that were rejected by Microsoft Dataverse
+
+Dataverse enforces service protection limits and answers with `429 Too Many Requests`
+(or `503 Service Unavailable`) together with a `Retry-After` header when a client
+sends too many requests. A `RetryPolicy` describes how a client reacts to these
+responses and to other transient failures.
+
+# Examples
+```rust
+use std::time::Duration;
+use powerplatform_dataverse_service_client::{client::Client, retry::RetryPolicy};
+
+let client = Client::new_dummy().with_retry_policy(
+    RetryPolicy::new()
+        .max_attempts(8)
+        .base_delay(Duration::from_millis(500))
+        .max_delay(Duration::from_secs(30)),
+);
+```
+*/
+
+use std::future::Future;
+use std::time::{Duration, SystemTime};
+
+use chrono::DateTime;
+use reqwest::{header::RETRY_AFTER, Method, RequestBuilder, Response, StatusCode};
+use uuid::Uuid;
+
+use crate::result::{IntoDataverseResult, Result};
+
+/**
+Describes how often and how long a client waits before a failed request is sent again
+
+Requests are retried when:
+- Dataverse answered with `429 Too Many Requests`
+- Dataverse answered with `503 Service Unavailable` and a `Retry-After` header
+- the connection to Dataverse could not be established
+
+These responses guarantee that the request has not been processed, so they are
+retried for every http method. Other transient failures (`502`, `503` and `504`
+without `Retry-After`, timeouts) may happen after the request was processed, so they
+are only retried for idempotent methods (`GET`, `HEAD`, `OPTIONS`, `PUT`, `DELETE`)
+unless `retry_non_idempotent(true)` is set
+
+The delay between two attempts is taken from the `Retry-After` header when the server
+provides one. Otherwise an exponential backoff with full jitter is used
+*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub retry_non_idempotent: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            retry_non_idempotent: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Creates the default retry policy with 5 attempts and a backoff between 1 and 60 seconds
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a retry policy that sends every request exactly once
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// sets the maximum number of attempts including the first one
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// sets the delay the exponential backoff starts with
+    pub fn base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    /// sets the upper bound of the exponential backoff
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// allows retrying transient failures for non-idempotent methods like `POST` and `PATCH`
+    pub fn retry_non_idempotent(mut self, retry_non_idempotent: bool) -> Self {
+        self.retry_non_idempotent = retry_non_idempotent;
+        self
+    }
+
+    /// Indicates if transient failures of requests with the given method may be retried
+    pub fn is_idempotent(&self, method: &Method) -> bool {
+        self.retry_non_idempotent
+            || matches!(
+                *method,
+                Method::GET | Method::HEAD | Method::OPTIONS | Method::PUT | Method::DELETE
+            )
+    }
+
+    /**
+    Sends the request produced by `prepare` until it succeeds, fails permanently
+    or the maximum number of attempts is reached
+
+    `prepare` is called once per attempt so it can acquire a fresh token each time.
+    The last response is returned even if it indicates an error, so the caller can
+    turn it into a meaningful error message
+    */
+    pub(crate) async fn send<F, Fut>(&self, idempotent: bool, mut prepare: F) -> Result<Response>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<RequestBuilder>>,
+    {
+        let mut attempt = 1;
+
+        loop {
+            let result = prepare().await?.send().await;
+            let last_attempt = attempt >= self.max_attempts;
+
+            let delay = match &result {
+                Ok(response) if !last_attempt => self.delay_for_response(response, idempotent, attempt),
+                Err(error) if !last_attempt && (error.is_connect() || (idempotent && (error.is_timeout() || error.is_request()))) => {
+                    Some(self.backoff(attempt))
+                }
+                _ => None,
+            };
+
+            match delay {
+                Some(delay) => tokio::time::sleep(delay).await,
+                None => return result.into_dataverse_result(),
+            }
+
+            attempt += 1;
+        }
+    }
+
+    fn delay_for_response(&self, response: &Response, idempotent: bool, attempt: u32) -> Option<Duration> {
+        let retry_after = parse_retry_after(response);
+
+        match response.status() {
+            StatusCode::TOO_MANY_REQUESTS => Some(retry_after.unwrap_or_else(|| self.backoff(attempt))),
+            StatusCode::SERVICE_UNAVAILABLE if retry_after.is_some() => retry_after,
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+                if idempotent =>
+            {
+                Some(self.backoff(attempt))
+            }
+            _ => None,
+        }
+    }
+
+    /// exponential backoff with full jitter for the given attempt (starting at 1)
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let ceiling = self
+            .base_delay
+            .saturating_mul(1 << exponent)
+            .min(self.max_delay);
+
+        let jitter = (Uuid::new_v4().as_u128() % 1_000_001) as f64 / 1_000_000.0;
+        ceiling.mul_f64(jitter)
+    }
+}
+
+/// reads the `Retry-After` header which is either given in seconds or as an http date
+fn parse_retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        SystemTime::from(date)
+            .duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use reqwest::Method;
+
+    use super::RetryPolicy;
+
+    #[test]
+    fn backoff_is_bounded() {
+        let policy = RetryPolicy::new()
+            .base_delay(Duration::from_millis(100))
+            .max_delay(Duration::from_secs(1));
+
+        for attempt in 1..40 {
+            assert!(policy.backoff(attempt) <= Duration::from_secs(1));
+        }
+    }
+
+    #[test]
+    fn idempotent_methods() {
+        let policy = RetryPolicy::new();
+        assert!(policy.is_idempotent(&Method::GET));
+        assert!(policy.is_idempotent(&Method::DELETE));
+        assert!(!policy.is_idempotent(&Method::POST));
+        assert!(!policy.is_idempotent(&Method::PATCH));
+        assert!(policy.retry_non_idempotent(true).is_idempotent(&Method::POST));
+    }
+}
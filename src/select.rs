@@ -1,3 +1,5 @@
+use crate::query::expand::Expand;
+
 /**
 trait for acquiring the relevant attribute names for queries
 */
@@ -5,4 +7,14 @@ pub trait Select {
     /// gets a vector of attribute names that shall be included in
     /// the query select statement
     fn get_columns() -> &'static [&'static str];
+
+    /**
+    gets the navigation properties that shall be expanded together with the attributes
+
+    Override this for structs that hold related records in `Option<T>` or `Vec<T>` fields,
+    see `Expand` for an example. By default nothing is expanded
+    */
+    fn get_expands() -> Vec<Expand> {
+        Vec::new()
+    }
 }
@@ -0,0 +1,91 @@
+/*!
+Module for streaming the entities of a query across all of its pages
+
+See `Client::retrieve_stream(...)` for how to create an `EntityStream`
+*/
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::Stream;
+
+use crate::{
+    auth::Authenticate,
+    client::{Client, Page, PageCursor},
+    entity::ReadEntity,
+    result::Result,
+};
+
+type PageFuture<'a, E> = Pin<Box<dyn Future<Output = Result<Page<E>>> + Send + 'a>>;
+
+/**
+A stream of entities that follows the `@odata.nextLink` of each page automatically
+
+The request for the next page is started as soon as a page arrives and is driven
+while the entities of the current page are consumed. The stream ends after the
+last page or after the first error
+*/
+pub struct EntityStream<'a, E> {
+    fetch_page: Box<dyn Fn(PageCursor) -> PageFuture<'a, E> + Send + 'a>,
+    entities: std::vec::IntoIter<E>,
+    pending: Option<PageFuture<'a, E>>,
+    prefetched: Option<Result<Page<E>>>,
+}
+
+impl<'a, E: ReadEntity + Send + 'a> EntityStream<'a, E> {
+    pub(crate) fn new<A: Authenticate + Send + Sync>(client: &'a Client<'_, A>, cursor: PageCursor) -> Self {
+        let fetch_page: Box<dyn Fn(PageCursor) -> PageFuture<'a, E> + Send + 'a> =
+            Box::new(move |cursor| Box::pin(client.retrieve_page(cursor)));
+        let pending = Some(fetch_page(cursor));
+
+        Self {
+            fetch_page,
+            entities: Vec::new().into_iter(),
+            pending,
+            prefetched: None,
+        }
+    }
+}
+
+// the entities are never pinned and the page requests are boxed
+impl<'a, E> Unpin for EntityStream<'a, E> {}
+
+impl<'a, E> Stream for EntityStream<'a, E> {
+    type Item = Result<E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            if let Some(pending) = this.pending.as_mut() {
+                if let Poll::Ready(page) = pending.as_mut().poll(cx) {
+                    this.pending = None;
+                    this.prefetched = Some(page);
+                }
+            }
+
+            if let Some(entity) = this.entities.next() {
+                return Poll::Ready(Some(Ok(entity)));
+            }
+
+            match this.prefetched.take() {
+                Some(Ok(page)) => {
+                    if let Some(cursor) = page.cursor() {
+                        this.pending = Some((this.fetch_page)(cursor));
+                    }
+
+                    this.entities = page.into_inner().into_iter();
+                }
+                Some(Err(error)) => {
+                    this.pending = None;
+                    return Poll::Ready(Some(Err(error)));
+                }
+                None if this.pending.is_some() => return Poll::Pending,
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
@@ -0,0 +1,201 @@
+/*!
+Minimal local http stand-in for Microsoft Dataverse used by the unit tests
+
+The server answers each incoming connection with the next canned response and
+records the requests it received so tests can assert on them
+*/
+
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::{auth::Authenticate, result::Result};
+
+/// Authenticates every request with the same static token
+pub struct StaticAuth;
+
+#[async_trait]
+impl Authenticate for StaticAuth {
+    async fn get_valid_token(&self) -> Result<Arc<String>> {
+        Ok(Arc::new(String::from("test-token")))
+    }
+}
+
+/// A request as it was received by the `TestServer`
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl RecordedRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn body_text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+/// A response the `TestServer` sends back
+#[derive(Clone, Debug)]
+pub struct CannedResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl CannedResponse {
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn json(self, body: &str) -> Self {
+        self.body(body.as_bytes().to_vec(), "application/json")
+    }
+
+    pub fn body(mut self, body: Vec<u8>, content_type: &str) -> Self {
+        self.body = body;
+        self.header("Content-Type", content_type)
+    }
+}
+
+/**
+Local http server that answers with canned responses in order
+
+The placeholder `{server}` in a response body is replaced with the url of the server
+*/
+pub struct TestServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+impl TestServer {
+    pub async fn start(responses: Vec<CannedResponse>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&requests);
+        let responses: Vec<CannedResponse> = responses
+            .into_iter()
+            .map(|mut response| {
+                if let Ok(body) = std::str::from_utf8(&response.body) {
+                    response.body = body.replace("{server}", &url).into_bytes();
+                }
+
+                response
+            })
+            .collect();
+
+        tokio::spawn(async move {
+            let mut responses = responses.into_iter();
+
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let request = match read_request(&mut stream).await {
+                    Some(request) => request,
+                    None => continue,
+                };
+
+                recorded.lock().unwrap().push(request);
+                let response = responses.next().unwrap_or_else(|| {
+                    CannedResponse::new(500).json(r#"{"error":{"code":"test","message":"no canned response left"}}"#)
+                });
+
+                write_response(&mut stream, response).await;
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+async fn read_request(stream: &mut TcpStream) -> Option<RecordedRequest> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    let head_end = loop {
+        let read = stream.read(&mut chunk).await.ok()?;
+        if read == 0 {
+            return None;
+        }
+
+        buffer.extend_from_slice(&chunk[..read]);
+        if let Some(position) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            break position;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..head_end]).into_owned();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split(' ');
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect();
+
+    let content_length = headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.parse::<usize>().ok())
+        .unwrap_or(0);
+
+    let mut body = buffer[head_end + 4..].to_vec();
+    while body.len() < content_length {
+        let read = stream.read(&mut chunk).await.ok()?;
+        if read == 0 {
+            break;
+        }
+
+        body.extend_from_slice(&chunk[..read]);
+    }
+
+    Some(RecordedRequest {
+        method,
+        path,
+        headers,
+        body,
+    })
+}
+
+async fn write_response(stream: &mut TcpStream, response: CannedResponse) {
+    let mut head = format!(
+        "HTTP/1.1 {} Canned\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        response.body.len()
+    );
+
+    for (key, value) in response.headers {
+        head.push_str(&format!("{}: {}\r\n", key, value));
+    }
+
+    head.push_str("\r\n");
+    let _ = stream.write_all(head.as_bytes()).await;
+    let _ = stream.write_all(&response.body).await;
+    let _ = stream.shutdown().await;
+}